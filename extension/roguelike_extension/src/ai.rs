@@ -0,0 +1,551 @@
+//! 敵の行動を決める思考ルーチンを定義するモジュール
+//!
+//! 思考ルーチンは`MobAi`トレイトを実装し、敵ごとの状態(`MobState`)を更新しながら
+//! そのターンの行動(`MobAction`)を返す。どの思考ルーチンを使うかは種類ごとに`MobBehavior`で決める。
+
+use crate::fov::FieldOfView;
+use crate::mob::GameMob;
+use crate::pathfinding::DijkstraMap;
+use crate::pathfinding::PathFinder;
+use crate::player::Direction;
+use crate::projectile::has_line_of_fire;
+use crate::tile::Tile;
+
+use rand::Rng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+/// 思考ルーチンの種類、敵の種類ごとにカタログで指定する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MobBehavior {
+    /// プレイヤーに気づいたら追いかける
+    #[default]
+    Chase,
+    /// プレイヤーに気づくまでうろつく
+    Wander,
+    /// プレイヤーに気づいて目を覚ますまで動かない
+    Sleep,
+    /// 追いかけるが、HPが減ると逃げる
+    Flee,
+    /// プレイヤーと一定の距離を保つ
+    KeepDistance,
+    /// ゴールの近くを守り、近づいたプレイヤーだけを追いかける
+    Guard,
+}
+
+impl MobBehavior {
+    /// 種類に対応する思考ルーチン
+    pub fn ai(&self) -> &'static dyn MobAi {
+        match self {
+            MobBehavior::Chase => &ChaseAi { notice_radius: 8 },
+            MobBehavior::Wander => &WanderAi { notice_radius: 6 },
+            MobBehavior::Sleep => &SleepAi { notice_radius: 4, wake_rate: 0.5 },
+            MobBehavior::Flee => &FleeAi { notice_radius: 8, flee_hp_rate: 0.3 },
+            MobBehavior::KeepDistance => &KeepDistanceAi { notice_radius: 8, preferred_distance: 3 },
+            MobBehavior::Guard => &GuardAi { guard_radius: 5 },
+        }
+    }
+
+    /// 生成されたときの状態
+    pub fn initial_state(&self) -> MobState {
+        match self {
+            MobBehavior::Wander => MobState::Wandering,
+            MobBehavior::Sleep => MobState::Sleeping,
+            MobBehavior::Guard => MobState::Guarding,
+            _ => MobState::Idle,
+        }
+    }
+}
+
+/// 敵ごとの思考の状態、ターンをまたいで保持する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MobState {
+    /// プレイヤーに気づいておらず、その場で待機している
+    #[default]
+    Idle,
+    /// 眠っている
+    Sleeping,
+    /// うろついている
+    Wandering,
+    /// 最後にプレイヤーを見た位置に向かっている
+    Chasing { target: (i32, i32) },
+    /// プレイヤーから逃げている
+    Fleeing,
+    /// 持ち場を守っている
+    Guarding,
+}
+
+/// そのターンの敵の行動
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MobAction {
+    /// 何もしない
+    Wait,
+    /// 隣のマスに移動する、他の敵と重なる場合は移動できないことがある
+    Move((i32, i32)),
+    /// 指定した向きに攻撃する
+    Attack(Direction),
+    /// 指定したマスに向けて飛び道具を放つ
+    Shoot((i32, i32)),
+}
+
+/// 思考ルーチンが参照するマップとプレイヤーの情報
+pub struct AiContext<'a> {
+    /// 静的マップ
+    pub map: &'a [Vec<Tile>],
+    /// プレイヤーの位置
+    pub player_position: (i32, i32),
+    /// ゴールの位置
+    pub goal_position: (i32, i32),
+    /// プレイヤーの視界、視線は対称とみなして敵からプレイヤーが見えるかの判定にも使う
+    pub player_view: &'a FieldOfView,
+    /// 経路探索の設定
+    pub path_finder: PathFinder,
+    /// プレイヤーまでの距離
+    pub to_player: DijkstraMap,
+}
+
+impl<'a> AiContext<'a> {
+    /// プレイヤーまでの距離を求めてコンテキストを作る
+    pub fn new(
+        map: &'a [Vec<Tile>],
+        player_position: (i32, i32),
+        goal_position: (i32, i32),
+        player_view: &'a FieldOfView,
+        path_finder: PathFinder,
+    ) -> Self {
+        let to_player = path_finder.dijkstra_map(map, &[player_position]);
+        Self {
+            map,
+            player_position,
+            goal_position,
+            player_view,
+            path_finder,
+            to_player,
+        }
+    }
+
+    /// 指定した位置からプレイヤーが見えるかどうか
+    pub fn can_see_player(&self, position: (i32, i32)) -> bool {
+        self.player_view.is_visible(position.0, position.1)
+    }
+
+    /// 指定した位置からプレイヤーまでのマス数(斜めも1マスと数える)
+    pub fn distance_to_player(&self, position: (i32, i32)) -> i32 {
+        chebyshev(position, self.player_position)
+    }
+
+    /// 指定した位置の敵が、半径内にいるプレイヤーに気づくかどうか
+    fn notices_player(&self, position: (i32, i32), radius: i32) -> bool {
+        self.can_see_player(position) && self.distance_to_player(position) <= radius
+    }
+}
+
+/// 敵の思考ルーチン
+pub trait MobAi {
+    /// 敵の状態を更新し、そのターンの行動を決める
+    ///
+    /// # Arguments
+    /// * `mob` - 行動する敵、`ai_state`はこの関数の中で更新する
+    /// * `context` - マップとプレイヤーの情報
+    /// * `rng` - 乱数生成器
+    fn decide(&self, mob: &mut GameMob, context: &AiContext, rng: &mut dyn RngCore) -> MobAction;
+}
+
+/// 斜めも1マスと数えた距離
+fn chebyshev(a: (i32, i32), b: (i32, i32)) -> i32 {
+    (a.0 - b.0).abs().max((a.1 - b.1).abs())
+}
+
+/// 射程内のプレイヤーを狙えるなら飛び道具を放つ、射程のない敵や隣接している場合はNone
+fn shoot(mob: &GameMob, context: &AiContext) -> Option<MobAction> {
+    if mob.range <= 0 || context.distance_to_player(mob.position) <= 1 || !context.can_see_player(mob.position) {
+        return None;
+    }
+    if has_line_of_fire(context.map, mob.position, context.player_position, mob.range) {
+        Some(MobAction::Shoot(context.player_position))
+    } else {
+        None
+    }
+}
+
+/// 目標に向かって1歩進む、目標がプレイヤーで隣接しているか射程内にいる場合は攻撃する
+fn step_toward(mob: &GameMob, context: &AiContext, target: (i32, i32)) -> MobAction {
+    let position = mob.position;
+    if target == context.player_position {
+        if context.distance_to_player(position) == 1 {
+            let (dx, dy) = (target.0 - position.0, target.1 - position.1);
+            return Direction::from_delta(dx, dy).map_or(MobAction::Wait, MobAction::Attack);
+        }
+        if let Some(action) = shoot(mob, context) {
+            return action;
+        }
+        return context.to_player.next_step(context.map, position)
+            .map_or(MobAction::Wait, MobAction::Move);
+    }
+    match context.path_finder.find_path(context.map, position, target) {
+        Some(path) if !path.is_empty() && path[0] != context.player_position => MobAction::Move(path[0]),
+        _ => MobAction::Wait,
+    }
+}
+
+/// プレイヤーから離れる方向に1歩進む、追い詰められて隣接している場合は攻撃する
+fn step_away(mob: &GameMob, context: &AiContext) -> MobAction {
+    let position = mob.position;
+    let mut best = (position, context.to_player.distance(position.0, position.1).unwrap_or(0));
+    for next in context.path_finder.neighbors(context.map, position) {
+        if let Some(distance) = context.to_player.distance(next.0, next.1) {
+            if distance > best.1 {
+                best = (next, distance);
+            }
+        }
+    }
+    if best.0 != position {
+        MobAction::Move(best.0)
+    } else if context.distance_to_player(position) == 1 {
+        step_toward(mob, context, context.player_position)
+    } else {
+        MobAction::Wait
+    }
+}
+
+/// 移動できるマスからランダムに1歩進む
+fn wander(mob: &GameMob, context: &AiContext, rng: &mut dyn RngCore) -> MobAction {
+    let candidates: Vec<(i32, i32)> = context.path_finder.neighbors(context.map, mob.position)
+        .into_iter()
+        .filter(|position| *position != context.player_position)
+        .collect();
+    if candidates.is_empty() {
+        return MobAction::Wait;
+    }
+    MobAction::Move(candidates[rng.gen_range(0..candidates.len())])
+}
+
+/// 追跡中の敵の行動
+///
+/// プレイヤーが見えている間は追跡先を更新し、見失った位置にたどり着いたらlost_stateに戻る。
+fn chase(mob: &mut GameMob, context: &AiContext, lost_state: MobState) -> MobAction {
+    if matches!(mob.ai_state, MobState::Chasing { .. }) && context.can_see_player(mob.position) {
+        mob.ai_state = MobState::Chasing { target: context.player_position };
+    }
+    match mob.ai_state {
+        MobState::Chasing { target } if target == mob.position => {
+            mob.ai_state = lost_state;
+            MobAction::Wait
+        },
+        MobState::Chasing { target } => step_toward(mob, context, target),
+        _ => MobAction::Wait,
+    }
+}
+
+/// プレイヤーに気づいたら追いかける
+pub struct ChaseAi {
+    /// プレイヤーに気づく距離
+    pub notice_radius: i32,
+}
+
+impl MobAi for ChaseAi {
+    fn decide(&self, mob: &mut GameMob, context: &AiContext, _rng: &mut dyn RngCore) -> MobAction {
+        if context.notices_player(mob.position, self.notice_radius) {
+            mob.ai_state = MobState::Chasing { target: context.player_position };
+        }
+        chase(mob, context, MobState::Idle)
+    }
+}
+
+/// プレイヤーに気づくまでうろつき、見失ったらまたうろつく
+pub struct WanderAi {
+    /// プレイヤーに気づく距離
+    pub notice_radius: i32,
+}
+
+impl MobAi for WanderAi {
+    fn decide(&self, mob: &mut GameMob, context: &AiContext, rng: &mut dyn RngCore) -> MobAction {
+        if context.notices_player(mob.position, self.notice_radius) {
+            mob.ai_state = MobState::Chasing { target: context.player_position };
+        }
+        match mob.ai_state {
+            MobState::Chasing { .. } => chase(mob, context, MobState::Wandering),
+            _ => {
+                mob.ai_state = MobState::Wandering;
+                wander(mob, context, rng)
+            },
+        }
+    }
+}
+
+/// 眠っていて、プレイヤーに気づくか攻撃されると目を覚まして追いかける
+pub struct SleepAi {
+    /// プレイヤーに気づく距離
+    pub notice_radius: i32,
+    /// プレイヤーに気づいたときに目を覚ます確率
+    pub wake_rate: f32,
+}
+
+impl MobAi for SleepAi {
+    fn decide(&self, mob: &mut GameMob, context: &AiContext, rng: &mut dyn RngCore) -> MobAction {
+        if mob.ai_state != MobState::Sleeping {
+            return ChaseAi { notice_radius: self.notice_radius }.decide(mob, context, rng);
+        }
+        let noticed = context.notices_player(mob.position, self.notice_radius)
+            && rng.gen::<f32>() < self.wake_rate;
+        if noticed || mob.hp < mob.max_hp {
+            // 目を覚ましたターンは行動しない
+            mob.ai_state = MobState::Chasing { target: context.player_position };
+        }
+        MobAction::Wait
+    }
+}
+
+/// プレイヤーを追いかけるが、HPが一定の割合を下回ると逃げる
+pub struct FleeAi {
+    /// プレイヤーに気づく距離
+    pub notice_radius: i32,
+    /// 逃げ始めるHPの割合
+    pub flee_hp_rate: f32,
+}
+
+impl MobAi for FleeAi {
+    fn decide(&self, mob: &mut GameMob, context: &AiContext, rng: &mut dyn RngCore) -> MobAction {
+        if mob.max_hp > 0 && (mob.hp as f32) < mob.max_hp as f32 * self.flee_hp_rate {
+            mob.ai_state = MobState::Fleeing;
+        }
+        if mob.ai_state == MobState::Fleeing {
+            return step_away(mob, context);
+        }
+        ChaseAi { notice_radius: self.notice_radius }.decide(mob, context, rng)
+    }
+}
+
+/// プレイヤーに気づいたら一定の距離を保つ
+///
+/// 離れた位置からの攻撃手段を持つ敵に使う。近づかれすぎた場合は離れ、追い詰められたら殴り返す。
+pub struct KeepDistanceAi {
+    /// プレイヤーに気づく距離
+    pub notice_radius: i32,
+    /// 保とうとする距離
+    pub preferred_distance: i32,
+}
+
+impl MobAi for KeepDistanceAi {
+    fn decide(&self, mob: &mut GameMob, context: &AiContext, _rng: &mut dyn RngCore) -> MobAction {
+        if context.notices_player(mob.position, self.notice_radius) {
+            mob.ai_state = MobState::Chasing { target: context.player_position };
+        }
+        if !context.can_see_player(mob.position) {
+            return chase(mob, context, MobState::Idle);
+        }
+        if !matches!(mob.ai_state, MobState::Chasing { .. }) {
+            return MobAction::Wait;
+        }
+        mob.ai_state = MobState::Chasing { target: context.player_position };
+        let distance = context.distance_to_player(mob.position);
+        if distance < self.preferred_distance {
+            step_away(mob, context)
+        } else if let Some(action) = shoot(mob, context) {
+            action
+        } else if distance > self.preferred_distance {
+            step_toward(mob, context, context.player_position)
+        } else {
+            MobAction::Wait
+        }
+    }
+}
+
+/// ゴールの近くを守り、ゴールに近づいたプレイヤーだけを追いかける
+pub struct GuardAi {
+    /// 守る範囲、ゴールからこの距離以内にいるプレイヤーを追いかける
+    pub guard_radius: i32,
+}
+
+impl MobAi for GuardAi {
+    fn decide(&self, mob: &mut GameMob, context: &AiContext, _rng: &mut dyn RngCore) -> MobAction {
+        let player_near_goal =
+            chebyshev(context.player_position, context.goal_position) <= self.guard_radius;
+        if player_near_goal && context.can_see_player(mob.position) {
+            mob.ai_state = MobState::Chasing { target: context.player_position };
+        } else if !player_near_goal {
+            mob.ai_state = MobState::Guarding;
+        }
+        match mob.ai_state {
+            MobState::Chasing { .. } => chase(mob, context, MobState::Guarding),
+            _ => {
+                mob.ai_state = MobState::Guarding;
+                // ゴールの上には立たず、隣で待つ
+                if chebyshev(mob.position, context.goal_position) > 1 {
+                    step_toward(mob, context, context.goal_position)
+                } else {
+                    MobAction::Wait
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::parse_map;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// 仕切りのある部屋、左右は(5, 4)の開口部でつながっている
+    fn test_map() -> Vec<Vec<Tile>> {
+        parse_map(&[
+            "###########",
+            "#....#....#",
+            "#....#....#",
+            "#....#....#",
+            "#.........#",
+            "#....#....#",
+            "###########",
+        ])
+    }
+
+    fn view(map: &[Vec<Tile>], player: (i32, i32)) -> FieldOfView {
+        let mut fov = FieldOfView::new(0, 0);
+        fov.compute(map, player, 8);
+        fov
+    }
+
+    fn mob_with(behavior: MobBehavior, x: i32, y: i32) -> GameMob {
+        let mut mob = GameMob::new(1, x, y);
+        mob.behavior = behavior;
+        mob.ai_state = behavior.initial_state();
+        mob
+    }
+
+    fn decide(mob: &mut GameMob, map: &[Vec<Tile>], player: (i32, i32), goal: (i32, i32)) -> MobAction {
+        let fov = view(map, player);
+        let context = AiContext::new(map, player, goal, &fov, PathFinder::default());
+        let mut rng = StdRng::seed_from_u64(0);
+        mob.behavior.ai().decide(mob, &context, &mut rng)
+    }
+
+    #[test]
+    fn test_chase() {
+        let map = test_map();
+        let mut mob = mob_with(MobBehavior::Chase, 1, 1);
+        // 見えているプレイヤーに近づく
+        assert_eq!(decide(&mut mob, &map, (4, 4), (9, 1)), MobAction::Move((2, 2)));
+        assert_eq!(mob.ai_state, MobState::Chasing { target: (4, 4) });
+        // 隣接していれば攻撃する
+        mob.position = (3, 3);
+        assert_eq!(decide(&mut mob, &map, (4, 4), (9, 1)), MobAction::Attack(Direction::DownRight));
+    }
+
+    #[test]
+    fn test_chase_lost() {
+        // 仕切りの向こうに隠れたプレイヤーを、最後に見た位置まで追いかける
+        let map = test_map();
+        let mut mob = mob_with(MobBehavior::Chase, 2, 2);
+        mob.ai_state = MobState::Chasing { target: (4, 2) };
+        assert_eq!(decide(&mut mob, &map, (8, 1), (9, 1)), MobAction::Move((3, 2)));
+        mob.position = (4, 2);
+        assert_eq!(decide(&mut mob, &map, (8, 1), (9, 1)), MobAction::Wait);
+        assert_eq!(mob.ai_state, MobState::Idle);
+    }
+
+    #[test]
+    fn test_chase_not_noticed() {
+        // 見えないプレイヤーには気づかない
+        let map = test_map();
+        let mut mob = mob_with(MobBehavior::Chase, 1, 1);
+        assert_eq!(decide(&mut mob, &map, (8, 1), (9, 1)), MobAction::Wait);
+        assert_eq!(mob.ai_state, MobState::Idle);
+    }
+
+    #[test]
+    fn test_wander() {
+        let map = test_map();
+        let mut mob = mob_with(MobBehavior::Wander, 2, 2);
+        match decide(&mut mob, &map, (8, 1), (9, 1)) {
+            MobAction::Move(next) => assert_eq!(chebyshev(next, (2, 2)), 1),
+            action => panic!("unexpected action: {:?}", action),
+        }
+        assert_eq!(mob.ai_state, MobState::Wandering);
+        // プレイヤーが見えたら追いかける
+        assert_eq!(decide(&mut mob, &map, (4, 2), (9, 1)), MobAction::Move((3, 2)));
+        assert_eq!(mob.ai_state, MobState::Chasing { target: (4, 2) });
+    }
+
+    #[test]
+    fn test_sleep() {
+        let map = test_map();
+        let mut mob = mob_with(MobBehavior::Sleep, 2, 2);
+        // 見えていても距離が遠ければ起きない
+        assert_eq!(decide(&mut mob, &map, (8, 4), (9, 1)), MobAction::Wait);
+        assert_eq!(mob.ai_state, MobState::Sleeping);
+        // 攻撃されると起きる、起きたターンは行動しない
+        mob.hp -= 1;
+        assert_eq!(decide(&mut mob, &map, (8, 4), (9, 1)), MobAction::Wait);
+        assert!(matches!(mob.ai_state, MobState::Chasing { .. }));
+        assert_eq!(decide(&mut mob, &map, (4, 2), (9, 1)), MobAction::Move((3, 2)));
+    }
+
+    #[test]
+    fn test_flee() {
+        let map = test_map();
+        let mut mob = mob_with(MobBehavior::Flee, 3, 2);
+        assert_eq!(decide(&mut mob, &map, (4, 2), (9, 1)), MobAction::Attack(Direction::Right));
+        // HPが減ると離れる
+        mob.hp = 1;
+        match decide(&mut mob, &map, (4, 2), (9, 1)) {
+            MobAction::Move(next) => assert_eq!(chebyshev(next, (4, 2)), 2),
+            action => panic!("unexpected action: {:?}", action),
+        }
+        assert_eq!(mob.ai_state, MobState::Fleeing);
+        // 追い詰められると攻撃する
+        mob.position = (1, 1);
+        assert_eq!(decide(&mut mob, &map, (2, 2), (9, 1)), MobAction::Attack(Direction::DownRight));
+    }
+
+    #[test]
+    fn test_keep_distance() {
+        let map = test_map();
+        let mut mob = mob_with(MobBehavior::KeepDistance, 1, 2);
+        assert_eq!(decide(&mut mob, &map, (4, 2), (9, 1)), MobAction::Wait);
+        // 保ちたい距離より近ければ離れ、遠ければ近づく
+        assert_eq!(decide(&mut mob, &map, (2, 2), (9, 1)), MobAction::Move((1, 1)));
+        mob.position = (1, 1);
+        match decide(&mut mob, &map, (4, 5), (9, 1)) {
+            MobAction::Move(next) => assert_eq!(chebyshev(next, (4, 5)), 3),
+            action => panic!("unexpected action: {:?}", action),
+        }
+    }
+
+    #[test]
+    fn test_shoot() {
+        let map = test_map();
+        let mut mob = mob_with(MobBehavior::Chase, 1, 1);
+        mob.range = 5;
+        // 射程内で壁に遮られていなければ飛び道具を放つ
+        assert_eq!(decide(&mut mob, &map, (4, 1), (9, 1)), MobAction::Shoot((4, 1)));
+        // 射程外なら近づく
+        mob.range = 2;
+        assert_eq!(decide(&mut mob, &map, (4, 1), (9, 1)), MobAction::Move((2, 1)));
+        // 隣接していれば殴る
+        mob.range = 5;
+        assert_eq!(decide(&mut mob, &map, (2, 2), (9, 1)), MobAction::Attack(Direction::DownRight));
+
+        // 距離を保つ敵は、保ちたい距離より遠くても射程内なら撃つ
+        let mut mob = mob_with(MobBehavior::KeepDistance, 1, 1);
+        mob.range = 5;
+        assert_eq!(decide(&mut mob, &map, (4, 4), (9, 1)), MobAction::Shoot((4, 4)));
+    }
+
+    #[test]
+    fn test_guard() {
+        let map = test_map();
+        let goal = (8, 2);
+        let mut mob = mob_with(MobBehavior::Guard, 8, 5);
+        // ゴールから遠いプレイヤーは無視してゴールに向かう
+        assert_eq!(decide(&mut mob, &map, (1, 1), goal), MobAction::Move((8, 4)));
+        assert_eq!(mob.ai_state, MobState::Guarding);
+        // ゴールの隣で待つ
+        mob.position = (7, 3);
+        assert_eq!(decide(&mut mob, &map, (1, 1), goal), MobAction::Wait);
+        // ゴールに近づいたプレイヤーは追いかける
+        assert_eq!(decide(&mut mob, &map, (6, 1), goal), MobAction::Move((6, 2)));
+        assert!(matches!(mob.ai_state, MobState::Chasing { .. }));
+    }
+}
@@ -0,0 +1,127 @@
+//! 攻撃の範囲を定義するモジュール
+//!
+//! 武器や敵の種類ごとに攻撃が届くマスの並びを決めておき、
+//! 攻撃するときは向きに合わせて`(x, y, damage)`の攻撃情報に展開する。
+
+use crate::player::Direction;
+use crate::projectile::has_line_of_fire;
+use crate::tile::Tile;
+
+use serde::{Deserialize, Serialize};
+
+/// 攻撃が届くマスの並び
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AttackPattern {
+    /// 正面の1マス
+    #[default]
+    Single,
+    /// 正面とその両隣の3マスをなぎ払う
+    Sweep,
+    /// 正面に向かって指定したマス数を貫く
+    Pierce(i32),
+    /// 自分の周りの指定した半径のマスすべて
+    Burst(i32),
+}
+
+impl AttackPattern {
+    /// 攻撃が届くマス、同じマスは含まない
+    ///
+    /// # Arguments
+    /// * `origin` - 攻撃する者のいるマス
+    /// * `direction` - 攻撃する者の向き
+    pub fn tiles(&self, origin: (i32, i32), direction: Direction) -> Vec<(i32, i32)> {
+        let (x, y) = origin;
+        match *self {
+            AttackPattern::Single => {
+                let (dx, dy) = direction.delta();
+                vec![(x + dx, y + dy)]
+            },
+            AttackPattern::Sweep => [-1, 0, 1].iter()
+                .map(|steps| direction.rotated(*steps).delta())
+                .map(|(dx, dy)| (x + dx, y + dy))
+                .collect(),
+            AttackPattern::Pierce(range) => {
+                let (dx, dy) = direction.delta();
+                (1..=range.max(1)).map(|i| (x + dx * i, y + dy * i)).collect()
+            },
+            AttackPattern::Burst(radius) => {
+                let radius = radius.max(1);
+                let mut tiles = vec![];
+                for ty in (y - radius)..=(y + radius) {
+                    for tx in (x - radius)..=(x + radius) {
+                        if (tx, ty) != origin {
+                            tiles.push((tx, ty));
+                        }
+                    }
+                }
+                tiles
+            },
+        }
+    }
+
+    /// 攻撃が届くマスごとに攻撃情報を追加する
+    pub fn attack_info(&self, origin: (i32, i32), direction: Direction, damage: i32, result: &mut Vec<(i32, i32, i32)>) {
+        for (x, y) in self.tiles(origin, direction) {
+            result.push((x, y, damage));
+        }
+    }
+}
+
+/// 攻撃情報から壁に遮られて届かないマスへの攻撃を取り除く
+///
+/// # Arguments
+/// * `map` - 静的マップ
+/// * `origin` - 攻撃する者のいるマス
+/// * `attack_info` - 攻撃情報、`(x, y, damage)`の並び
+pub fn retain_reachable(map: &[Vec<Tile>], origin: (i32, i32), attack_info: &mut Vec<(i32, i32, i32)>) {
+    attack_info.retain(|(x, y, _)| has_line_of_fire(map, origin, (*x, *y), i32::MAX));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiles() {
+        assert_eq!(AttackPattern::Single.tiles((5, 5), Direction::Up), vec![(5, 4)]);
+        // なぎ払いは正面と斜め前の3マス
+        assert_eq!(
+            AttackPattern::Sweep.tiles((5, 5), Direction::Up),
+            vec![(4, 4), (5, 4), (6, 4)]);
+        assert_eq!(
+            AttackPattern::Sweep.tiles((5, 5), Direction::UpRight),
+            vec![(5, 4), (6, 4), (6, 5)]);
+        assert_eq!(
+            AttackPattern::Pierce(3).tiles((5, 5), Direction::Left),
+            vec![(4, 5), (3, 5), (2, 5)]);
+        // 周囲のマスは自分のいるマスを含まない
+        let tiles = AttackPattern::Burst(1).tiles((5, 5), Direction::Up);
+        assert_eq!(tiles.len(), 8);
+        assert!(!tiles.contains(&(5, 5)));
+        assert_eq!(AttackPattern::Burst(2).tiles((5, 5), Direction::Up).len(), 24);
+    }
+
+    #[test]
+    fn test_retain_reachable() {
+        // 0列目とx = 3の列は壁
+        let mut map = vec![vec![Tile::Floor; 5]; 6];
+        map[0] = vec![Tile::Wall; 5];
+        map[3] = vec![Tile::Wall; 5];
+        let mut attack_info = vec![];
+        AttackPattern::Pierce(4).attack_info((1, 2), Direction::Right, 7, &mut attack_info);
+        AttackPattern::Single.attack_info((1, 2), Direction::Left, 7, &mut attack_info);
+        retain_reachable(&map, (1, 2), &mut attack_info);
+        assert_eq!(attack_info, vec![(2, 2, 7)]);
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let patterns: Vec<AttackPattern> = serde_json::from_str(r#"["Single", "Sweep", {"Pierce": 2}, {"Burst": 1}]"#).unwrap();
+        assert_eq!(patterns, vec![
+            AttackPattern::Single,
+            AttackPattern::Sweep,
+            AttackPattern::Pierce(2),
+            AttackPattern::Burst(1),
+        ]);
+    }
+}
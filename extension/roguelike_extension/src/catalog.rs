@@ -0,0 +1,516 @@
+//! データファイルから読み込むカタログ(敵やアイテムの種類など)を定義するモジュール
+//!
+//! カタログはJSONで記述し、拡張を再コンパイルせずに調整できるようにする。
+//! Godotのプロジェクト側に置いたファイルが読み込めない場合に備えて、
+//! 同じ内容を既定値として埋め込んでおく。
+
+use crate::item::GameItem;
+use crate::item::HealthPotion;
+use crate::item::Sword;
+use crate::item::Shield;
+use crate::item::Food;
+use crate::item::Key;
+use crate::item::Elixir;
+use crate::ai::MobBehavior;
+use crate::scheduler::NORMAL_SPEED;
+use crate::attack_pattern::AttackPattern;
+use crate::status_effect::StatusEffect;
+use crate::status_effect::StatusKind;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// 既定の敵のカタログ
+pub const DEFAULT_MOB_CATALOG: &str = include_str!("../../../project/data/mobs.json");
+/// 既定のアイテムのカタログ
+pub const DEFAULT_ITEM_CATALOG: &str = include_str!("../../../project/data/items.json");
+/// 鍵のかかった扉を開ける鍵のアイテムのキー
+pub const DOOR_KEY_ITEM: &str = "door_key";
+
+/// 階層が出現範囲に含まれるかどうか、最も深い階層を指定しない場合は上限なし
+fn in_floor_range(floor: i32, min_floor: i32, max_floor: Option<i32>) -> bool {
+    floor >= min_floor && floor <= max_floor.unwrap_or(i32::MAX)
+}
+
+/// 重みに従って候補から一つを選ぶ
+///
+/// 候補が空か、重みの合計が0の場合はNoneを返す。
+pub fn choose_weighted<'a, T, R: Rng + ?Sized>(
+    candidates: &[&'a T],
+    weight: impl Fn(&T) -> u32,
+    rng: &mut R,
+) -> Option<&'a T> {
+    let total: u32 = candidates.iter().map(|c| weight(c)).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut roll = rng.gen_range(0..total);
+    for candidate in candidates {
+        let w = weight(candidate);
+        if roll < w {
+            return Some(candidate);
+        }
+        roll -= w;
+    }
+    None
+}
+
+/// 敵のステータス、基本値と階層ごとの成長量の両方に使う
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MobStats {
+    pub hp: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub exp_point: i32,
+}
+
+/// 敵が倒されたときに落とすアイテムの候補
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MobDrop {
+    /// 落とすアイテムのキー、アイテムのカタログに対応する
+    pub item: String,
+    /// 選ばれやすさ
+    pub weight: u32,
+}
+
+/// 敵の種類の定義
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MobSpecies {
+    /// 種類を識別するキー
+    pub key: String,
+    /// 表示名
+    pub name: String,
+    /// Godot側で見た目を選ぶためのキー
+    pub model: String,
+    /// 出現する最初の階層でのステータス
+    pub base: MobStats,
+    /// 階層が一つ深くなるごとに増えるステータス
+    pub growth: MobStats,
+    /// 出現する最も浅い階層
+    pub min_floor: i32,
+    /// 出現する最も深い階層、指定しない場合は上限なし
+    #[serde(default)]
+    pub max_floor: Option<i32>,
+    /// 出現のしやすさ
+    pub weight: u32,
+    /// 倒されたときに落とすアイテムの候補
+    #[serde(default)]
+    pub drop_table: Vec<MobDrop>,
+    /// 思考ルーチンの種類、指定しない場合はChase
+    #[serde(default)]
+    pub behavior: MobBehavior,
+    /// 素早さ、指定しない場合は標準の素早さ
+    #[serde(default = "default_speed")]
+    pub speed: i32,
+    /// 遠距離攻撃の射程、0の場合は隣接した相手にしか攻撃しない
+    #[serde(default)]
+    pub range: i32,
+    /// 近接攻撃の範囲、指定しない場合は正面の1マス
+    #[serde(default)]
+    pub attack_pattern: AttackPattern,
+    /// 攻撃が当たった相手にかける状態異常
+    #[serde(default)]
+    pub on_hit: Option<StatusEffect>,
+}
+
+fn default_speed() -> i32 {
+    NORMAL_SPEED
+}
+
+impl MobSpecies {
+    /// 指定した階層に出現するかどうか
+    pub fn can_spawn_at(&self, floor: i32) -> bool {
+        in_floor_range(floor, self.min_floor, self.max_floor)
+    }
+
+    /// 指定した階層でのステータス
+    pub fn stats_at(&self, floor: i32) -> MobStats {
+        let n = (floor - self.min_floor).max(0);
+        MobStats {
+            hp: self.base.hp + self.growth.hp * n,
+            attack: self.base.attack + self.growth.attack * n,
+            defense: self.base.defense + self.growth.defense * n,
+            exp_point: self.base.exp_point + self.growth.exp_point * n,
+        }
+    }
+
+    /// 落とすアイテムのキーを重みに従って選ぶ
+    pub fn choose_drop<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&str> {
+        let candidates: Vec<&MobDrop> = self.drop_table.iter().collect();
+        choose_weighted(&candidates, |drop| drop.weight, rng).map(|drop| drop.item.as_str())
+    }
+}
+
+/// 敵の種類の一覧
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MobCatalog {
+    pub species: Vec<MobSpecies>,
+}
+
+impl MobCatalog {
+    /// JSON文字列から読み込む
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| e.to_string())
+    }
+
+    /// 埋め込まれた既定のカタログ
+    pub fn default_catalog() -> Self {
+        Self::from_json(DEFAULT_MOB_CATALOG).unwrap()
+    }
+
+    /// ドロップテーブルのキーがすべてアイテムのカタログに存在するかを確かめる
+    ///
+    /// # Returns
+    /// 存在しないキーがある場合はそのキーを含むエラーを返す
+    pub fn check_drop_items(&self, items: &ItemCatalog) -> Result<(), String> {
+        for species in &self.species {
+            for drop in &species.drop_table {
+                if items.get(&drop.item).is_none() {
+                    return Err(format!("unknown item key in {}: {}", species.key, drop.item));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// キーから種類を探す
+    pub fn get(&self, key: &str) -> Option<&MobSpecies> {
+        self.species.iter().find(|species| species.key == key)
+    }
+
+    /// 指定した階層に出現する種類を重みに従って選ぶ
+    pub fn choose_species<R: Rng + ?Sized>(&self, floor: i32, rng: &mut R) -> Option<&MobSpecies> {
+        let candidates: Vec<&MobSpecies> = self.species.iter()
+            .filter(|species| species.can_spawn_at(floor))
+            .collect();
+        choose_weighted(&candidates, |species| species.weight, rng)
+    }
+}
+
+/// アイテムの種類、種類ごとにvalueの意味が変わる
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ItemKind {
+    /// 回復薬、valueは回復量
+    HealthPotion,
+    /// 武器、valueは攻撃力の補正
+    Sword,
+    /// 防具、valueは防御力の補正
+    Shield,
+    /// 食料、valueは回復する満腹度
+    Food,
+    /// 鍵、valueは使用回数
+    Key,
+    /// 状態異常にかかる薬、valueは効果の続くターン数
+    Elixir(StatusKind),
+}
+
+/// アイテムの定義
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemDefinition {
+    /// アイテムを識別するキー
+    pub key: String,
+    /// 表示名
+    pub name: String,
+    /// アイテムの種類
+    pub kind: ItemKind,
+    /// 効果量の最小値
+    pub min_value: i32,
+    /// 効果量の最大値、生成するたびにこの範囲から決める
+    pub max_value: i32,
+    /// 武器の攻撃範囲、武器以外では使わない
+    #[serde(default)]
+    pub pattern: AttackPattern,
+    /// 薬の効果の強さ、薬以外では使わない
+    #[serde(default)]
+    pub potency: i32,
+}
+
+impl ItemDefinition {
+    /// 効果量を範囲から決めてアイテムを生成する
+    pub fn create_item<R: Rng + ?Sized>(&self, rng: &mut R) -> GameItem {
+        let value = if self.max_value > self.min_value {
+            rng.gen_range(self.min_value..=self.max_value)
+        } else {
+            self.min_value
+        };
+        match self.kind {
+            ItemKind::HealthPotion => GameItem::HealthPotion(HealthPotion { heal_amount: value }),
+            ItemKind::Sword => GameItem::Sword(Sword { attack_bonus: value, pattern: self.pattern }),
+            ItemKind::Shield => GameItem::Shield(Shield { defense_bonus: value }),
+            ItemKind::Food => GameItem::Food(Food { nutrition: value }),
+            ItemKind::Key => GameItem::Key(Key { uses: value }),
+            ItemKind::Elixir(kind) => GameItem::Elixir(Elixir { effect: StatusEffect::new(kind, value, self.potency) }),
+        }
+    }
+}
+
+/// 床に配置するアイテムの候補
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemSpawn {
+    /// 配置するアイテムのキー
+    pub item: String,
+    /// 選ばれやすさ
+    pub weight: u32,
+    /// 配置される最も浅い階層
+    pub min_floor: i32,
+    /// 配置される最も深い階層、指定しない場合は上限なし
+    #[serde(default)]
+    pub max_floor: Option<i32>,
+}
+
+/// アイテムの一覧と、階層ごとに床に配置するアイテムの表
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ItemCatalog {
+    pub items: Vec<ItemDefinition>,
+    #[serde(default)]
+    pub floor_spawn_table: Vec<ItemSpawn>,
+}
+
+impl ItemCatalog {
+    /// JSON文字列から読み込む
+    ///
+    /// 表の中にカタログに存在しないキーがある場合は失敗とする。
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let catalog: Self = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        for spawn in &catalog.floor_spawn_table {
+            if catalog.get(&spawn.item).is_none() {
+                return Err(format!("unknown item key: {}", spawn.item));
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// 埋め込まれた既定のカタログ
+    pub fn default_catalog() -> Self {
+        Self::from_json(DEFAULT_ITEM_CATALOG).unwrap()
+    }
+
+    /// キーからアイテムの定義を探す
+    pub fn get(&self, key: &str) -> Option<&ItemDefinition> {
+        self.items.iter().find(|item| item.key == key)
+    }
+
+    /// キーを指定してアイテムを生成する
+    pub fn create_item<R: Rng + ?Sized>(&self, key: &str, rng: &mut R) -> Option<GameItem> {
+        self.get(key).map(|item| item.create_item(rng))
+    }
+
+    /// 指定した階層の床に配置するアイテムを重みに従って選んで生成する
+    pub fn create_floor_item<R: Rng + ?Sized>(&self, floor: i32, rng: &mut R) -> Option<GameItem> {
+        let candidates: Vec<&ItemSpawn> = self.floor_spawn_table.iter()
+            .filter(|spawn| in_floor_range(floor, spawn.min_floor, spawn.max_floor))
+            .collect();
+        let key = choose_weighted(&candidates, |spawn| spawn.weight, rng)?.item.as_str();
+        self.create_item(key, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn create_species(key: &str, min_floor: i32, max_floor: Option<i32>, weight: u32) -> MobSpecies {
+        MobSpecies {
+            key: key.into(),
+            name: key.into(),
+            model: key.into(),
+            base: MobStats { hp: 10, attack: 5, defense: 2, exp_point: 1 },
+            growth: MobStats { hp: 3, attack: 2, defense: 1, exp_point: 1 },
+            min_floor,
+            max_floor,
+            weight,
+            drop_table: vec![],
+            behavior: MobBehavior::Chase,
+            speed: NORMAL_SPEED,
+            range: 0,
+            attack_pattern: AttackPattern::Single,
+            on_hit: None,
+        }
+    }
+
+    #[test]
+    fn test_default_catalog() {
+        let catalog = MobCatalog::default_catalog();
+        assert!(!catalog.species.is_empty());
+        assert!(catalog.get("mob").is_some());
+        // 1階に出現する種類が必ずあること
+        assert!(catalog.species.iter().any(|species| species.can_spawn_at(1)));
+    }
+
+    #[test]
+    fn test_stats_at() {
+        let species = create_species("a", 2, None, 1);
+        assert_eq!(species.stats_at(2), MobStats { hp: 10, attack: 5, defense: 2, exp_point: 1 });
+        assert_eq!(species.stats_at(4), MobStats { hp: 16, attack: 9, defense: 4, exp_point: 3 });
+    }
+
+    #[test]
+    fn test_choose_species_by_floor() {
+        let catalog = MobCatalog {
+            species: vec![
+                create_species("shallow", 1, Some(2), 1),
+                create_species("deep", 3, None, 1),
+            ],
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            assert_eq!(catalog.choose_species(1, &mut rng).unwrap().key, "shallow");
+            assert_eq!(catalog.choose_species(5, &mut rng).unwrap().key, "deep");
+        }
+        assert!(MobCatalog::default().choose_species(1, &mut rng).is_none());
+    }
+
+    #[test]
+    fn test_choose_weighted() {
+        // 重みが0のものは選ばれない
+        let catalog = MobCatalog {
+            species: vec![
+                create_species("never", 1, None, 0),
+                create_species("always", 1, None, 3),
+            ],
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            assert_eq!(catalog.choose_species(1, &mut rng).unwrap().key, "always");
+        }
+    }
+
+    #[test]
+    fn test_choose_drop() {
+        let mut species = create_species("a", 1, None, 1);
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(species.choose_drop(&mut rng), None);
+        species.drop_table.push(MobDrop { item: "herb".into(), weight: 1 });
+        assert_eq!(species.choose_drop(&mut rng), Some("herb"));
+    }
+
+    #[test]
+    fn test_default_catalog_drop_keys() {
+        // 既定の敵のドロップテーブルはすべてアイテムのカタログに存在すること
+        let mob_catalog = MobCatalog::default_catalog();
+        let item_catalog = ItemCatalog::default_catalog();
+        assert_eq!(mob_catalog.check_drop_items(&item_catalog), Ok(()));
+    }
+
+    #[test]
+    fn test_unknown_drop_item() {
+        let mut species = create_species("a", 1, None, 1);
+        species.drop_table.push(MobDrop { item: "unknown".into(), weight: 1 });
+        let catalog = MobCatalog { species: vec![species] };
+        let result = catalog.check_drop_items(&ItemCatalog::default_catalog());
+        assert_eq!(result, Err("unknown item key in a: unknown".to_string()));
+    }
+
+    #[test]
+    fn test_default_item_catalog() {
+        let catalog = ItemCatalog::default_catalog();
+        let mut rng = StdRng::seed_from_u64(0);
+        // 1階に配置するアイテムが必ずあること
+        assert!(catalog.create_floor_item(1, &mut rng).is_some());
+        for item in &catalog.items {
+            assert!(item.min_value <= item.max_value, "{}", item.key);
+        }
+        // 鍵のかかった扉に合わせて置く鍵があること
+        let key = catalog.get(DOOR_KEY_ITEM).unwrap();
+        assert!(matches!(key.create_item(&mut rng), GameItem::Key(_)));
+    }
+
+    #[test]
+    fn test_create_item() {
+        let item = ItemDefinition {
+            key: "sword".into(),
+            name: "剣".into(),
+            kind: ItemKind::Sword,
+            min_value: 2,
+            max_value: 4,
+            pattern: AttackPattern::Sweep,
+            potency: 0,
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            match item.create_item(&mut rng) {
+                GameItem::Sword(sword) => {
+                    assert!((2..=4).contains(&sword.attack_bonus));
+                    assert_eq!(sword.pattern, AttackPattern::Sweep);
+                },
+                other => panic!("unexpected item: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_create_elixir() {
+        let catalog = ItemCatalog::from_json(r#"{
+            "items": [
+                {"key": "regen", "name": "再生の薬", "kind": {"Elixir": "Regen"}, "min_value": 5, "max_value": 5, "potency": 2}
+            ]
+        }"#).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            catalog.create_item("regen", &mut rng),
+            Some(GameItem::Elixir(Elixir { effect: StatusEffect::new(StatusKind::Regen, 5, 2) })));
+    }
+
+    #[test]
+    fn test_default_status_sources() {
+        // どの状態異常も敵の攻撃か薬のどちらかでかかる
+        let mob_catalog = MobCatalog::default_catalog();
+        let item_catalog = ItemCatalog::default_catalog();
+        for kind in StatusKind::ALL {
+            let from_mob = mob_catalog.species.iter()
+                .any(|species| species.on_hit.is_some_and(|effect| effect.kind == kind));
+            let from_item = item_catalog.items.iter()
+                .any(|item| item.kind == ItemKind::Elixir(kind));
+            assert!(from_mob || from_item, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn test_create_floor_item() {
+        let catalog = ItemCatalog::from_json(r#"{
+            "items": [
+                {"key": "herb", "name": "薬草", "kind": "HealthPotion", "min_value": 10, "max_value": 10},
+                {"key": "shield", "name": "盾", "kind": "Shield", "min_value": 3, "max_value": 3}
+            ],
+            "floor_spawn_table": [
+                {"item": "herb", "weight": 1, "min_floor": 1, "max_floor": 2},
+                {"item": "shield", "weight": 1, "min_floor": 3}
+            ]
+        }"#).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            assert_eq!(
+                catalog.create_floor_item(1, &mut rng),
+                Some(GameItem::HealthPotion(HealthPotion { heal_amount: 10 })));
+            assert_eq!(
+                catalog.create_floor_item(3, &mut rng),
+                Some(GameItem::Shield(Shield { defense_bonus: 3 })));
+        }
+        assert_eq!(catalog.create_item("unknown", &mut rng), None);
+    }
+
+    #[test]
+    fn test_item_catalog_unknown_key() {
+        let result = ItemCatalog::from_json(r#"{
+            "items": [],
+            "floor_spawn_table": [{"item": "herb", "weight": 1, "min_floor": 1}]
+        }"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_from_json() {
+        assert!(MobCatalog::from_json("{").is_err());
+        let catalog = MobCatalog::from_json(r#"{"species": [{
+            "key": "bat", "name": "コウモリ", "model": "bat",
+            "base": {"hp": 5, "attack": 3, "defense": 0, "exp_point": 1},
+            "growth": {"hp": 1, "attack": 1, "defense": 0, "exp_point": 0},
+            "min_floor": 1, "weight": 2
+        }]}"#).unwrap();
+        let species = catalog.get("bat").unwrap();
+        assert_eq!(species.max_floor, None);
+        assert!(species.drop_table.is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+//! 洞窟のようなマップを生成するモジュール
+//!
+//! セルオートマトンで削った洞窟と、ランダムウォークで掘り進めた洞窟を生成する。
+//! どちらも部屋の区切りがないので、床が十分にある区画を部屋とみなす。
+
+use crate::map_generator::{
+    carve_corridor,
+    floor_regions,
+    room_around,
+    rooms_from_sectors,
+    BSPNodeParams,
+    MapGenerator,
+};
+use crate::tile::Tile;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+/// 部屋が1つもできなかったときにマップの中央に掘る部屋の半径
+const FALLBACK_ROOM_RADIUS: i32 = 2;
+
+/// マップの中央に小さな部屋を掘って、それを唯一の部屋とする
+fn fallback_room(dungeon: &mut [Vec<Tile>], width: i32, height: i32) -> Vec<BSPNodeParams> {
+    let (cx, cy) = (width / 2, height / 2);
+    let r = FALLBACK_ROOM_RADIUS;
+    for x in (cx - r).max(1)..=(cx + r).min(width - 2) {
+        for y in (cy - r).max(1)..=(cy + r).min(height - 2) {
+            dungeon[x as usize][y as usize] = Tile::Floor;
+        }
+    }
+    room_around(dungeon, cx - r, cy - r, r * 2 + 1, r * 2 + 1).into_iter().collect()
+}
+
+/// セルオートマトンで洞窟を生成するアルゴリズム
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaveGenerator {
+    /// 最初に壁にするマスの割合
+    pub wall_probability: f32,
+    /// 壁をならす回数
+    pub iterations: usize,
+    /// これより小さい洞窟は埋める
+    pub min_region_size: usize,
+    /// 部屋とみなす区画の大きさ
+    pub sector_size: i32,
+}
+
+impl Default for CaveGenerator {
+    fn default() -> Self {
+        Self {
+            wall_probability: 0.45,
+            iterations: 5,
+            min_region_size: 24,
+            sector_size: 16,
+        }
+    }
+}
+
+impl CaveGenerator {
+    /// 周りの壁の数に応じて壁をならす
+    ///
+    /// 自分を含めた3x3のマスのうち5マス以上が壁なら壁に、そうでなければ床にする。マップの外は壁とみなす。
+    fn smooth(dungeon: &[Vec<Tile>]) -> Vec<Vec<Tile>> {
+        let width = dungeon.len() as i32;
+        let height = dungeon[0].len() as i32;
+        let mut next = dungeon.to_vec();
+        for x in 1..width - 1 {
+            for y in 1..height - 1 {
+                let mut walls = 0;
+                for dx in -1..=1 {
+                    for dy in -1..=1 {
+                        if dungeon[(x + dx) as usize][(y + dy) as usize] == Tile::Wall {
+                            walls += 1;
+                        }
+                    }
+                }
+                next[x as usize][y as usize] = if walls >= 5 { Tile::Wall } else { Tile::Floor };
+            }
+        }
+        next
+    }
+}
+
+impl MapGenerator for CaveGenerator {
+    fn generate(&self, width: i32, height: i32, rng: &mut StdRng) -> (Vec<Vec<Tile>>, Vec<BSPNodeParams>) {
+        // 外周は常に壁にする
+        let mut dungeon = vec![vec![Tile::Wall; height as usize]; width as usize];
+        for x in 1..width - 1 {
+            for y in 1..height - 1 {
+                if rng.gen::<f32>() >= self.wall_probability {
+                    dungeon[x as usize][y as usize] = Tile::Floor;
+                }
+            }
+        }
+        for _ in 0..self.iterations {
+            dungeon = Self::smooth(&dungeon);
+        }
+
+        // 小さな洞窟は埋めて、残った洞窟同士を順に通路でつなぐ
+        let mut caves = vec![];
+        for region in floor_regions(&dungeon) {
+            if region.len() < self.min_region_size {
+                for (x, y) in region {
+                    dungeon[x as usize][y as usize] = Tile::Wall;
+                }
+            } else {
+                caves.push(region[0]);
+            }
+        }
+        for pair in caves.windows(2) {
+            carve_corridor(&mut dungeon, pair[0], pair[1], rng);
+        }
+
+        let mut rooms = rooms_from_sectors(&dungeon, self.sector_size);
+        if rooms.is_empty() {
+            rooms = fallback_room(&mut dungeon, width, height);
+        }
+        (dungeon, rooms)
+    }
+}
+
+/// ランダムウォークで洞窟を掘り進めるアルゴリズム
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrunkardWalkGenerator {
+    /// 外周を除いたマップのうち、床にするマスの割合
+    pub floor_ratio: f32,
+    /// 部屋とみなす区画の大きさ
+    pub sector_size: i32,
+}
+
+impl Default for DrunkardWalkGenerator {
+    fn default() -> Self {
+        Self {
+            floor_ratio: 0.4,
+            sector_size: 16,
+        }
+    }
+}
+
+impl MapGenerator for DrunkardWalkGenerator {
+    fn generate(&self, width: i32, height: i32, rng: &mut StdRng) -> (Vec<Vec<Tile>>, Vec<BSPNodeParams>) {
+        let mut dungeon = vec![vec![Tile::Wall; height as usize]; width as usize];
+        let interior = ((width - 2).max(0) * (height - 2).max(0)) as usize;
+        let target = (interior as f32 * self.floor_ratio) as usize;
+
+        // 中央から歩き始め、外周に出ないようにしながら壁を掘る
+        // 1本の道を掘り進めるので、掘った床はすべてつながっている
+        let (mut x, mut y) = (width / 2, height / 2);
+        dungeon[x as usize][y as usize] = Tile::Floor;
+        let mut floor_count = 1;
+        for _ in 0..interior * 100 {
+            if floor_count >= target {
+                break;
+            }
+            let (dx, dy) = [(0, -1), (1, 0), (0, 1), (-1, 0)][rng.gen_range(0..4)];
+            x = (x + dx).clamp(1, width - 2);
+            y = (y + dy).clamp(1, height - 2);
+            if dungeon[x as usize][y as usize] == Tile::Wall {
+                dungeon[x as usize][y as usize] = Tile::Floor;
+                floor_count += 1;
+            }
+        }
+
+        let mut rooms = rooms_from_sectors(&dungeon, self.sector_size);
+        if rooms.is_empty() {
+            rooms = fallback_room(&mut dungeon, width, height);
+        }
+        (dungeon, rooms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_generator::unreachable_rooms;
+    use rand::SeedableRng;
+
+    /// 外周がすべて壁になっているかどうか
+    fn is_enclosed(dungeon: &[Vec<Tile>]) -> bool {
+        let (width, height) = (dungeon.len(), dungeon[0].len());
+        (0..width).all(|x| dungeon[x][0] == Tile::Wall && dungeon[x][height - 1] == Tile::Wall)
+            && (0..height).all(|y| dungeon[0][y] == Tile::Wall && dungeon[width - 1][y] == Tile::Wall)
+    }
+
+    #[test]
+    fn test_cave_generator() {
+        let generator = CaveGenerator::default();
+        for seed in 0..10 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (dungeon, rooms) = generator.generate(64, 48, &mut rng);
+            assert!(is_enclosed(&dungeon));
+            // 洞窟は1つながりになっていて、小さな洞窟は残っていない
+            let regions = floor_regions(&dungeon);
+            assert_eq!(regions.len(), 1);
+            assert!(regions[0].len() >= generator.min_region_size);
+            assert!(!rooms.is_empty());
+            assert!(unreachable_rooms(&dungeon, &rooms).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_drunkard_walk_generator() {
+        let generator = DrunkardWalkGenerator::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let (dungeon, rooms) = generator.generate(64, 64, &mut rng);
+        assert!(is_enclosed(&dungeon));
+        let floor_count = dungeon.iter().flatten().filter(|tile| **tile == Tile::Floor).count();
+        assert_eq!(floor_count, (62.0 * 62.0 * generator.floor_ratio) as usize);
+        assert_eq!(floor_regions(&dungeon).len(), 1);
+        for room in &rooms {
+            assert_eq!(dungeon[room.room_center_x as usize][room.room_center_y as usize], Tile::Floor);
+        }
+    }
+
+    #[test]
+    fn test_fallback_room() {
+        // 全部壁になる設定でも部屋が1つはできる
+        let generator = CaveGenerator { wall_probability: 1.0, ..Default::default() };
+        let mut rng = StdRng::seed_from_u64(0);
+        let (dungeon, rooms) = generator.generate(20, 20, &mut rng);
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(dungeon[rooms[0].room_center_x as usize][rooms[0].room_center_y as usize], Tile::Floor);
+    }
+}
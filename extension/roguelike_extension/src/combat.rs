@@ -0,0 +1,182 @@
+//! 戦闘時のダメージ計算を行うモジュール
+
+use rand::Rng;
+use rand::RngCore;
+
+/// 1回の攻撃の結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttackResult {
+    /// 通常の命中
+    Hit(i32),
+    /// 会心の一撃
+    Critical(i32),
+    /// 攻撃が外れた
+    Miss,
+}
+
+impl AttackResult {
+    /// 与えるダメージ、外れた場合は0
+    pub fn damage(&self) -> i32 {
+        match self {
+            AttackResult::Hit(damage) => *damage,
+            AttackResult::Critical(damage) => *damage,
+            AttackResult::Miss => 0,
+        }
+    }
+}
+
+/// ダメージ計算式を差し替えられるようにするためのトレイト
+pub trait DamageFormula {
+    /// 攻撃力と防御力からダメージを計算する
+    ///
+    /// # Arguments
+    ///
+    /// * `attack` - 攻撃側の攻撃力(装備品の補正を含む)
+    /// * `defense` - 防御側の防御力(装備品の補正を含む)
+    /// * `rng` - 乱数生成器
+    fn calculate(&self, attack: i32, defense: i32, rng: &mut dyn RngCore) -> AttackResult;
+}
+
+/// 標準のダメージ計算式
+///
+/// ダメージ = (攻撃力 - 防御力 * defense_factor) * (1 ± variance)
+/// 会心の一撃の場合は防御力を無視して攻撃力 * critical_multiplier とする。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StandardDamageFormula {
+    /// 防御力がダメージを減らす割合
+    pub defense_factor: f32,
+    /// 命中した場合の最低ダメージ
+    pub min_damage: i32,
+    /// ダメージの振れ幅、0.1なら±10%
+    pub variance: f32,
+    /// 会心の一撃が出る確率
+    pub critical_rate: f32,
+    /// 会心の一撃のダメージ倍率
+    pub critical_multiplier: f32,
+    /// 攻撃が外れる確率
+    pub miss_rate: f32,
+}
+
+impl Default for StandardDamageFormula {
+    fn default() -> Self {
+        Self {
+            defense_factor: 0.5,
+            min_damage: 1,
+            variance: 0.1,
+            critical_rate: 0.05,
+            critical_multiplier: 1.5,
+            miss_rate: 0.05,
+        }
+    }
+}
+
+impl DamageFormula for StandardDamageFormula {
+    fn calculate(&self, attack: i32, defense: i32, rng: &mut dyn RngCore) -> AttackResult {
+        // 乱数の消費回数を一定にして、結果を再現しやすくする
+        let miss_roll = rng.gen::<f32>();
+        let critical_roll = rng.gen::<f32>();
+        let variance_roll = rng.gen::<f32>();
+
+        if miss_roll < self.miss_rate {
+            return AttackResult::Miss;
+        }
+
+        let is_critical = critical_roll < self.critical_rate;
+        let base = if is_critical {
+            attack as f32 * self.critical_multiplier
+        } else {
+            attack as f32 - defense as f32 * self.defense_factor
+        };
+        // variance_rollを[-1, 1)に変換して振れ幅を決める
+        let base = base * (1.0 + self.variance * (variance_roll * 2.0 - 1.0));
+        let damage = (base.round() as i32).max(self.min_damage);
+
+        if is_critical {
+            AttackResult::Critical(damage)
+        } else {
+            AttackResult::Hit(damage)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// 乱数の影響を受けない設定
+    fn fixed_formula() -> StandardDamageFormula {
+        StandardDamageFormula {
+            defense_factor: 0.5,
+            min_damage: 1,
+            variance: 0.0,
+            critical_rate: 0.0,
+            critical_multiplier: 2.0,
+            miss_rate: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_hit() {
+        let formula = fixed_formula();
+        let mut rng = StepRng::new(u64::MAX / 2, 0);
+        assert_eq!(formula.calculate(10, 4, &mut rng), AttackResult::Hit(8));
+        assert_eq!(formula.calculate(10, 0, &mut rng), AttackResult::Hit(10));
+    }
+
+    #[test]
+    fn test_min_damage() {
+        // 防御力が高くても最低ダメージは与える
+        let mut formula = fixed_formula();
+        let mut rng = StepRng::new(u64::MAX / 2, 0);
+        assert_eq!(formula.calculate(5, 100, &mut rng), AttackResult::Hit(1));
+        formula.min_damage = 0;
+        assert_eq!(formula.calculate(5, 100, &mut rng), AttackResult::Hit(0));
+    }
+
+    #[test]
+    fn test_miss() {
+        // 乱数が常に0の場合、外れる確率が0より大きければ必ず外れる
+        let mut formula = fixed_formula();
+        formula.miss_rate = 0.1;
+        let mut rng = StepRng::new(0, 0);
+        assert_eq!(formula.calculate(10, 4, &mut rng), AttackResult::Miss);
+        assert_eq!(AttackResult::Miss.damage(), 0);
+    }
+
+    #[test]
+    fn test_critical() {
+        // 会心の一撃は防御力を無視する
+        let mut formula = fixed_formula();
+        formula.critical_rate = 0.1;
+        let mut rng = StepRng::new(0, 0);
+        assert_eq!(formula.calculate(10, 4, &mut rng), AttackResult::Critical(20));
+    }
+
+    #[test]
+    fn test_variance() {
+        // 振れ幅の範囲に収まること
+        let mut formula = fixed_formula();
+        formula.variance = 0.2;
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let damage = formula.calculate(100, 0, &mut rng).damage();
+            assert!((80..=120).contains(&damage));
+        }
+    }
+
+    #[test]
+    fn test_same_seed() {
+        // 同じシード値からは同じ結果が得られること
+        let formula = StandardDamageFormula::default();
+        let mut rng_a = StdRng::seed_from_u64(3);
+        let mut rng_b = StdRng::seed_from_u64(3);
+        for _ in 0..100 {
+            assert_eq!(
+                formula.calculate(12, 5, &mut rng_a),
+                formula.calculate(12, 5, &mut rng_b));
+        }
+    }
+}
@@ -3,11 +3,14 @@
 use crate::player::GamePlayer;
 use crate::mob::GameMob;
 use crate::item::DroppedItem;
+use crate::trap::Traps;
 
+use serde::{Deserialize, Serialize};
 use std::rc::Rc;
 use std::cell::RefCell;
 
 /// 動的マップを管理するクラス
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DynamicMapManager {
     /// プレイヤー
     pub player: GamePlayer,
@@ -17,9 +20,14 @@ pub struct DynamicMapManager {
     pub mob_list: Vec<RefCell<GameMob>>,
 
     /// 倒したモンスターのIDのリスト
+    /// そのターンだけの情報なので保存はしない
+    #[serde(skip)]
     pub defeated_mob_id: Vec<i32>,
     /// ゴールの位置
     pub goal_position: (i32, i32),
+    /// マップ上に仕掛けられた罠
+    #[serde(default)]
+    pub traps: Traps,
 }
 
 impl DynamicMapManager {
@@ -31,6 +39,7 @@ impl DynamicMapManager {
             mob_list: vec![],
             defeated_mob_id: vec![],
             goal_position: (0, 0),
+            traps: Traps::default(),
         }
     }
 
@@ -39,5 +48,6 @@ impl DynamicMapManager {
         self.item_list.clear();
         self.mob_list.clear();
         self.defeated_mob_id.clear();
+        self.traps.clear();
     }
 }
@@ -0,0 +1,362 @@
+//! ターン中に起きた出来事を記録するモジュール
+//!
+//! ゲームの処理は出来事(`GameEvent`)を記録するだけにして、
+//! 画面に表示するメッセージは出来事から別に作る。
+
+use crate::item::EquipSlot;
+use crate::item::GameItem;
+use crate::locale::MessageCatalog;
+use crate::status_effect::StatusKind;
+use crate::tile::Tile;
+use crate::trap::TrapKind;
+
+/// Godot側に渡すときのプレイヤーのID、敵のIDは0以上なので重ならない
+pub const PLAYER_ACTOR_ID: i32 = -1;
+
+/// 行動の主体
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Actor {
+    Player,
+    /// 敵、値は敵のID
+    Mob(i32),
+}
+
+impl Actor {
+    /// Godot側に渡すためのID、プレイヤーはPLAYER_ACTOR_ID
+    pub fn id(&self) -> i32 {
+        match self {
+            Actor::Player => PLAYER_ACTOR_ID,
+            Actor::Mob(id) => *id,
+        }
+    }
+}
+
+/// ターン中に起きた出来事
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    /// 移動した
+    Moved { actor: Actor, from: (i32, i32), to: (i32, i32) },
+    /// 飛び道具を放った、投げたアイテムの場合はitemにそのアイテムが入る
+    ProjectileFired { actor: Actor, path: Vec<(i32, i32)>, item: Option<GameItem> },
+    /// 攻撃が命中した
+    Attacked { attacker: Actor, target: Actor, damage: i32, critical: bool },
+    /// 攻撃が外れた、誰もいない場所を攻撃した場合はtargetがNone
+    Missed { attacker: Actor, target: Option<Actor> },
+    /// HPが0になった、defeatedは敵をプレイヤーが倒して経験値を得た場合にtrue
+    Died { actor: Actor, position: (i32, i32), defeated: bool },
+    /// 落ちているアイテムを拾った
+    ItemPickedUp { item_id: i32, item: GameItem },
+    /// 持ち物がいっぱいでアイテムを拾えなかった
+    InventoryFull { item_id: i32 },
+    /// アイテムが床に落ちた
+    ItemDropped { item_id: i32, position: (i32, i32), item: GameItem },
+    /// アイテムを使った
+    ItemUsed { item: GameItem },
+    /// 装備を外した
+    Unequipped { slot: EquipSlot },
+    /// 装備を外せなかった
+    UnequipFailed { slot: EquipSlot },
+    /// プレイヤーのレベルが上がった
+    LevelUp { level: i32 },
+    /// プレイヤーが力尽きた
+    GameOver,
+    /// プレイヤーがゴールに到達した
+    GoalReached { position: (i32, i32) },
+    /// 次の階層へ移動した
+    Descended { level: i32 },
+    /// 最後の階層を踏破した
+    Won { level: i32 },
+    /// 状態異常にかかった
+    StatusApplied { actor: Actor, kind: StatusKind },
+    /// 状態異常の効果でHPが増減した、amountは増えた量で減った場合は負の値
+    StatusTicked { actor: Actor, kind: StatusKind, amount: i32 },
+    /// 状態異常が治った
+    StatusExpired { actor: Actor, kind: StatusKind },
+    /// プレイヤーの満腹度が残り少なくなった
+    Hungry,
+    /// プレイヤーの満腹度が0になった
+    Starving,
+    /// 満腹度が0のためプレイヤーがダメージを受けた
+    StarvationDamaged { damage: i32 },
+    /// 踏み込んだ地形でダメージを受けた
+    TerrainDamaged { actor: Actor, tile: Tile, damage: i32 },
+    /// 扉を開けた、unlockedは鍵を使って開けたかどうか
+    DoorOpened { actor: Actor, position: (i32, i32), unlocked: bool },
+    /// 鍵を持たずに鍵のかかった扉を開けようとした
+    DoorLocked { position: (i32, i32) },
+    /// 罠でダメージを受けた
+    TrapDamaged { actor: Actor, kind: TrapKind, damage: i32 },
+    /// 敵が罠で力尽きた、プレイヤーから見えないところで力尽きた敵は記録しない
+    TrapKilled { actor: Actor, kind: TrapKind },
+    /// 罠を踏んだ、プレイヤーから見えないところで敵が踏んだ罠は記録しない
+    TrapTriggered { actor: Actor, kind: TrapKind, position: (i32, i32) },
+    /// 敵が落とし穴に落ちて階層からいなくなった
+    Fell { actor: Actor, position: (i32, i32) },
+    /// プレイヤーが周りを調べて罠を見つけた
+    TrapRevealed { kind: TrapKind, position: (i32, i32) },
+    /// プレイヤーが周りを調べた、foundは見つけた罠の数
+    Searched { found: i32 },
+}
+
+impl GameEvent {
+    /// 出来事の種類を表す文字列、Godot側で種類を判別するのに使う
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GameEvent::Moved { .. } => "moved",
+            GameEvent::ProjectileFired { .. } => "projectile_fired",
+            GameEvent::Attacked { .. } => "attacked",
+            GameEvent::Missed { .. } => "missed",
+            GameEvent::Died { .. } => "died",
+            GameEvent::ItemPickedUp { .. } => "item_picked_up",
+            GameEvent::InventoryFull { .. } => "inventory_full",
+            GameEvent::ItemDropped { .. } => "item_dropped",
+            GameEvent::ItemUsed { .. } => "item_used",
+            GameEvent::Unequipped { .. } => "unequipped",
+            GameEvent::UnequipFailed { .. } => "unequip_failed",
+            GameEvent::LevelUp { .. } => "level_up",
+            GameEvent::GameOver => "game_over",
+            GameEvent::GoalReached { .. } => "goal_reached",
+            GameEvent::Descended { .. } => "descended",
+            GameEvent::Won { .. } => "won",
+            GameEvent::StatusApplied { .. } => "status_applied",
+            GameEvent::StatusTicked { .. } => "status_ticked",
+            GameEvent::StatusExpired { .. } => "status_expired",
+            GameEvent::Hungry => "hungry",
+            GameEvent::Starving => "starving",
+            GameEvent::StarvationDamaged { .. } => "starvation_damaged",
+            GameEvent::TerrainDamaged { .. } => "terrain_damaged",
+            GameEvent::DoorOpened { .. } => "door_opened",
+            GameEvent::DoorLocked { .. } => "door_locked",
+            GameEvent::TrapDamaged { .. } => "trap_damaged",
+            GameEvent::TrapKilled { .. } => "trap_killed",
+            GameEvent::TrapTriggered { .. } => "trap_triggered",
+            GameEvent::Fell { .. } => "fell",
+            GameEvent::TrapRevealed { .. } => "trap_revealed",
+            GameEvent::Searched { .. } => "searched",
+        }
+    }
+}
+
+/// 出来事から画面に表示するメッセージを作る
+///
+/// 1つの出来事から複数行のメッセージを作ることがある。表示しない出来事の場合は空。
+pub fn event_messages(event: &GameEvent, catalog: &MessageCatalog) -> Vec<String> {
+    let mut messages = vec![];
+    let mut push = |key: &str, params: &[(&str, String)]| messages.push(catalog.text(key, params));
+    match event {
+        GameEvent::ProjectileFired { actor, item, .. } => match (actor, item) {
+            (Actor::Player, Some(item)) => push("item_thrown", &[("item", catalog.item_text(item))]),
+            (Actor::Mob(id), _) => push("mob_shot", &[("id", id.to_string())]),
+            _ => {},
+        },
+        GameEvent::Attacked { attacker, target, damage, critical } => match (attacker, target) {
+            (Actor::Player, Actor::Mob(id)) => {
+                if *critical {
+                    push("player_attack_critical", &[]);
+                }
+                push("player_attack_hit", &[("id", id.to_string()), ("damage", damage.to_string())]);
+            },
+            (Actor::Mob(id), Actor::Player) => {
+                if *critical {
+                    push("mob_attack_critical", &[]);
+                }
+                push("mob_attack_hit", &[("id", id.to_string()), ("damage", damage.to_string())]);
+            },
+            _ => {},
+        },
+        GameEvent::Missed { attacker, target } => match (attacker, target) {
+            (Actor::Player, Some(Actor::Mob(id))) => push("player_attack_missed", &[("id", id.to_string())]),
+            (Actor::Player, None) => push("player_attack_fumbled", &[]),
+            (Actor::Mob(id), _) => push("mob_attack_missed", &[("id", id.to_string())]),
+            _ => {},
+        },
+        GameEvent::Died { actor: Actor::Mob(id), defeated: true, .. } => push("mob_defeated", &[("id", id.to_string())]),
+        GameEvent::ItemPickedUp { .. } => push("item_picked_up", &[]),
+        GameEvent::InventoryFull { .. } => push("inventory_full", &[]),
+        GameEvent::ItemUsed { item } => match item {
+            GameItem::HealthPotion(_) => push("healed", &[]),
+            GameItem::Food(_) => push("ate", &[]),
+            GameItem::Elixir(elixir) => push("status_applied_player", &[("status", catalog.status_text(elixir.effect.kind))]),
+            GameItem::Sword(_) | GameItem::Shield(_) => push("equipped", &[]),
+            _ => {},
+        },
+        GameEvent::Unequipped { .. } => push("unequipped", &[]),
+        GameEvent::UnequipFailed { .. } => push("unequip_failed", &[]),
+        GameEvent::LevelUp { .. } => push("level_up", &[]),
+        GameEvent::GameOver => push("game_over", &[]),
+        GameEvent::GoalReached { .. } => push("goal_reached", &[]),
+        GameEvent::Descended { level } => push("current_floor", &[("level", level.to_string())]),
+        GameEvent::Won { .. } => push("victory", &[]),
+        GameEvent::StatusApplied { actor, kind } => {
+            let status = catalog.status_text(*kind);
+            match actor {
+                Actor::Player => push("status_applied_player", &[("status", status)]),
+                Actor::Mob(id) => push("status_applied_mob", &[("id", id.to_string()), ("status", status)]),
+            }
+        },
+        // 再生による回復は毎ターン起きるのでメッセージにしない
+        GameEvent::StatusTicked { actor, kind: StatusKind::Poison, amount } => {
+            let damage = (-amount).to_string();
+            match actor {
+                Actor::Player => push("poison_damage_player", &[("damage", damage)]),
+                Actor::Mob(id) => push("poison_damage_mob", &[("id", id.to_string()), ("damage", damage)]),
+            }
+        },
+        GameEvent::StatusExpired { actor, kind } => {
+            let status = catalog.status_text(*kind);
+            match actor {
+                Actor::Player => push("status_expired_player", &[("status", status)]),
+                Actor::Mob(id) => push("status_expired_mob", &[("id", id.to_string()), ("status", status)]),
+            }
+        },
+        GameEvent::Hungry => push("hungry", &[]),
+        GameEvent::Starving => push("starving", &[]),
+        GameEvent::StarvationDamaged { damage } => push("starvation_damage", &[("damage", damage.to_string())]),
+        GameEvent::TerrainDamaged { actor, tile, damage } => {
+            let terrain = catalog.tile_text(*tile);
+            let damage = damage.to_string();
+            match actor {
+                Actor::Player => push("terrain_damage_player", &[("terrain", terrain), ("damage", damage)]),
+                Actor::Mob(id) => push("terrain_damage_mob", &[("id", id.to_string()), ("terrain", terrain), ("damage", damage)]),
+            }
+        },
+        // 敵が扉を開けたことはメッセージにしない
+        GameEvent::DoorOpened { actor: Actor::Player, unlocked, .. } => {
+            push(if *unlocked { "door_unlocked" } else { "door_opened" }, &[]);
+        },
+        GameEvent::DoorLocked { .. } => push("door_locked", &[]),
+        GameEvent::TrapDamaged { actor, kind, damage } => {
+            let trap = catalog.trap_text(*kind);
+            let damage = damage.to_string();
+            match actor {
+                Actor::Player => push("trap_damage_player", &[("trap", trap), ("damage", damage)]),
+                Actor::Mob(id) => push("trap_damage_mob", &[("id", id.to_string()), ("trap", trap), ("damage", damage)]),
+            }
+        },
+        GameEvent::TrapKilled { actor: Actor::Mob(id), kind } => {
+            push("trap_killed_mob", &[("id", id.to_string()), ("trap", catalog.trap_text(*kind))]);
+        },
+        GameEvent::TrapTriggered { actor, kind, .. } => {
+            let trap = catalog.trap_text(*kind);
+            match actor {
+                Actor::Player => push("trap_triggered_player", &[("trap", trap)]),
+                Actor::Mob(id) => push("trap_triggered_mob", &[("id", id.to_string()), ("trap", trap)]),
+            }
+            // 警報はどこで鳴っても聞こえる
+            if *kind == TrapKind::Alarm {
+                push("alarm_rang", &[]);
+            }
+        },
+        GameEvent::TrapRevealed { kind, .. } => push("trap_revealed", &[("trap", catalog.trap_text(*kind))]),
+        GameEvent::Searched { found: 0 } => push("search_nothing", &[]),
+        _ => {},
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::HealthPotion;
+
+    #[test]
+    fn test_actor_id() {
+        assert_eq!(Actor::Player.id(), PLAYER_ACTOR_ID);
+        assert_eq!(Actor::Mob(3).id(), 3);
+    }
+
+    #[test]
+    fn test_attack_messages() {
+        let catalog = MessageCatalog::default_catalog("ja").unwrap();
+        let event = GameEvent::Attacked {
+            attacker: Actor::Player,
+            target: Actor::Mob(2),
+            damage: 7,
+            critical: true,
+        };
+        assert_eq!(event.kind(), "attacked");
+        assert_eq!(event_messages(&event, &catalog), vec!["会心の一撃！", "ID2に7ダメージを与えた。"]);
+
+        let event = GameEvent::Attacked {
+            attacker: Actor::Mob(2),
+            target: Actor::Player,
+            damage: 3,
+            critical: false,
+        };
+        assert_eq!(event_messages(&event, &catalog), vec!["プレイヤーはID2から3ダメージを受けた。"]);
+
+        let event = GameEvent::Missed { attacker: Actor::Player, target: None };
+        assert_eq!(event_messages(&event, &catalog), vec!["攻撃が外れた。"]);
+    }
+
+    #[test]
+    fn test_silent_events() {
+        // 移動やアイテムが落ちたことはメッセージにしない
+        let catalog = MessageCatalog::default_catalog("ja").unwrap();
+        let event = GameEvent::Moved { actor: Actor::Mob(1), from: (0, 0), to: (1, 0) };
+        assert!(event_messages(&event, &catalog).is_empty());
+        let event = GameEvent::ItemDropped {
+            item_id: 1,
+            position: (1, 0),
+            item: GameItem::HealthPotion(HealthPotion { heal_amount: 10 }),
+        };
+        assert!(event_messages(&event, &catalog).is_empty());
+        assert!(event_messages(&GameEvent::Died { actor: Actor::Player, position: (0, 0), defeated: false }, &catalog).is_empty());
+        // 罠などで力尽きた敵は倒したことにならない
+        assert!(event_messages(&GameEvent::Died { actor: Actor::Mob(1), position: (0, 0), defeated: false }, &catalog).is_empty());
+        let messages = event_messages(&GameEvent::Died { actor: Actor::Mob(1), position: (0, 0), defeated: true }, &catalog);
+        assert_eq!(messages, vec!["ID1を倒した。".to_string()]);
+        let event = GameEvent::StatusTicked { actor: Actor::Player, kind: StatusKind::Regen, amount: 2 };
+        assert!(event_messages(&event, &catalog).is_empty());
+    }
+
+    #[test]
+    fn test_status_messages() {
+        let catalog = MessageCatalog::default_catalog("ja").unwrap();
+        let event = GameEvent::StatusApplied { actor: Actor::Player, kind: StatusKind::Poison };
+        assert_eq!(event.kind(), "status_applied");
+        assert_eq!(event_messages(&event, &catalog), vec!["プレイヤーは毒状態になった。"]);
+        let event = GameEvent::StatusTicked { actor: Actor::Mob(3), kind: StatusKind::Poison, amount: -2 };
+        assert_eq!(event_messages(&event, &catalog), vec!["ID3は毒で2ダメージを受けた。"]);
+        let event = GameEvent::StatusExpired { actor: Actor::Mob(3), kind: StatusKind::Sleep };
+        assert_eq!(event_messages(&event, &catalog), vec!["ID3の眠り状態が治った。"]);
+    }
+
+    #[test]
+    fn test_terrain_messages() {
+        let catalog = MessageCatalog::default_catalog("ja").unwrap();
+        let event = GameEvent::TerrainDamaged { actor: Actor::Player, tile: Tile::Lava, damage: 10 };
+        assert_eq!(event.kind(), "terrain_damaged");
+        assert_eq!(event_messages(&event, &catalog), vec!["プレイヤーは溶岩で10ダメージを受けた。"]);
+    }
+
+    #[test]
+    fn test_trap_messages() {
+        let catalog = MessageCatalog::default_catalog("ja").unwrap();
+        let event = GameEvent::TrapTriggered { actor: Actor::Player, kind: TrapKind::Alarm, position: (1, 1) };
+        assert_eq!(event.kind(), "trap_triggered");
+        assert_eq!(event_messages(&event, &catalog), vec!["警報の罠を踏んだ！", "けたたましい警報が鳴り響いた！"]);
+        let event = GameEvent::TrapDamaged { actor: Actor::Mob(2), kind: TrapKind::Damage, damage: 5 };
+        assert_eq!(event.kind(), "trap_damaged");
+        assert_eq!(event_messages(&event, &catalog), vec!["ID2はトゲの罠で5ダメージを受けた。"]);
+        let event = GameEvent::TrapKilled { actor: Actor::Mob(2), kind: TrapKind::Damage };
+        assert_eq!(event.kind(), "trap_killed");
+        assert_eq!(event_messages(&event, &catalog), vec!["ID2はトゲの罠で力尽きた。"]);
+        let event = GameEvent::TrapRevealed { kind: TrapKind::Pit, position: (1, 1) };
+        assert_eq!(event_messages(&event, &catalog), vec!["落とし穴を見つけた。"]);
+        // 罠を見つけた場合は見つけた罠ごとのメッセージだけを出す
+        assert_eq!(event_messages(&GameEvent::Searched { found: 0 }, &catalog), vec!["周りを調べたが何も見つからなかった。"]);
+        assert!(event_messages(&GameEvent::Searched { found: 1 }, &catalog).is_empty());
+    }
+
+    #[test]
+    fn test_door_messages() {
+        let catalog = MessageCatalog::default_catalog("ja").unwrap();
+        let event = GameEvent::DoorOpened { actor: Actor::Player, position: (3, 4), unlocked: true };
+        assert_eq!(event.kind(), "door_opened");
+        assert_eq!(event_messages(&event, &catalog), vec!["鍵を使って扉を開けた。"]);
+        let event = GameEvent::DoorOpened { actor: Actor::Mob(1), position: (3, 4), unlocked: false };
+        assert!(event_messages(&event, &catalog).is_empty());
+        assert_eq!(event_messages(&GameEvent::DoorLocked { position: (3, 4) }, &catalog), vec!["扉には鍵がかかっている。"]);
+    }
+}
@@ -0,0 +1,257 @@
+//! プレイヤーの視界(FOV)と探索済みの範囲を計算するモジュール
+//!
+//! 視界の計算にはシャドウキャスティングを使う。
+//! 視界を8つの八分円に分け、それぞれで壁が作る影を追跡しながら1列ずつ走査する。
+
+use crate::tile::Tile;
+
+use serde::{Deserialize, Serialize};
+
+/// 八分円ごとの座標変換
+const OCTANTS: [(i32, i32, i32, i32); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+/// 視界の中心と半径
+struct LightSource {
+    x: i32,
+    y: i32,
+    radius: i32,
+}
+
+/// 各マスが現在見えているか、一度でも見えたことがあるかを管理するクラス
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FieldOfView {
+    /// マップの幅
+    pub width: i32,
+    /// マップの高さ
+    pub height: i32,
+    /// 現在見えているマス、dungeon_map_2dと同じく[x][y]の順で参照する
+    pub visible: Vec<Vec<bool>>,
+    /// 一度でも見えたことがあるマス
+    pub explored: Vec<Vec<bool>>,
+}
+
+impl FieldOfView {
+    /// 何も見えていない状態で生成する
+    pub fn new(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            visible: vec![vec![false; height as usize]; width as usize],
+            explored: vec![vec![false; height as usize]; width as usize],
+        }
+    }
+
+    /// 指定したマスが現在見えているかどうか、マップ外はfalse
+    pub fn is_visible(&self, x: i32, y: i32) -> bool {
+        self.in_bounds(x, y) && self.visible[x as usize][y as usize]
+    }
+
+    /// 指定したマスが一度でも見えたことがあるかどうか、マップ外はfalse
+    pub fn is_explored(&self, x: i32, y: i32) -> bool {
+        self.in_bounds(x, y) && self.explored[x as usize][y as usize]
+    }
+
+    /// 現在見えているマスの一覧
+    pub fn visible_tiles(&self) -> Vec<(i32, i32)> {
+        let mut tiles = vec![];
+        for x in 0..self.width {
+            for y in 0..self.height {
+                if self.visible[x as usize][y as usize] {
+                    tiles.push((x, y));
+                }
+            }
+        }
+        tiles
+    }
+
+    /// 視界を計算し直す
+    ///
+    /// 床(0)以外のマスは視線を遮る。壁そのものは見えるマスとして扱う。
+    /// マップの大きさが変わっていた場合は探索済みの情報も初期化する。
+    ///
+    /// # Arguments
+    /// * `map` - 静的マップ、[x][y]の順で参照する
+    /// * `origin` - 視界の中心(プレイヤーの位置)
+    /// * `radius` - 視界の半径
+    pub fn compute(&mut self, map: &[Vec<Tile>], origin: (i32, i32), radius: i32) {
+        let width = map.len() as i32;
+        let height = map.first().map_or(0, |column| column.len() as i32);
+        if width != self.width || height != self.height {
+            *self = Self::new(width, height);
+        }
+        for column in self.visible.iter_mut() {
+            column.fill(false);
+        }
+
+        let (x, y) = origin;
+        if !self.in_bounds(x, y) {
+            return;
+        }
+        self.mark_visible(x, y);
+        let source = LightSource { x, y, radius };
+        for octant in &OCTANTS {
+            self.cast_light(map, &source, *octant, 1, 1.0, 0.0);
+        }
+    }
+
+    fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && x < self.width && y >= 0 && y < self.height
+    }
+
+    fn is_opaque(&self, map: &[Vec<Tile>], x: i32, y: i32) -> bool {
+        !self.in_bounds(x, y) || !map[x as usize][y as usize].is_transparent()
+    }
+
+    fn mark_visible(&mut self, x: i32, y: i32) {
+        if self.in_bounds(x, y) {
+            self.visible[x as usize][y as usize] = true;
+            self.explored[x as usize][y as usize] = true;
+        }
+    }
+
+    /// 1つの八分円について、row列目からstart_slopeとend_slopeの間を走査する
+    fn cast_light(
+        &mut self,
+        map: &[Vec<Tile>],
+        source: &LightSource,
+        octant: (i32, i32, i32, i32),
+        row: i32,
+        mut start_slope: f32,
+        end_slope: f32,
+    ) {
+        if start_slope < end_slope {
+            return;
+        }
+        let (xx, xy, yx, yy) = octant;
+        let radius2 = source.radius * source.radius;
+        let mut next_start_slope = start_slope;
+        for j in row..=source.radius {
+            let dy = -j;
+            let mut blocked = false;
+            for dx in -j..=0 {
+                let x = source.x + dx * xx + dy * xy;
+                let y = source.y + dx * yx + dy * yy;
+                // マスの左右の端の傾き
+                let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+                if start_slope < right_slope {
+                    continue;
+                } else if end_slope > left_slope {
+                    break;
+                }
+
+                if dx * dx + dy * dy <= radius2 {
+                    self.mark_visible(x, y);
+                }
+
+                if blocked {
+                    // 影の中を走査している
+                    if self.is_opaque(map, x, y) {
+                        next_start_slope = right_slope;
+                    } else {
+                        blocked = false;
+                        start_slope = next_start_slope;
+                    }
+                } else if self.is_opaque(map, x, y) && j < source.radius {
+                    // 影の始まり、影より手前の範囲を次の列から再帰的に走査する
+                    blocked = true;
+                    self.cast_light(map, source, octant, j + 1, start_slope, left_slope);
+                    next_start_slope = right_slope;
+                }
+            }
+            if blocked {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 外周が壁の部屋
+    fn room(width: usize, height: usize) -> Vec<Vec<Tile>> {
+        let mut map = vec![vec![Tile::Floor; height]; width];
+        for column in map.iter_mut() {
+            column[0] = Tile::Wall;
+            column[height - 1] = Tile::Wall;
+        }
+        map[0].fill(Tile::Wall);
+        map[width - 1].fill(Tile::Wall);
+        map
+    }
+
+    #[test]
+    fn test_open_room() {
+        // 遮るものがない部屋では半径内のすべてのマスと壁が見える
+        let map = room(11, 11);
+        let mut fov = FieldOfView::new(11, 11);
+        fov.compute(&map, (5, 5), 10);
+        for x in 0..11 {
+            for y in 0..11 {
+                assert!(fov.is_visible(x, y), "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_radius() {
+        let map = room(21, 21);
+        let mut fov = FieldOfView::new(21, 21);
+        fov.compute(&map, (10, 10), 3);
+        assert!(fov.is_visible(13, 10));
+        assert!(fov.is_visible(12, 12));
+        assert!(!fov.is_visible(14, 10));
+        assert!(!fov.is_visible(13, 13));
+    }
+
+    #[test]
+    fn test_wall_blocks_view() {
+        // 縦の壁の向こう側は見えない
+        let mut map = room(11, 11);
+        map[6][1..10].fill(Tile::Wall);
+        let mut fov = FieldOfView::new(11, 11);
+        fov.compute(&map, (3, 5), 10);
+        assert!(fov.is_visible(6, 5));
+        for y in 1..10 {
+            assert!(!fov.is_visible(7, y), "(7, {})", y);
+            assert!(!fov.is_visible(8, y), "(8, {})", y);
+        }
+    }
+
+    #[test]
+    fn test_explored() {
+        // 見えなくなったマスも探索済みとして残る
+        let mut map = room(11, 11);
+        map[5][1..10].fill(Tile::Wall);
+        map[5][5] = Tile::Floor;
+        let mut fov = FieldOfView::new(11, 11);
+        fov.compute(&map, (7, 5), 10);
+        assert!(fov.is_visible(9, 2));
+        fov.compute(&map, (3, 5), 10);
+        assert!(!fov.is_visible(9, 2));
+        assert!(fov.is_explored(9, 2));
+        assert!(!fov.is_explored(-1, 0));
+        assert!(fov.visible_tiles().iter().all(|&(x, y)| fov.is_explored(x, y)));
+    }
+
+    #[test]
+    fn test_resize() {
+        // マップの大きさが変わったら探索済みの情報は初期化される
+        let mut fov = FieldOfView::new(11, 11);
+        fov.compute(&room(11, 11), (5, 5), 10);
+        fov.compute(&room(7, 7), (3, 3), 1);
+        assert_eq!(fov.width, 7);
+        assert!(!fov.is_explored(5, 5));
+    }
+}
@@ -1,17 +1,70 @@
 //! ゲーム全体を管理するモジュール、Godot側からはこのモジュールが呼び出される
 
 use godot::prelude::*;
+use godot::engine::FileAccess;
+use godot::engine::DirAccess;
+use godot::engine::file_access::ModeFlags;
 use crate::player::Direction;
+use crate::player::HUNGRY_SATIETY;
 use crate::item::GameItem;
 use crate::item::HealthPotion;
 use crate::item::DroppedItem;
 use crate::item::SideEffect;
+use crate::item::EquipSlot;
+use crate::item::Key;
 use crate::mob::GameMob;
 use crate::static_map::StaticMapManager;
+use crate::tile::DoorState;
+use crate::tile::Tile;
+use crate::tile::tile_at;
+use crate::trap::Trap;
+use crate::trap::TrapKind;
 use crate::dynamic_map::DynamicMapManager;
-
+use crate::map_generator::floor_seed;
+use crate::map_generator::choose_reachable_start_and_goal_rooms;
+use crate::map_generator::GeneratorKind;
+use crate::save_data::SaveData;
+use crate::combat::AttackResult;
+use crate::combat::DamageFormula;
+use crate::combat::StandardDamageFormula;
+use crate::catalog::MobCatalog;
+use crate::catalog::ItemCatalog;
+use crate::catalog::DOOR_KEY_ITEM;
+use crate::fov::FieldOfView;
+use crate::pathfinding::DiagonalRule;
+use crate::pathfinding::PathFinder;
+use crate::ai::AiContext;
+use crate::ai::MobAction;
+use crate::ai::MobState;
+use crate::scheduler::schedule_turn;
+use crate::scheduler::Speed;
+use crate::event::Actor;
+use crate::event::GameEvent;
+use crate::event::event_messages;
+use crate::locale::MessageCatalog;
+use crate::locale::LOCALES;
+use crate::locale::DEFAULT_LOCALE;
+use crate::locale::default_catalogs;
+use crate::game_state::GameState;
+use crate::game_state::CauseOfDeath;
+use crate::game_state::DeathCause;
+use crate::run_summary::RunStats;
+use crate::run_summary::RunSummary;
+use crate::projectile;
+use crate::attack_pattern::retain_reachable;
+use crate::status_effect::StatusEffect;
+use crate::status_effect::StatusEffects;
+use crate::status_effect::StatusKind;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 #[derive(GodotClass)]
 #[class(base=Node3D)]
@@ -24,6 +77,42 @@ pub struct GameMaster {
     /// 現在の階層
     #[export]
     pub current_level: i32,
+    /// 最後の階層、この階層を踏破するとゲームクリア。0以下の場合は終わりがない
+    #[export]
+    pub final_floor: i32,
+    /// ゲームの進行状態
+    pub game_state: GameState,
+    /// プレイヤーが力尽きた原因、力尽きていない場合はNone
+    pub cause_of_death: Option<CauseOfDeath>,
+    /// ここまでのプレイの記録
+    pub run_stats: RunStats,
+    /// プレイの記録を書き出すディレクトリ、空の場合は書き出さない
+    #[export]
+    pub morgue_dir: GString,
+    /// 現在の階層のマップが生成済みかどうか、次の階層へ移動するときや始め直すときはfalseに戻す
+    pub level_ready: bool,
+    /// プレイヤーが落とし穴に落ちて、次の階層へ移動するのを待っているかどうか
+    pub falling: bool,
+    /// プレイヤーの初期位置とゴールを別の部屋に配置するかどうか
+    #[export]
+    pub separate_start_and_goal: bool,
+
+    /// 乱数のシード値、0の場合は最初のマップ生成時にランダムに決める
+    /// 同じシード値からは常に同じマップと配置が生成される
+    #[export]
+    pub seed: i64,
+    /// マップ生成や配置に使う乱数生成器
+    pub rng: StdRng,
+
+    /// マップ生成アルゴリズム
+    /// "bsp"、"cave"、"drunkard"、"maze"、"vault"のいずれか、"random"の場合は階層ごとにランダムに選ぶ
+    /// 知らない名前の場合は"bsp"とする
+    #[export]
+    pub map_generator: GString,
+    /// 階層ごとのマップ生成アルゴリズム、n番目が(n+1)階に使われる
+    /// 空文字列の階層や、リストより深い階層ではmap_generatorを使う
+    #[export]
+    pub floor_map_generators: Array<GString>,
 
     /// マップの幅
     #[export]
@@ -35,9 +124,22 @@ pub struct GameMaster {
     #[export]
     pub dungeon_map_1d: Array<i32>,
 
-    /// そのターンに発行されたメッセージ
+    /// プレイヤーの視界の半径
+    #[export]
+    pub view_radius: i32,
+    /// プレイヤーの視界と探索済みの範囲
+    pub field_of_view: FieldOfView,
+
+    /// 経路探索での斜め移動のルール
+    /// 0: 斜め移動しない、1: 両脇が床の場合のみ、2: 片側が床なら可、3: 常に可
+    #[export]
+    pub diagonal_rule: i32,
+
+    /// そのターンに発行されたメッセージ、eventsから作られる
     #[export]
     pub message: Array<GString>,
+    /// そのターンに起きた出来事
+    pub events: Vec<GameEvent>,
 
     /// 初期配置するアイテムの数
     #[export]
@@ -51,6 +153,66 @@ pub struct GameMaster {
     #[export]
     pub mob_drop_item_probability: f32,
 
+    /// 敵の種類のカタログのパス、読み込めない場合は埋め込みの既定値を使う
+    #[export]
+    pub mob_catalog_path: GString,
+    /// 敵の種類のカタログ
+    pub mob_catalog: MobCatalog,
+    /// アイテムのカタログのパス、読み込めない場合は埋め込みの既定値を使う
+    #[export]
+    pub item_catalog_path: GString,
+    /// アイテムのカタログ
+    pub item_catalog: ItemCatalog,
+
+    /// 表示する言語、用意していない言語の場合は既定の言語(ja)を使う
+    #[export]
+    pub locale: GString,
+    /// メッセージのカタログを置いたディレクトリ、言語ごとにmessages_{言語}.jsonを読み込む
+    #[export]
+    pub message_catalog_dir: GString,
+    /// 言語ごとのメッセージのカタログ
+    pub message_catalogs: HashMap<String, MessageCatalog>,
+
+    /// 防御力がダメージを減らす割合
+    #[export]
+    pub defense_factor: f32,
+    /// 攻撃が命中した場合の最低ダメージ
+    #[export]
+    pub min_damage: i32,
+    /// ダメージの振れ幅、0.1なら±10%
+    #[export]
+    pub damage_variance: f32,
+    /// 会心の一撃が出る確率
+    #[export]
+    pub critical_rate: f32,
+    /// 会心の一撃のダメージ倍率
+    #[export]
+    pub critical_multiplier: f32,
+    /// 攻撃が外れる確率
+    #[export]
+    pub miss_rate: f32,
+    /// アイテムを投げたときに届くマス数
+    #[export]
+    pub throw_range: i32,
+    /// 1ターンごとに減る満腹度
+    #[export]
+    pub satiety_drain_rate: f32,
+    /// 満腹度が0のときに1ターンごとに受けるダメージ
+    #[export]
+    pub starvation_damage: i32,
+    /// 扉ごとに鍵をかける確率
+    #[export]
+    pub locked_door_probability: f32,
+    /// 部屋ごとに仕掛ける罠の数の上限
+    #[export]
+    pub traps_per_room: i32,
+    /// 罠を踏んだときに受けるダメージ
+    #[export]
+    pub trap_damage: i32,
+    /// 周りを調べたときに罠を見つけられる範囲
+    #[export]
+    pub search_radius: i32,
+
     /// そのターンにプレイヤーが行った攻撃情報
     pub player_attack_info: Vec<(i32, i32, i32)>,
     /// そのターンにプレイヤーが行ったアイテム使用情報
@@ -76,13 +238,49 @@ impl INode3D for GameMaster {
     fn init(base: Base<Node3D>) -> Self {
         Self {
             current_level: 1,
+            final_floor: 10,
+            throw_range: 8,
+            satiety_drain_rate: 0.1,
+            starvation_damage: 1,
+            locked_door_probability: 0.2,
+            traps_per_room: 1,
+            trap_damage: 8,
+            search_radius: 1,
+            game_state: GameState::Playing,
+            cause_of_death: None,
+            run_stats: RunStats::default(),
+            morgue_dir: "user://morgue".into(),
+            level_ready: false,
+            falling: false,
+            separate_start_and_goal: true,
+            seed: 0,
+            rng: StdRng::seed_from_u64(0),
             initial_item_count: 10,
             initial_mob_count: 10,
             mob_drop_item_probability: 0.5,
+            mob_catalog_path: "res://data/mobs.json".into(),
+            mob_catalog: MobCatalog::default_catalog(),
+            item_catalog_path: "res://data/items.json".into(),
+            item_catalog: ItemCatalog::default_catalog(),
+            locale: DEFAULT_LOCALE.into(),
+            message_catalog_dir: "res://data".into(),
+            message_catalogs: default_catalogs(),
+            defense_factor: 0.5,
+            min_damage: 1,
+            damage_variance: 0.1,
+            critical_rate: 0.05,
+            critical_multiplier: 1.5,
+            miss_rate: 0.05,
+            map_generator: "bsp".into(),
+            floor_map_generators: Array::new(),
             dungeon_width: 100,
             dungeon_height: 100,
             dungeon_map_1d: Array::new(),
+            view_radius: 8,
+            field_of_view: FieldOfView::new(100, 100),
+            diagonal_rule: 1,
             message: Array::new(),
+            events: vec![],
             player_attack_info: vec![],
             player_side_effect_info: vec![],
             mob_attack_info: vec![],
@@ -104,19 +302,103 @@ impl INode3D for GameMaster {
 
 #[godot_api]
 impl GameMaster {
+    /// プレイヤーが敵の攻撃でダメージを受けた
+    #[signal]
+    fn player_damaged(attacker_id: i32, damage: i32, hp: i32);
+
+    /// 敵を倒した、落とし穴に落ちて階層からいなくなった場合も発行する
+    #[signal]
+    fn mob_defeated(mob_id: i32, position: Vector2i);
+
+    /// アイテムが床に落ちた
+    #[signal]
+    fn item_dropped(item_id: i32, position: Vector2i);
+
+    /// プレイヤーがアイテムを拾った
+    #[signal]
+    fn item_picked_up(item_id: i32);
+
+    /// プレイヤーのレベルが上がった
+    #[signal]
+    fn level_up(level: i32);
+
+    /// プレイヤーが力尽きた
+    #[signal]
+    fn game_over();
+
+    /// 階層を踏破して次の階層に進む
+    #[signal]
+    fn floor_cleared(level: i32);
+
+    /// 最後の階層を踏破した
+    #[signal]
+    fn won(level: i32);
+
+    /// プレイヤーがゴールに到達した
+    #[signal]
+    fn goal_reached(position: Vector2i);
+
+    /// プレイヤーか敵の状態異常が変わった、プレイヤーのIDは-1
+    #[signal]
+    fn status_changed(actor_id: i32);
+
+    /// 扉が開くなどして地形が変わった、tileはdungeon_map_1dと同じ値
+    #[signal]
+    fn tile_changed(position: Vector2i, tile: i32);
+
+    /// プレイヤーが罠を見つけた
+    #[signal]
+    fn trap_revealed(position: Vector2i);
+
     /// インスタンスを生成
     #[func]
     pub fn new() -> Gd<Self> {
         Gd::from_init_fn(|base| {
             Self {
                 current_level: 1,
+                final_floor: 10,
+                throw_range: 8,
+                satiety_drain_rate: 0.1,
+                starvation_damage: 1,
+                locked_door_probability: 0.2,
+                traps_per_room: 1,
+                trap_damage: 8,
+                search_radius: 1,
+                game_state: GameState::Playing,
+                cause_of_death: None,
+                run_stats: RunStats::default(),
+                morgue_dir: "user://morgue".into(),
+                level_ready: false,
+                falling: false,
+                separate_start_and_goal: true,
+                seed: 0,
+                rng: StdRng::seed_from_u64(0),
                 initial_item_count: 10,
                 initial_mob_count: 10,
                 mob_drop_item_probability: 0.5,
+                mob_catalog_path: "res://data/mobs.json".into(),
+                mob_catalog: MobCatalog::default_catalog(),
+                item_catalog_path: "res://data/items.json".into(),
+                item_catalog: ItemCatalog::default_catalog(),
+                locale: DEFAULT_LOCALE.into(),
+                message_catalog_dir: "res://data".into(),
+                message_catalogs: default_catalogs(),
+                defense_factor: 0.5,
+                min_damage: 1,
+                damage_variance: 0.1,
+                critical_rate: 0.05,
+                critical_multiplier: 1.5,
+                miss_rate: 0.05,
+                map_generator: "bsp".into(),
+                floor_map_generators: Array::new(),
                 dungeon_width: 100,
                 dungeon_height: 100,
                 dungeon_map_1d: Array::new(),
+                view_radius: 8,
+                field_of_view: FieldOfView::new(100, 100),
+                diagonal_rule: 1,
                 message: Array::new(),
+                events: vec![],
                 player_attack_info: vec![],
                 player_side_effect_info: vec![],
                 mob_attack_info: vec![],
@@ -132,17 +414,191 @@ impl GameMaster {
     }
 
     /// 次の階層へ移動する際に、現在の階層を一つ進める
+    ///
+    /// 最後の階層を踏破した場合は次の階層に進まず、ゲームクリアの状態になる。
     #[func]
     pub fn next_level(&mut self) {
+        if !self.game_state.can_act() {
+            return;
+        }
+        let cleared_level = self.current_level;
+        self.emit_deferred("floor_cleared", &[cleared_level.to_variant()]);
+        self.game_state = GameState::after_floor_cleared(cleared_level, self.final_floor);
+        if self.game_state == GameState::Won {
+            self.push_event(GameEvent::Won { level: cleared_level });
+            self.write_run_summary();
+            return;
+        }
         self.current_level += 1;
+        self.level_ready = false;
+    }
+
+    /// プレイヤーがゴールの上にいるかどうか
+    #[func]
+    pub fn is_player_on_goal(&self) -> bool {
+        self.dynamic_map_manager.player.position == self.dynamic_map_manager.goal_position
+    }
+
+    /// ゴールから次の階層へ移動する
+    ///
+    /// 階層を一つ進めて現在と同じ大きさのマップを生成し直す。プレイヤーの状態はそのまま引き継ぐ。
+    ///
+    /// # Returns
+    /// 次の階層へ移動した場合はtrue、ゴールの上にいない場合や最後の階層を踏破した場合はfalseを返す
+    #[func]
+    pub fn descend(&mut self) -> bool {
+        if !self.game_state.can_act() || self.falling || !self.is_player_on_goal() {
+            return false;
+        }
+        self.move_to_next_level()
+    }
+
+    /// 落とし穴から次の階層へ落ちる
+    ///
+    /// # Returns
+    /// 次の階層へ移動した場合はtrue、落とし穴に落ちていない場合や力尽きた場合はfalseを返す
+    #[func]
+    pub fn fall_into_pit(&mut self) -> bool {
+        if !self.game_state.can_act() || !self.falling {
+            return false;
+        }
+        self.falling = false;
+        self.move_to_next_level()
+    }
+
+    /// 階層を一つ進めて現在と同じ大きさのマップを生成し直す
+    fn move_to_next_level(&mut self) -> bool {
+        self.next_level();
+        if self.game_state != GameState::Descending {
+            return false;
+        }
+        self.initialize_level(self.dungeon_width, self.dungeon_height);
+        self.push_event(GameEvent::Descended { level: self.current_level });
+        true
+    }
+
+    /// 現在の階層のマップが生成済みかどうか、生成済みでなければinitialize_levelを呼び出す必要がある
+    #[func]
+    pub fn is_level_ready(&self) -> bool {
+        self.level_ready
+    }
+
+    /// 最初の階層から新しく始め直す、マップの生成はこの後initialize_levelで行う
+    ///
+    /// シード値も決め直すので、前回とは異なるダンジョンになる。
+    #[func]
+    pub fn restart_run(&mut self) {
+        self.current_level = 1;
+        self.seed = 0;
+        self.game_state = GameState::Playing;
+        self.cause_of_death = None;
+        self.run_stats = RunStats::default();
+        self.dynamic_map_manager = DynamicMapManager::new();
+        self.level_ready = false;
+        self.falling = false;
+        self.clear_turn_info();
+    }
+
+    /// ゲームの進行状態をgodotに渡す
+    ///
+    /// # Returns
+    /// 0: プレイ中、1: 力尽きた、2: 次の階層へ移動中、3: 踏破した
+    #[func]
+    pub fn get_game_state(&self) -> i32 {
+        self.game_state.to_i32()
+    }
+
+    /// プレイヤーが力尽きた原因を表示用の文字列にしてgodotに渡す
+    ///
+    /// # Returns
+    /// 力尽きた原因、力尽きていない場合は空文字列を返す
+    #[func]
+    pub fn get_cause_of_death(&self) -> GString {
+        match &self.cause_of_death {
+            Some(cause) => cause.text(self.messages()).into(),
+            None => GString::new(),
+        }
+    }
+
+    /// 現在の状態からプレイの記録を作成する
+    pub fn run_summary(&self) -> RunSummary {
+        RunSummary::new(
+            self.seed,
+            self.current_level,
+            self.game_state,
+            self.cause_of_death.as_ref(),
+            &self.run_stats,
+            &self.dynamic_map_manager.player)
+    }
+
+    /// プレイの記録を人が読むための文章にしてgodotに渡す
+    #[func]
+    pub fn get_run_summary_text(&self) -> GString {
+        self.run_summary().to_text(self.messages(), &self.mob_catalog).into()
+    }
+
+    /// プレイの記録をJSON文字列にしてgodotに渡す
+    #[func]
+    pub fn get_run_summary_json(&self) -> GString {
+        self.run_summary().to_json().into()
+    }
+
+    /// プレイの記録をmorgue_dirに文章とJSONの2つのファイルで書き出す
+    ///
+    /// ファイル名はシード値と書き出した時刻から決め、同じシード値で遊び直しても前の記録を上書きしない。
+    /// 力尽きたときと踏破したときに呼び出す。
+    fn write_run_summary(&self) {
+        if self.morgue_dir.is_empty() {
+            return;
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        DirAccess::make_dir_recursive_absolute(self.morgue_dir.clone());
+        let summary = self.run_summary();
+        let files = [
+            ("txt", summary.to_text(self.messages(), &self.mob_catalog)),
+            ("json", summary.to_json()),
+        ];
+        for (extension, contents) in files {
+            let path = format!("{}/run_{}_{}.{}", self.morgue_dir, self.seed, timestamp, extension);
+            match FileAccess::open(path.clone().into(), ModeFlags::WRITE) {
+                Some(mut file) => {
+                    file.store_string(contents.into());
+                    file.close();
+                    godot_print!("run summary saved: {}", path);
+                },
+                None => godot_print!("failed to open {}", path),
+            }
+        }
     }
 
     /// 一番最初にマップ生成を行う関数
     #[func]
     pub fn initialize_level(&mut self, width: i32, height: i32) {
+        // 次の階層へ移動中の状態から、新しい階層でのプレイを始める
+        self.game_state = GameState::Playing;
+        self.level_ready = true;
+        // シード値が未指定の場合はランダムに決めて、Godot側から参照できるように保存する
+        if self.seed == 0 {
+            self.seed = rand::thread_rng().gen_range(1..i64::MAX);
+        }
+        // シード値と階層から乱数生成器を初期化し、以降の生成と配置はすべてこれを使う
+        self.rng = StdRng::seed_from_u64(floor_seed(self.seed as u64, self.current_level));
+        godot_print!("seed: {}, level: {}", self.seed, self.current_level);
+
+        // カタログはデータファイルの調整がすぐに反映されるよう、階層ごとに読み直す
+        self.load_catalogs();
+
         // 静的マップの生成
-        self.static_map_manager.generate_dungeon(width, height);
+        let map_seed = self.rng.gen::<u64>();
+        let generator = self.map_generator_kind();
+        godot_print!("map generator: {}", generator.key());
+        self.static_map_manager.generate_with(generator, width, height, Some(map_seed));
         self.copy_from_static_map_manager();
+        // 新しい階層は何も探索していない状態から始める
+        self.field_of_view = FieldOfView::new(width, height);
 
         // 動的マップの初期化
         self.dynamic_map_manager.clear();
@@ -157,17 +613,26 @@ impl GameMaster {
                 (param.room_center_x, param.room_center_y);
             self.dynamic_map_manager.goal_position = 
                 (param.room_center_x, param.room_center_y);
+            self.place_stairs();
+            self.update_field_of_view();
             return;
         } else {
-            let position_idx = (rand::random::<f32>() * (n_position_candidates - 1) as f32) as usize;
-            let param = &self.static_map_manager.room_params[position_idx];
+            // マップ生成時につながりを確かめているが、ゴールは念のため初期位置からたどり着ける部屋に限る
+            let (start_idx, goal_idx) = choose_reachable_start_and_goal_rooms(
+                &self.static_map_manager.dungeon_map_2d,
+                &self.static_map_manager.room_params,
+                self.separate_start_and_goal,
+                &mut self.rng,
+            ).unwrap_or((0, 0));
+            let param = &self.static_map_manager.room_params[start_idx];
             self.dynamic_map_manager.player.position = 
                 (param.room_center_x, param.room_center_y);
-            let position_idx = (rand::random::<f32>() * (n_position_candidates - 1) as f32) as usize;
-            let param = &self.static_map_manager.room_params[position_idx];
+            let param = &self.static_map_manager.room_params[goal_idx];
             self.dynamic_map_manager.goal_position = 
                 (param.room_center_x, param.room_center_y);
+            self.place_stairs();
         }
+        self.update_field_of_view();
 
         // アイテムの初期位置を設定
         // 小部屋ごとに均一になるようにアイテムを配置したい
@@ -180,11 +645,13 @@ impl GameMaster {
                 if item_count >= self.initial_item_count {
                     break;
                 }
-                let x = param.x + (rand::random::<f32>() * param.width as f32) as i32;
-                let y = param.y + (rand::random::<f32>() * param.height as f32) as i32;
+                let x = param.x + (self.rng.gen::<f32>() * param.width as f32) as i32;
+                let y = param.y + (self.rng.gen::<f32>() * param.height as f32) as i32;
                 // 床である場所にのみアイテムを配置
-                if (self.static_map_manager.dungeon_map_2d[x as usize][y as usize] == 0) {
-                    let item = GameItem::HealthPotion(HealthPotion {heal_amount: 10});
+                if self.static_map_manager.dungeon_map_2d[x as usize][y as usize] == Tile::Floor {
+                    // 現在の階層に配置するアイテムをカタログから選ぶ
+                    let item = self.item_catalog.create_floor_item(self.current_level, &mut self.rng)
+                        .unwrap_or(GameItem::HealthPotion(HealthPotion {heal_amount: 10}));
                     let ditem = DroppedItem {
                         id: item_count as i32,
                         position: (x, y),
@@ -197,6 +664,30 @@ impl GameMaster {
             }
         }
         godot_print!("{} items generated (max: {})", item_count, self.initial_item_count);
+
+        // 扉の一部に鍵をかけ、開けるための鍵をプレイヤーがたどり着ける床に置く
+        let finder = self.path_finder();
+        let item_positions: Vec<(i32, i32)> = self.dynamic_map_manager.item_list.iter()
+            .map(|item| item.borrow().position)
+            .collect();
+        let key_positions = self.static_map_manager.lock_doors(
+            self.dynamic_map_manager.player.position, &item_positions,
+            self.locked_door_probability, &finder, &mut self.rng);
+        for position in &key_positions {
+            let item = self.item_catalog.get(DOOR_KEY_ITEM)
+                .map(|definition| definition.create_item(&mut self.rng))
+                .unwrap_or(GameItem::Key(Key { uses: 1 }));
+            let ditem = DroppedItem {
+                id: item_count,
+                position: *position,
+                item: RefCell::new(item)
+            };
+            self.dynamic_map_manager.item_list.push(RefCell::new(ditem));
+            item_count += 1;
+        }
+        godot_print!("{} doors locked", key_positions.len());
+        // 鍵をかけた扉をGodot側のマップにも反映する
+        self.copy_from_static_map_manager();
         self.current_item_id_max = item_count as i32;
 
         // 敵の初期位置を設定
@@ -208,23 +699,405 @@ impl GameMaster {
                 if mob_count >= self.initial_mob_count {
                     break;
                 }
-                let x = param.x + (rand::random::<f32>() * param.width as f32) as i32;
-                let y = param.y + (rand::random::<f32>() * param.height as f32) as i32;
+                let x = param.x + (self.rng.gen::<f32>() * param.width as f32) as i32;
+                let y = param.y + (self.rng.gen::<f32>() * param.height as f32) as i32;
                 // 床である場所にのみモブを配置
-                if (self.static_map_manager.dungeon_map_2d[x as usize][y as usize] == 0) {
-                    let mob = GameMob::new_from_level(mob_count as i32, x, y, self.current_level);
+                if self.static_map_manager.dungeon_map_2d[x as usize][y as usize] == Tile::Floor {
+                    // 現在の階層に出現する種類をカタログから選ぶ
+                    let mob = match self.mob_catalog.choose_species(self.current_level, &mut self.rng) {
+                        Some(species) => GameMob::new_from_species(mob_count, x, y, species, self.current_level),
+                        None => GameMob::new_from_level(mob_count, x, y, self.current_level),
+                    };
                     self.dynamic_map_manager.mob_list.push(RefCell::new(mob));
                     mob_count += 1;
                 }
             }
         }
         godot_print!("{} mobs generated (max: {})", mob_count, self.initial_mob_count);
+
+        // 罠の位置を設定
+        // 部屋ごとに上限の数だけ候補を選び、プレイヤーの初期位置とゴール以外の床に隠して置く
+        // 最後の階層には落ちる先がないので落とし穴を置かない
+        let allow_pit = self.current_level < self.final_floor;
+        let mut trap_count = 0;
+        for param in &self.static_map_manager.room_params {
+            for _ in 0..self.traps_per_room {
+                let x = param.x + (self.rng.gen::<f32>() * param.width as f32) as i32;
+                let y = param.y + (self.rng.gen::<f32>() * param.height as f32) as i32;
+                let position = (x, y);
+                if self.static_map_manager.dungeon_map_2d[x as usize][y as usize] != Tile::Floor
+                    || position == self.dynamic_map_manager.player.position
+                    || position == self.dynamic_map_manager.goal_position {
+                    continue;
+                }
+                let kind = TrapKind::choose(allow_pit, &mut self.rng);
+                if self.dynamic_map_manager.traps.add(Trap::new(kind, position)) {
+                    trap_count += 1;
+                }
+            }
+        }
+        godot_print!("{} traps generated", trap_count);
+    }
+
+    /// 現在の階層で使うマップ生成アルゴリズムを決める
+    fn map_generator_kind(&mut self) -> GeneratorKind {
+        let floor_key = usize::try_from(self.current_level - 1).ok()
+            .filter(|idx| *idx < self.floor_map_generators.len())
+            .map(|idx| self.floor_map_generators.get(idx))
+            .map(|key| key.to_string())
+            .filter(|key| !key.is_empty());
+        let key = floor_key.unwrap_or_else(|| self.map_generator.to_string());
+        if key == "random" {
+            return *GeneratorKind::ALL.choose(&mut self.rng).unwrap_or(&GeneratorKind::Bsp);
+        }
+        GeneratorKind::from_key(&key).unwrap_or(GeneratorKind::Bsp)
+    }
+
+    /// ゲームの状態をファイルに保存する
+    ///
+    /// # Arguments
+    /// * `path` - 保存先のパス、user://なども指定できる
+    ///
+    /// # Returns
+    /// 保存に成功した場合はtrueを返す、力尽きた後や階層の移動中は保存せずにfalseを返す
+    #[func]
+    pub fn save_game(&self, path: GString) -> bool {
+        if !self.game_state.can_save() {
+            godot_print!("cannot save in state: {:?}", self.game_state);
+            return false;
+        }
+        let data = SaveData {
+            falling: self.falling,
+            ..SaveData::new(
+                self.seed,
+                self.current_level,
+                self.current_item_id_max,
+                &self.static_map_manager,
+                &self.dynamic_map_manager,
+                &self.field_of_view,
+                &self.run_stats)
+        };
+        match FileAccess::open(path.clone(), ModeFlags::WRITE) {
+            Some(mut file) => {
+                file.store_string(data.to_json().into());
+                file.close();
+                godot_print!("game saved: {}", path);
+                true
+            },
+            None => {
+                godot_print!("failed to open {}", path);
+                false
+            }
+        }
+    }
+
+    /// ファイルからゲームの状態を読み込む
+    ///
+    /// # Arguments
+    /// * `path` - 読み込むファイルのパス
+    ///
+    /// # Returns
+    /// 読み込みに成功した場合はtrueを返す、失敗した場合は現在の状態を変更しない
+    #[func]
+    pub fn load_game(&mut self, path: GString) -> bool {
+        let json = FileAccess::get_file_as_string(path.clone()).to_string();
+        let data = match SaveData::from_json(&json) {
+            Ok(data) => data,
+            Err(e) => {
+                godot_print!("failed to load {}: {}", path, e);
+                return false;
+            }
+        };
+        self.seed = data.seed;
+        self.current_level = data.current_level;
+        self.current_item_id_max = data.current_item_id_max;
+        self.static_map_manager = data.static_map;
+        self.dynamic_map_manager = data.dynamic_map;
+        self.field_of_view = data.field_of_view;
+        self.run_stats = data.run_stats;
+        self.falling = data.falling;
+        // 乱数生成器の内部状態は保存できないので、シード値と階層から初期化し直す
+        self.rng = StdRng::seed_from_u64(floor_seed(self.seed as u64, self.current_level));
+        self.copy_from_static_map_manager();
+        // 古いセーブデータには探索済みの範囲がないので、大きさが合わなければ初期化される
+        self.update_field_of_view();
+
+        // 保存できるのはプレイ中だけなので、プレイ中の状態から再開する
+        self.game_state = GameState::Playing;
+        self.level_ready = true;
+        self.cause_of_death = None;
+        self.clear_turn_info();
+        godot_print!("game loaded: {}", path);
+        true
+    }
+
+    /// そのターンだけの情報をすべて破棄する
+    fn clear_turn_info(&mut self) {
+        self.message.clear();
+        self.events.clear();
+        self.player_attack_info.clear();
+        self.player_side_effect_info.clear();
+        self.mob_attack_info.clear();
+        self.mob_side_effect_info.clear();
+        self.dropped_item_added_ids.clear();
+        self.dropped_item_removed_ids.clear();
+    }
+
+    /// データファイルからカタログを読み込む
+    ///
+    /// 読み込みに失敗した場合は現在のカタログをそのまま使う。
+    /// 敵のドロップテーブルにアイテムのカタログにないキーがある場合も失敗とする。
+    pub fn load_catalogs(&mut self) {
+        if !self.item_catalog_path.is_empty() {
+            let json = FileAccess::get_file_as_string(self.item_catalog_path.clone()).to_string();
+            match ItemCatalog::from_json(&json) {
+                Ok(catalog) => self.item_catalog = catalog,
+                Err(e) => godot_print!("failed to load {}: {}", self.item_catalog_path, e),
+            }
+        }
+        if !self.mob_catalog_path.is_empty() {
+            let json = FileAccess::get_file_as_string(self.mob_catalog_path.clone()).to_string();
+            let result = MobCatalog::from_json(&json)
+                .and_then(|catalog| catalog.check_drop_items(&self.item_catalog).map(|_| catalog));
+            match result {
+                Ok(catalog) => self.mob_catalog = catalog,
+                Err(e) => godot_print!("failed to load {}: {}", self.mob_catalog_path, e),
+            }
+        }
+        if !self.message_catalog_dir.is_empty() {
+            for locale in LOCALES {
+                let path = format!("{}/messages_{}.json", self.message_catalog_dir, locale);
+                let json = FileAccess::get_file_as_string(path.clone().into()).to_string();
+                match MessageCatalog::from_json(&json) {
+                    Ok(catalog) => {
+                        self.message_catalogs.insert(locale.to_string(), catalog);
+                    },
+                    Err(e) => godot_print!("failed to load {}: {}", path, e),
+                }
+            }
+        }
     }
 
-    /// メッセージをクリア、godot側から呼び出される
+    /// メッセージと出来事をクリア、godot側から呼び出される
     #[func]
     pub fn clear_message(&mut self) {
         self.message.clear();
+        self.events.clear();
+    }
+
+    /// 現在の言語のメッセージのカタログ
+    pub fn messages(&self) -> &MessageCatalog {
+        self.message_catalogs.get(&self.locale.to_string())
+            .unwrap_or_else(|| &self.message_catalogs[DEFAULT_LOCALE])
+    }
+
+    /// メッセージIDから現在の言語のメッセージを作る、godot側で表示する文字列に使う
+    ///
+    /// # Arguments
+    /// * `key` - メッセージID
+    /// * `params` - メッセージ中の{名前}を置き換える値
+    #[func]
+    pub fn get_message(&self, key: GString, params: Dictionary) -> GString {
+        let params: Vec<(String, String)> = params.iter_shared()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let params: Vec<(&str, String)> = params.iter()
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect();
+        self.messages().text(&key.to_string(), &params).into()
+    }
+
+    /// 出来事を記録し、出来事から作ったメッセージを追加する
+    pub fn push_event(&mut self, event: GameEvent) {
+        for message in event_messages(&event, self.messages()) {
+            self.message.push(message.into());
+        }
+        self.emit_event_signal(&event);
+        self.run_stats.record_event(&event);
+        self.events.push(event);
+    }
+
+    /// 出来事に対応するシグナルを発行する、対応するシグナルがない出来事は何もしない
+    fn emit_event_signal(&mut self, event: &GameEvent) {
+        let (signal, args) = match event {
+            GameEvent::Attacked { attacker, target: Actor::Player, damage, .. } => {
+                let hp = self.dynamic_map_manager.player.hp;
+                ("player_damaged", vec![attacker.id().to_variant(), damage.to_variant(), hp.to_variant()])
+            },
+            GameEvent::Died { actor: Actor::Mob(id), position, .. } | GameEvent::Fell { actor: Actor::Mob(id), position } => {
+                ("mob_defeated", vec![id.to_variant(), Vector2i::new(position.0, position.1).to_variant()])
+            },
+            GameEvent::ItemDropped { item_id, position, .. } => {
+                ("item_dropped", vec![item_id.to_variant(), Vector2i::new(position.0, position.1).to_variant()])
+            },
+            GameEvent::ItemPickedUp { item_id, .. } => ("item_picked_up", vec![item_id.to_variant()]),
+            GameEvent::LevelUp { level } => ("level_up", vec![level.to_variant()]),
+            GameEvent::GameOver => ("game_over", vec![]),
+            GameEvent::GoalReached { position } => {
+                ("goal_reached", vec![Vector2i::new(position.0, position.1).to_variant()])
+            },
+            GameEvent::Won { level } => ("won", vec![level.to_variant()]),
+            GameEvent::StatusApplied { actor, .. } | GameEvent::StatusExpired { actor, .. } => {
+                ("status_changed", vec![actor.id().to_variant()])
+            },
+            GameEvent::DoorOpened { position, .. } => {
+                let tile = self.static_map_manager.tile(position.0, position.1).to_i32();
+                ("tile_changed", vec![Vector2i::new(position.0, position.1).to_variant(), tile.to_variant()])
+            },
+            GameEvent::TrapTriggered { position, .. } | GameEvent::TrapRevealed { position, .. } => {
+                ("trap_revealed", vec![Vector2i::new(position.0, position.1).to_variant()])
+            },
+            _ => return,
+        };
+        self.emit_deferred(signal, &args);
+    }
+
+    /// シグナルを発行する
+    ///
+    /// 受け取った側からGameMasterを呼び出せるよう、現在の呼び出しが終わってから発行する。
+    fn emit_deferred(&mut self, signal: &str, args: &[Variant]) {
+        let mut call_args = vec![StringName::from(signal).to_variant()];
+        call_args.extend_from_slice(args);
+        self.base_mut().call_deferred("emit_signal".into(), &call_args);
+    }
+
+    /// そのターンに起きた出来事をgodotに渡す
+    ///
+    /// # Returns
+    /// 出来事ごとのDictionaryを起きた順に並べた配列、"type"に出来事の種類が入る。
+    /// プレイヤーや敵はID(プレイヤーは-1)、位置はVector2iで入る。
+    #[func]
+    pub fn get_events(&self) -> Array<Dictionary> {
+        let mut events = Array::new();
+        for event in &self.events {
+            events.push(self.event_to_dictionary(event));
+        }
+        events
+    }
+
+    fn event_to_dictionary(&self, event: &GameEvent) -> Dictionary {
+        let mut dict = Dictionary::new();
+        dict.set("type", event.kind());
+        match event {
+            GameEvent::Moved { actor, from, to } => {
+                dict.set("actor", actor.id());
+                dict.set("from", Vector2i::new(from.0, from.1));
+                dict.set("to", Vector2i::new(to.0, to.1));
+            },
+            GameEvent::ProjectileFired { actor, path, item } => {
+                dict.set("actor", actor.id());
+                let mut positions = Array::<Vector2i>::new();
+                for (x, y) in path {
+                    positions.push(Vector2i::new(*x, *y));
+                }
+                dict.set("path", positions);
+                if let Some(item) = item {
+                    dict.set("item", self.item_to_string(item));
+                }
+            },
+            GameEvent::Attacked { attacker, target, damage, critical } => {
+                dict.set("attacker", attacker.id());
+                dict.set("target", target.id());
+                dict.set("damage", *damage);
+                dict.set("critical", *critical);
+            },
+            GameEvent::Missed { attacker, target } => {
+                dict.set("attacker", attacker.id());
+                if let Some(target) = target {
+                    dict.set("target", target.id());
+                }
+            },
+            GameEvent::Died { actor, position, defeated } => {
+                dict.set("actor", actor.id());
+                dict.set("position", Vector2i::new(position.0, position.1));
+                dict.set("defeated", *defeated);
+            },
+            GameEvent::Fell { actor, position } => {
+                dict.set("actor", actor.id());
+                dict.set("position", Vector2i::new(position.0, position.1));
+            },
+            GameEvent::TrapKilled { actor, kind } => {
+                dict.set("actor", actor.id());
+                dict.set("trap", kind.key());
+            },
+            GameEvent::ItemPickedUp { item_id, item } => {
+                dict.set("item_id", *item_id);
+                dict.set("item", self.item_to_string(item));
+            },
+            GameEvent::InventoryFull { item_id } => {
+                dict.set("item_id", *item_id);
+            },
+            GameEvent::ItemDropped { item_id, position, item } => {
+                dict.set("item_id", *item_id);
+                dict.set("position", Vector2i::new(position.0, position.1));
+                dict.set("item", self.item_to_string(item));
+            },
+            GameEvent::ItemUsed { item } => {
+                dict.set("item", self.item_to_string(item));
+            },
+            GameEvent::Unequipped { slot } | GameEvent::UnequipFailed { slot } => {
+                // player_unequipの引数と同じ値にする
+                let slot = match slot {
+                    EquipSlot::Weapon => 0,
+                    EquipSlot::Armor => 1,
+                };
+                dict.set("slot", slot);
+            },
+            GameEvent::LevelUp { level } => {
+                dict.set("level", *level);
+            },
+            GameEvent::GameOver => {},
+            GameEvent::GoalReached { position } => {
+                dict.set("position", Vector2i::new(position.0, position.1));
+            },
+            GameEvent::Descended { level } | GameEvent::Won { level } => {
+                dict.set("level", *level);
+            },
+            GameEvent::StatusApplied { actor, kind } | GameEvent::StatusExpired { actor, kind } => {
+                dict.set("actor", actor.id());
+                dict.set("status", kind.key());
+            },
+            GameEvent::StatusTicked { actor, kind, amount } => {
+                dict.set("actor", actor.id());
+                dict.set("status", kind.key());
+                dict.set("amount", *amount);
+            },
+            GameEvent::Hungry | GameEvent::Starving => {},
+            GameEvent::StarvationDamaged { damage } => {
+                dict.set("damage", *damage);
+            },
+            GameEvent::TerrainDamaged { actor, tile, damage } => {
+                dict.set("actor", actor.id());
+                dict.set("tile", tile.to_i32());
+                dict.set("damage", *damage);
+            },
+            GameEvent::DoorOpened { actor, position, unlocked } => {
+                dict.set("actor", actor.id());
+                dict.set("position", Vector2i::new(position.0, position.1));
+                dict.set("unlocked", *unlocked);
+            },
+            GameEvent::DoorLocked { position } => {
+                dict.set("position", Vector2i::new(position.0, position.1));
+            },
+            GameEvent::TrapDamaged { actor, kind, damage } => {
+                dict.set("actor", actor.id());
+                dict.set("trap", kind.key());
+                dict.set("damage", *damage);
+            },
+            GameEvent::TrapTriggered { actor, kind, position } => {
+                dict.set("actor", actor.id());
+                dict.set("trap", kind.key());
+                dict.set("position", Vector2i::new(position.0, position.1));
+            },
+            GameEvent::TrapRevealed { kind, position } => {
+                dict.set("trap", kind.key());
+                dict.set("position", Vector2i::new(position.0, position.1));
+            },
+            GameEvent::Searched { found } => {
+                dict.set("found", *found);
+            },
+        }
+        dict
     }
 
     /// goal_positionをgodotに渡す
@@ -244,8 +1117,16 @@ impl GameMaster {
     #[func]
     pub fn get_player_status(&self) -> GString {
         let player = &self.dynamic_map_manager.player;
-        format!("Level: {}\nHP: {} /{}\nAttack: {}\nDefense: {}\nexp: {}",
-            player.level, player.hp, player.max_hp, player.attack, player.defense, player.exp_point).into()
+        self.messages().text("player_status", &[
+            ("level", player.level.to_string()),
+            ("hp", player.hp.to_string()),
+            ("max_hp", player.max_hp.to_string()),
+            ("attack", player.attack.to_string()),
+            ("defense", player.defense.to_string()),
+            ("exp", player.exp_point.to_string()),
+            ("satiety", player.satiety_display().to_string()),
+            ("max_satiety", (player.max_satiety as i32).to_string()),
+        ]).into()
     }
 
     /// playerのアイテムリストをGStringのArrayにしてgodotに渡す
@@ -256,23 +1137,38 @@ impl GameMaster {
     pub fn get_player_items(&self) -> Array<GString> {
         let mut items = Array::new();
         for item in &self.dynamic_map_manager.player.items {
-            let item_str = match *item.borrow() {
-                GameItem::HealthPotion(potion) => {
-                    format!("Health Potion: {}", potion.heal_amount)
-                }
-                GameItem::Sword(sword) => {
-                    format!("Sword: {}", sword.attack_bonus)
-                }
-                GameItem::Shield(shield) => {
-                    format!("Shield: {}", shield.defense_bonus)
-                }
-                _ => "-".into()
-            };
-            items.push(item_str.into());
+            items.push(self.item_to_string(&item.borrow()).into());
         }
         items
     }
 
+    /// playerが装備している武器をGStringにしてgodotに渡す
+    ///
+    /// # Returns
+    /// 装備している武器、何も装備していない場合は"-"を返す
+    #[func]
+    pub fn get_player_weapon(&self) -> GString {
+        let weapon = self.dynamic_map_manager.player.weapon
+            .map_or(GameItem::Null, GameItem::Sword);
+        self.item_to_string(&weapon).into()
+    }
+
+    /// playerが装備している防具をGStringにしてgodotに渡す
+    ///
+    /// # Returns
+    /// 装備している防具、何も装備していない場合は"-"を返す
+    #[func]
+    pub fn get_player_armor(&self) -> GString {
+        let armor = self.dynamic_map_manager.player.armor
+            .map_or(GameItem::Null, GameItem::Shield);
+        self.item_to_string(&armor).into()
+    }
+
+    /// アイテムを表示用の文字列に変換する
+    fn item_to_string(&self, item: &GameItem) -> String {
+        self.messages().item_text(item)
+    }
+
     /// playerの位置
     #[func]
     pub fn get_player_position(&self) -> Vector2i {
@@ -299,9 +1195,87 @@ impl GameMaster {
         }
     }
 
+    /// playerが行動を指示できるかどうか、眠っている間は指示できずprocessでターンを進める
+    ///
+    /// 落とし穴に落ちている間も、fall_into_pitで次の階層へ移動するまでは指示できない。
+    #[func]
+    pub fn player_can_act(&self) -> bool {
+        self.game_state.can_act() && !self.falling && !self.dynamic_map_manager.player.status_effects.is_asleep()
+    }
+
+    /// プレイヤーが落とし穴に落ちて、次の階層へ移動するのを待っているかどうか
+    #[func]
+    pub fn is_falling(&self) -> bool {
+        self.falling
+    }
+
+    /// playerがかかっている状態異常をgodotに渡す
+    ///
+    /// # Returns
+    /// 状態異常ごとのDictionary、"status"に種類、"name"に表示名、"turns"に残りターン数、"potency"に強さが入る
+    #[func]
+    pub fn get_player_status_effects(&self) -> Array<Dictionary> {
+        self.status_effects_to_array(&self.dynamic_map_manager.player.status_effects)
+    }
+
+    /// 指定した敵がかかっている状態異常をgodotに渡す、中身はget_player_status_effectsと同じ
+    #[func]
+    pub fn get_mob_status_effects(&self, mob_id: i32) -> Array<Dictionary> {
+        self.dynamic_map_manager.mob_list.iter()
+            .find(|mob_rc| mob_rc.borrow().id == mob_id)
+            .map_or(Array::new(), |mob_rc| self.status_effects_to_array(&mob_rc.borrow().status_effects))
+    }
+
+    fn status_effects_to_array(&self, effects: &StatusEffects) -> Array<Dictionary> {
+        let mut result = Array::new();
+        for effect in &effects.effects {
+            let mut dict = Dictionary::new();
+            dict.set("status", effect.kind.key());
+            dict.set("name", self.messages().status_text(effect.kind));
+            dict.set("turns", effect.turns);
+            dict.set("potency", effect.potency);
+            result.push(dict);
+        }
+        result
+    }
+
+    /// playerを状態異常にかける
+    ///
+    /// # Arguments
+    /// * `status` - 状態異常の種類、"poison"、"sleep"、"confusion"、"regen"、"haste"、"slow"のいずれか
+    /// * `turns` - 続くターン数
+    /// * `potency` - 強さ
+    ///
+    /// # Returns
+    /// 知らない種類の場合はfalseを返す
+    #[func]
+    pub fn apply_player_status_effect(&mut self, status: GString, turns: i32, potency: i32) -> bool {
+        let Some(kind) = StatusKind::from_key(&String::from(&status)) else {
+            return false;
+        };
+        self.add_status_effect(Actor::Player, StatusEffect::new(kind, turns, potency));
+        true
+    }
+
+    /// プレイヤーか敵を状態異常にかける
+    fn add_status_effect(&mut self, actor: Actor, effect: StatusEffect) {
+        let added = match actor {
+            Actor::Player => self.dynamic_map_manager.player.status_effects.add(effect),
+            Actor::Mob(id) => self.dynamic_map_manager.mob_list.iter()
+                .find(|mob_rc| mob_rc.borrow().id == id)
+                .is_some_and(|mob_rc| mob_rc.borrow_mut().status_effects.add(effect)),
+        };
+        if added {
+            self.push_event(GameEvent::StatusApplied { actor, kind: effect.kind });
+        }
+    }
+
     /// playerに向きを指示、ターンを消費しない
     #[func]
     pub fn player_turn(&mut self, direction: i32) {
+        if !self.player_can_act() {
+            return;
+        }
         let player_dir = match direction {
             0 => Direction::Up,
             1 => Direction::UpRight,
@@ -326,12 +1300,46 @@ impl GameMaster {
     #[func]
     pub fn player_move(&mut self, next_position: Vector2i) -> bool {
         let mut result = false;
+        if !self.player_can_act() {
+            return result;
+        }
         // ターンの最初にアイテムの差分をクリア
         self.dropped_item_added_ids.clear();
         self.dropped_item_removed_ids.clear();
 
-        // まず移動先がstatic_map上でfreeであることを確認
-        if (self.static_map_manager.dungeon_map_2d[next_position.x as usize][next_position.y as usize] == 0) {
+        // 混乱している場合はでたらめな方向へ進むことがある、進めなくてもターンは消費する
+        let mut next_position = next_position;
+        let stumbled = match self.dynamic_map_manager.player.status_effects.confused_direction(&mut self.rng) {
+            Some(direction) => {
+                let (x, y) = self.dynamic_map_manager.player.position;
+                let (dx, dy) = direction.delta();
+                next_position = Vector2i::new(x + dx, y + dy);
+                self.dynamic_map_manager.player.direction = direction;
+                true
+            },
+            None => false,
+        };
+
+        // 閉じた扉に向かって進んだ場合は移動せずに扉を開ける、鍵のかかった扉は鍵を持っていれば開けられる
+        let door_position = (next_position.x, next_position.y);
+        match self.static_map_manager.tile(door_position.0, door_position.1) {
+            Tile::Door(DoorState::Closed) => {
+                self.open_door(Actor::Player, door_position, false);
+                return true;
+            },
+            Tile::Door(DoorState::Locked) => {
+                if self.dynamic_map_manager.player.use_key() {
+                    self.open_door(Actor::Player, door_position, true);
+                    return true;
+                }
+                self.push_event(GameEvent::DoorLocked { position: door_position });
+                return stumbled;
+            },
+            _ => {},
+        }
+
+        // まず移動先がstatic_map上で歩いて入れる地形であることを確認
+        if tile_at(&self.static_map_manager.dungeon_map_2d, next_position.x, next_position.y).is_walkable() {
             // 次に移動先にmobがいないことを確認
             let mut mob_exist = false;
             for mob in &self.dynamic_map_manager.mob_list {
@@ -342,7 +1350,18 @@ impl GameMaster {
             }
             // 移動先にmobがいない場合のみ移動、移動できない場合は移動できなかったことを通知
             if !mob_exist {
+                let from = self.dynamic_map_manager.player.position;
                 self.dynamic_map_manager.player.position = (next_position.x, next_position.y);
+                self.push_event(GameEvent::Moved {
+                    actor: Actor::Player,
+                    from,
+                    to: (next_position.x, next_position.y),
+                });
+                self.update_field_of_view();
+                if self.apply_terrain_damage() {
+                    return true;
+                }
+                self.apply_terrain_status(Actor::Player, (next_position.x, next_position.y));
                 // TODO: プレイヤーが移動した先にアイテムがある場合、それを自動的に拾うかどうか
                 //  たとえば、特定のキーと同時に移動をした場合拾わないという選択もありうる。
                 //  また、アイテム所持上限に達している場合は拾えない。
@@ -358,43 +1377,45 @@ impl GameMaster {
                 }
                 // 移動先にアイテムがあった場合
                 if let Some(idx) = item_idx {
-                    // アイテム所持上限に達していないことを確認
-                    let ditem_rc = &self.dynamic_map_manager.item_list[idx];
-                    let got_item = self.dynamic_map_manager.player.add_item(&ditem_rc.borrow().item);
-                    if got_item {
-                        // 拾った場合、アイテムリストから削除して、削除したことを削除リストに追加
-                        self.dropped_item_removed_ids.push(ditem_rc.borrow().id);
-                        self.dynamic_map_manager.item_list.remove(idx);
-                        self.message.push("アイテムを拾った。".into());
-                    } else {
-                        // 拾えなかった場合、メッセージを表示
-                        self.message.push("持ち物がいっぱいです。".into());
-                    }
+                    self.pick_up_dropped_item(idx);
                 }
+                if self.is_player_on_goal() {
+                    let position = self.dynamic_map_manager.goal_position;
+                    self.push_event(GameEvent::GoalReached { position });
+                }
+                // 移動先に罠があれば作動させる
+                let position = self.dynamic_map_manager.player.position;
+                self.trigger_trap(Actor::Player, position);
                 result = true;
             }
         }
-        result
+        result || stumbled
     }
 
     /// playerに攻撃を指示、ターンを消費する
     #[func]
     pub fn player_attack(&mut self) {
+        if !self.player_can_act() {
+            return;
+        }
         // ターンの最初にアイテムの差分をクリア
         self.dropped_item_added_ids.clear();
         self.dropped_item_removed_ids.clear();
         self.player_attack_info.clear();
+        // プレイヤーから帰ってきた攻撃情報を保存、結果はprocessで反映する
+        // 攻撃範囲のうち壁の向こうのマスには届かない
         self.dynamic_map_manager.player.attack(&mut self.player_attack_info);
-        // プレイヤーから帰ってきた攻撃情報を保存
-        for (x, y, damage) in &self.player_attack_info {
-            godot_print!("Player Attack: x: {}, y: {}, damage: {}", x, y, damage);
-        }
+        let position = self.dynamic_map_manager.player.position;
+        retain_reachable(&self.static_map_manager.dungeon_map_2d, position, &mut self.player_attack_info);
     }
 
     /// playerにアイテムを拾うよう指示、ターンを消費する
     #[func]
     pub fn player_pickup_item(&mut self) {
-        // ターンの最初にアイテムの差分をクリア
+        if !self.player_can_act() {
+            return;
+        }
+        // ターンの最初にアイテムの差分をクリア
         self.dropped_item_added_ids.clear();
         self.dropped_item_removed_ids.clear();
         let (x, y) = self.dynamic_map_manager.player.position;
@@ -407,10 +1428,52 @@ impl GameMaster {
         }
         // アイテムを拾った場合の処理
         if let Some(idx) = item_idx {
-            let item = self.dynamic_map_manager.item_list.remove(idx);
-            self.dropped_item_removed_ids.push(item.borrow().id);
-            self.dynamic_map_manager.player.add_item(&item.borrow().item);
+            self.pick_up_dropped_item(idx);
+        }
+    }
+
+    /// 落ちているアイテムを拾う
+    ///
+    /// 持ち物がいっぱいで拾えない場合は、アイテムを床に残したままにする。
+    ///
+    /// # Returns
+    /// 拾えた場合はtrue
+    fn pick_up_dropped_item(&mut self, idx: usize) -> bool {
+        let (item_id, item, got_item) = {
+            let ditem = self.dynamic_map_manager.item_list[idx].borrow();
+            let item = *ditem.item.borrow();
+            (ditem.id, item, self.dynamic_map_manager.player.add_item(&ditem.item))
+        };
+        if !got_item {
+            self.push_event(GameEvent::InventoryFull { item_id });
+            return false;
+        }
+        // 拾った場合、アイテムリストから削除して、削除したことを削除リストに追加
+        self.dynamic_map_manager.item_list.remove(idx);
+        self.dropped_item_removed_ids.push(item_id);
+        self.push_event(GameEvent::ItemPickedUp { item_id, item });
+        true
+    }
+
+    /// playerに周りを調べるよう指示、ターンを消費する
+    ///
+    /// search_radius以内に隠れている罠を見つける。
+    #[func]
+    pub fn player_search(&mut self) {
+        if !self.player_can_act() {
+            return;
+        }
+        // ターンの最初にアイテムの差分をクリア
+        self.dropped_item_added_ids.clear();
+        self.dropped_item_removed_ids.clear();
+        self.player_attack_info.clear();
+        let position = self.dynamic_map_manager.player.position;
+        let found = self.dynamic_map_manager.traps.reveal_around(position, self.search_radius);
+        let count = found.len() as i32;
+        for trap in found {
+            self.push_event(GameEvent::TrapRevealed { kind: trap.kind, position: trap.position });
         }
+        self.push_event(GameEvent::Searched { found: count });
     }
 
     /// playerが現在所持しているアイテムが使えるかどうかを確認
@@ -421,7 +1484,9 @@ impl GameMaster {
         }
         let item = &self.dynamic_map_manager.player.items[item_idx as usize];
         match *item.borrow() {
-            GameItem::HealthPotion(_) => true,
+            GameItem::HealthPotion(_) | GameItem::Food(_) | GameItem::Elixir(_) => true,
+            // 装備品は使うと装備する
+            GameItem::Sword(_) | GameItem::Shield(_) => true,
             _ => false,
         }
     }
@@ -429,10 +1494,131 @@ impl GameMaster {
     /// playerにアイテムを使うよう指示、ターンを消費する
     #[func]
     pub fn player_use_item(&mut self, item_idx: i32) {
+        if !self.player_can_act() {
+            return;
+        }
         self.player_side_effect_info.clear();
         self.dynamic_map_manager.player.select_item(item_idx as usize);
-        self.player_side_effect_info.push(self.dynamic_map_manager.player.use_item());
-        self.message.push("HPが回復した。".into());
+        let player = &self.dynamic_map_manager.player;
+        let item = *player.items[player.active_item_index].borrow();
+        let side_effect = self.dynamic_map_manager.player.use_item();
+        if side_effect != SideEffect::Fault {
+            self.push_event(GameEvent::ItemUsed { item });
+        }
+        self.player_side_effect_info.push(side_effect);
+    }
+
+    /// playerに向いている方向へアイテムを投げるよう指示、ターンを消費する
+    ///
+    /// # Arguments
+    /// * `item_idx` - 投げるアイテムの番号
+    ///
+    /// # Returns
+    /// 投げられた場合はtrueを返す
+    #[func]
+    pub fn player_throw_item(&mut self, item_idx: i32) -> bool {
+        let player = &self.dynamic_map_manager.player;
+        let line = projectile::line_in_direction(player.position, player.direction, self.throw_range);
+        self.throw_item(item_idx, line)
+    }
+
+    /// playerに指定したマスへ向けてアイテムを投げるよう指示、ターンを消費する
+    ///
+    /// # Arguments
+    /// * `item_idx` - 投げるアイテムの番号
+    /// * `target` - 狙うマス、射程より遠い場合は射程の分だけ飛ぶ
+    ///
+    /// # Returns
+    /// 投げられた場合はtrueを返す
+    #[func]
+    pub fn player_throw_item_at(&mut self, item_idx: i32, target: Vector2i) -> bool {
+        let position = self.dynamic_map_manager.player.position;
+        let line = projectile::line(position, (target.x, target.y))
+            .into_iter()
+            .take(self.throw_range.max(0) as usize)
+            .collect();
+        self.throw_item(item_idx, line)
+    }
+
+    /// アイテムを直線に沿って投げる
+    ///
+    /// 最初に当たった敵には攻撃力にアイテムの補正を加えたダメージを与え、結果はprocessで反映する。
+    /// 投げたアイテムは止まったマスに落ちる。
+    fn throw_item(&mut self, item_idx: i32, line: Vec<(i32, i32)>) -> bool {
+        if !self.player_can_act() || line.is_empty() {
+            return false;
+        }
+        if item_idx < 0 || item_idx as usize >= self.dynamic_map_manager.player.items.len() {
+            return false;
+        }
+        let item = *self.dynamic_map_manager.player.items[item_idx as usize].borrow();
+        if item == GameItem::Null {
+            return false;
+        }
+        // ターンの最初にアイテムの差分をクリア
+        self.dropped_item_added_ids.clear();
+        self.dropped_item_removed_ids.clear();
+        self.player_attack_info.clear();
+        self.dynamic_map_manager.player.items[item_idx as usize] = RefCell::new(GameItem::Null);
+
+        let mob_list = &self.dynamic_map_manager.mob_list;
+        let trajectory = projectile::trace(&self.static_map_manager.dungeon_map_2d, &line, |position| {
+            mob_list.iter().any(|mob| mob.borrow().position == position)
+        });
+        if let Some((x, y)) = trajectory.hit {
+            let attack = self.dynamic_map_manager.player.total_attack() + item.throw_bonus();
+            self.player_attack_info.push((x, y, attack));
+        }
+        // 裂け目の上には落ちないので手前に戻し、目の前が壁の場合は足元に落ちる
+        let position = trajectory.landing(&self.static_map_manager.dungeon_map_2d)
+            .unwrap_or(self.dynamic_map_manager.player.position);
+        self.push_event(GameEvent::ProjectileFired {
+            actor: Actor::Player,
+            path: trajectory.path,
+            item: Some(item),
+        });
+        self.drop_item(item, position);
+        true
+    }
+
+    /// 指定したマスにアイテムを落とす
+    fn drop_item(&mut self, item: GameItem, position: (i32, i32)) {
+        let item_id = self.current_item_id_max;
+        let ditem = DroppedItem {
+            id: item_id,
+            position,
+            item: RefCell::new(item)
+        };
+        self.dynamic_map_manager.item_list.push(RefCell::new(ditem));
+        self.current_item_id_max += 1;
+        self.dropped_item_added_ids.push(item_id);
+        self.push_event(GameEvent::ItemDropped { item_id, position, item });
+    }
+
+    /// playerに装備を外すよう指示する
+    ///
+    /// # Arguments
+    /// * `slot` - 外す装備の箇所、0: 武器、1: 防具
+    ///
+    /// # Returns
+    /// 外せた場合はtrueを返す
+    #[func]
+    pub fn player_unequip(&mut self, slot: i32) -> bool {
+        if !self.player_can_act() {
+            return false;
+        }
+        let slot = match slot {
+            0 => EquipSlot::Weapon,
+            1 => EquipSlot::Armor,
+            _ => return false,
+        };
+        let result = self.dynamic_map_manager.player.unequip(slot);
+        if result {
+            self.push_event(GameEvent::Unequipped { slot });
+        } else {
+            self.push_event(GameEvent::UnequipFailed { slot });
+        }
+        result
     }
 
     /// playerのアイテム使用時のsideeffectの反映
@@ -449,11 +1635,26 @@ impl GameMaster {
         }
     }
 
+    /// exportされたパラメータからダメージ計算式を作る
+    pub fn damage_formula(&self) -> StandardDamageFormula {
+        StandardDamageFormula {
+            defense_factor: self.defense_factor,
+            min_damage: self.min_damage,
+            variance: self.damage_variance,
+            critical_rate: self.critical_rate,
+            critical_multiplier: self.critical_multiplier,
+            miss_rate: self.miss_rate,
+        }
+    }
+
     /// playerのattack_infoの反映
     pub fn applyPlayerAttackInfo(&mut self) {
         self.dynamic_map_manager.defeated_mob_id.clear();
+        let formula = self.damage_formula();
         let mut fumbled = true;
-        for (x, y, damage) in &self.player_attack_info {
+        // 反映中に出来事を記録するため、攻撃情報は取り出しておく
+        let attack_info = std::mem::take(&mut self.player_attack_info);
+        for (x, y, attack) in &attack_info {
             // モブの位置と一致するものがあればダメージを与える
             let mut mob_idx = None;
             for (idx, mob) in self.dynamic_map_manager.mob_list.iter().enumerate() {
@@ -463,150 +1664,173 @@ impl GameMaster {
                 }
             }
             if let Some(idx) = mob_idx {
+                fumbled = false;
                 let id = self.dynamic_map_manager.mob_list[idx].borrow().id;
-                godot_print!("Mob {} damaged: {}", id, damage);
-                self.message.push(format!("ID{}に{}ダメージを与えた。", id, damage).into());
+                // 攻撃力とモブの防御力からダメージを計算する
+                let defense = self.dynamic_map_manager.mob_list[idx].borrow().defense;
+                let result = formula.calculate(*attack, defense, &mut self.rng);
+                if result == AttackResult::Miss {
+                    self.push_event(GameEvent::Missed { attacker: Actor::Player, target: Some(Actor::Mob(id)) });
+                    continue;
+                }
+                let damage = result.damage();
+                self.push_event(GameEvent::Attacked {
+                    attacker: Actor::Player,
+                    target: Actor::Mob(id),
+                    damage,
+                    critical: matches!(result, AttackResult::Critical(_)),
+                });
                 self.dynamic_map_manager.mob_list[idx].borrow_mut().hp -= damage;
+                // 眠っていた敵はダメージを受けると目を覚ます
+                if self.dynamic_map_manager.mob_list[idx].borrow_mut().status_effects.remove(StatusKind::Sleep) {
+                    self.push_event(GameEvent::StatusExpired { actor: Actor::Mob(id), kind: StatusKind::Sleep });
+                }
                 // モブのHPが0以下になった場合、リストから削除
                 if self.dynamic_map_manager.mob_list[idx].borrow().hp <= 0 {
-                    // モブの最終位置を確認
-                    let (x, y) = self.dynamic_map_manager.mob_list[idx].borrow().position;
-
-                    // モブを倒したら一定確率でアイテムをドロップするようにする
-                    if (rand::random::<f32>() < self.mob_drop_item_probability) {
-                        // モブの最終位置にアイテムをドロップ
-                        let item = GameItem::HealthPotion(HealthPotion {heal_amount: 10});
-                        let item_id = self.current_item_id_max;
-                        let ditem = DroppedItem {
-                            id: item_id,
-                            position: (x, y),
-                            item: RefCell::new(item)
-                        };
-                        self.dynamic_map_manager.item_list.push(RefCell::new(ditem));
-                        self.current_item_id_max += 1;
-                        self.dropped_item_added_ids.push(item_id);
-                    }
-
-                    // モブの持っていたexp_pointをプレイヤーに加算
-                    self.dynamic_map_manager.player.exp_point +=
-                        self.dynamic_map_manager.mob_list[idx].borrow().exp_point;
-                    // モブをリストから削除
-                    self.dynamic_map_manager.mob_list.remove(idx);
-                    self.dynamic_map_manager.defeated_mob_id.push(id);
-                    godot_print!("Mob {} defeated.", id);
-                    self.message.push(format!("ID{}を倒した。", id).into());
+                    self.defeat_mob(idx);
                 }
-                fumbled = false;
             }
         }
         // 攻撃を外したらメッセージを表示
-        if fumbled && !self.player_attack_info.is_empty() {
-            godot_print!("Player Attack Fumbled");
-            self.message.push("攻撃が外れた。".into());
+        if fumbled && !attack_info.is_empty() {
+            self.push_event(GameEvent::Missed { attacker: Actor::Player, target: None });
         }
-        self.player_attack_info.clear();
     }
 
-    /// mobの行動を決定
-    // TODO: もっと複雑なAIを実装する
-    pub fn decideMobAction(&mut self) {
+    /// HPが0以下になった敵を倒したものとしてリストから削除する
+    ///
+    /// 一定確率でアイテムを落とし、持っていた経験値はプレイヤーのものになる。
+    fn defeat_mob(&mut self, idx: usize) {
+        // モブの最終位置を確認
+        let (x, y) = self.dynamic_map_manager.mob_list[idx].borrow().position;
+
+        // モブを倒したら一定確率でアイテムをドロップするようにする
+        if (self.rng.gen::<f32>() < self.mob_drop_item_probability) {
+            // 落とすアイテムは種類ごとのドロップテーブルから選ぶ
+            let species = self.dynamic_map_manager.mob_list[idx].borrow().species.clone();
+            let item = self.mob_catalog.get(&species)
+                .and_then(|species| species.choose_drop(&mut self.rng))
+                .and_then(|key| self.item_catalog.create_item(key, &mut self.rng))
+                .unwrap_or(GameItem::HealthPotion(HealthPotion {heal_amount: 10}));
+            // モブの最終位置にアイテムをドロップ
+            self.drop_item(item, (x, y));
+        }
+
+        // モブの持っていたexp_pointをプレイヤーに加算
+        self.dynamic_map_manager.player.exp_point +=
+            self.dynamic_map_manager.mob_list[idx].borrow().exp_point;
+        // 倒した敵を種類ごとに数える
+        let species = self.dynamic_map_manager.mob_list[idx].borrow().species.clone();
+        self.run_stats.record_kill(&species);
+        // モブをリストから削除
+        let id = self.dynamic_map_manager.mob_list[idx].borrow().id;
+        self.remove_mob(id, GameEvent::Died { actor: Actor::Mob(id), position: (x, y), defeated: true });
+    }
+
+    /// mobの行動を決定、行動の内容はモブごとの思考ルーチンが決める
+    ///
+    /// # Arguments
+    /// * `acting_mob_ids` - このまとまりで行動するモブのID、行動順に並んでいる
+    pub fn decideMobAction(&mut self, acting_mob_ids: &[i32]) {
         let mut mob_next_positions = vec![];
+        let mut moved = vec![];
+        let mut arrived = vec![];
+        let mut shots = vec![];
         self.mob_attack_info.clear();
 
-        // プレイヤーの位置はこの関数を呼び出している間は不変なので、ループの外で取得
-        let (px, py) = self.dynamic_map_manager.player.position;
-
-        for mob_rc in &mut self.dynamic_map_manager.mob_list {
+        // プレイヤーの位置はこの関数を呼び出している間は不変なので、ループの外で用意する
+        // プレイヤーまでの距離もここでマップ全体について求めておき、各モブはそれを参照する
+        let context = AiContext::new(
+            &self.static_map_manager.dungeon_map_2d,
+            self.dynamic_map_manager.player.position,
+            self.dynamic_map_manager.goal_position,
+            &self.field_of_view,
+            self.path_finder());
+        // 飛び道具を遮る生き物の位置、モブの移動はこの関数の最後に反映するので変わらない
+        let mut creature_positions: Vec<(i32, i32)> = self.dynamic_map_manager.mob_list.iter()
+            .map(|mob_rc| mob_rc.borrow().position)
+            .collect();
+        creature_positions.push(self.dynamic_map_manager.player.position);
+
+        for id in acting_mob_ids {
+            let Some(mob_rc) = self.dynamic_map_manager.mob_list.iter()
+                .find(|mob_rc| mob_rc.borrow().id == *id) else {
+                continue;
+            };
             let mut mob = mob_rc.borrow_mut();
-            // 同じ部屋に入ったモブだけがアクティブになるようにする
-            let (mx, my) = mob.position;
-            // 部屋にいるかどうかの判定は、BSPNodeParamsのx, y, width, heightから計算を行う。
-            // これを、px, pyとmx, myが同じ部屋にいるかどうかで判定する。
-            let mut in_same_room = false;
-            for param in &self.static_map_manager.room_params {
-                if px >= param.x && px < param.x + param.width &&
-                    py >= param.y && py < param.y + param.height &&
-                    mx >= param.x && mx < param.x + param.width &&
-                    my >= param.y && my < param.y + param.height {
-                    in_same_room = true;
-                    break;
-                }
+            // 眠っている敵は行動しない
+            if mob.status_effects.is_asleep() {
+                continue;
             }
-            // 同じ部屋にいる場合だけ行動
-            if !in_same_room {
+            // 混乱している敵はでたらめな方向へ動くことがある
+            if let Some(direction) = mob.status_effects.confused_direction(&mut self.rng) {
+                let (dx, dy) = direction.delta();
+                let next_position = (mob.position.0 + dx, mob.position.1 + dy);
+                mob.direction = direction;
+                if PathFinder::is_passable(context.map, next_position.0, next_position.1) {
+                    mob_next_positions.push((mob.id, next_position));
+                }
                 continue;
             }
-
-            // プレイヤーの位置との距離を計算
-            let dx = px - mx;
-            let dy = py - my;
-            let abs_dx = dx.abs();
-            let abs_dy = dy.abs();
-            // プレイヤーに隣接している場合は攻撃
-            if abs_dx <= 1 && abs_dy <= 1 {
-                let mut attack_info = vec![];
-                // mobのdirectionをプレイヤーに向ける
-                if dx > 0 {
-                    if dy > 0 {
-                        mob.direction = Direction::DownRight;
-                    } else if dy < 0 {
-                        mob.direction = Direction::UpRight;
-                    } else {
-                        mob.direction = Direction::Right;
-                    }
-                } else if dx < 0 {
-                    if dy > 0 {
-                        mob.direction = Direction::DownLeft;
-                    } else if dy < 0 {
-                        mob.direction = Direction::UpLeft;
-                    } else {
-                        mob.direction = Direction::Left;
+            // 行動は種類ごとの思考ルーチンに決めてもらう
+            let ai = mob.behavior.ai();
+            match ai.decide(&mut mob, &context, &mut self.rng) {
+                MobAction::Attack(direction) => {
+                    let mut attack_info = vec![];
+                    mob.direction = direction;
+                    mob.attack(&mut attack_info);
+                    retain_reachable(&self.static_map_manager.dungeon_map_2d, mob.position, &mut attack_info);
+                    for (x, y, damage) in &attack_info {
+                        self.mob_attack_info.push((*x, *y, *damage, mob.id));
                     }
-                } else {
-                    if dy > 0 {
-                        mob.direction = Direction::Down;
-                    } else if dy < 0 {
-                        mob.direction = Direction::Up;
+                },
+                MobAction::Move(next_position) => {
+                    // 移動したい位置を決めておいて、そのあとで他のモブと重ならないかを確認
+                    let (mx, my) = mob.position;
+                    if let Some(direction) = Direction::from_delta(next_position.0 - mx, next_position.1 - my) {
+                        mob.direction = direction;
                     }
-                }
-                mob.attack(&mut attack_info);
-                for (x, y, damage) in &attack_info {
-                    self.mob_attack_info.push((*x, *y, *damage, mob.id));
-                }
-            } else {
-                // そうでなければプレイヤーの方向に移動
-                // 移動したい位置を決めておいて、そのあとで実際移動できるかどうかを確認
-                let mut next_position = (mx, my);
-                if abs_dx > abs_dy {
-                    if dx > 0 {
-                        next_position = (mx + 1, my);
-                        mob.direction = Direction::Right;
-                    } else {
-                        next_position = (mx - 1, my);
-                        mob.direction = Direction::Left;
+                    mob_next_positions.push((mob.id, next_position));
+                },
+                MobAction::Shoot(target) => {
+                    let (mx, my) = mob.position;
+                    if let Some(direction) = Direction::from_delta(target.0 - mx, target.1 - my) {
+                        mob.direction = direction;
                     }
-                } else {
-                    if dy > 0 {
-                        next_position = (mx, my + 1);
-                        mob.direction = Direction::Down;
-                    } else {
-                        next_position = (mx, my - 1);
-                        mob.direction = Direction::Up;
+                    // 射程の分だけ飛ばし、最初に当たった生き物に攻撃する
+                    let line: Vec<_> = projectile::line(mob.position, target).into_iter()
+                        .take(mob.range as usize)
+                        .collect();
+                    let trajectory = projectile::trace(
+                        &self.static_map_manager.dungeon_map_2d, &line,
+                        |position| creature_positions.contains(&position));
+                    if let Some((x, y)) = trajectory.hit {
+                        self.mob_attack_info.push((x, y, mob.attack, mob.id));
                     }
-                }
-                // static_map上で空きがあれば移動候補に入れる
-                if self.static_map_manager.dungeon_map_2d[next_position.0 as usize][next_position.1 as usize] == 0 {
-                    mob_next_positions.push((mob.id, next_position));
-                }
+                    shots.push(GameEvent::ProjectileFired {
+                        actor: Actor::Mob(mob.id),
+                        path: trajectory.path,
+                        item: None,
+                    });
+                },
+                MobAction::Wait => {},
             }
         }
+        for event in shots {
+            self.push_event(event);
+        }
 
         for (id, next_position) in &mob_next_positions {
+            // 閉じた扉に向かって進む場合は移動せずに扉を開ける
+            if self.static_map_manager.tile(next_position.0, next_position.1) == Tile::Door(DoorState::Closed) {
+                self.open_door(Actor::Mob(*id), *next_position, false);
+                continue;
+            }
             // mob_listの中のmobを全部読みだして
             // mob.idが一致するものは自分なので一度無視
             // それ以外のmobは、next_positionと一致しないかどうかを確認
-            // 一致するものがあれば移動しない
-            let mut can_move = true;
+            // 一致するものがあれば移動しない、プレイヤーのいる位置にも移動しない
+            let mut can_move = *next_position != self.dynamic_map_manager.player.position;
             for mob_rc in &self.dynamic_map_manager.mob_list {
                 let mob = mob_rc.borrow_mut();
                 if mob.id == *id {
@@ -621,12 +1845,71 @@ impl GameMaster {
                 for mob_rc in &mut self.dynamic_map_manager.mob_list {
                     let mut mob = mob_rc.borrow_mut();
                     if mob.id == *id {
+                        moved.push(GameEvent::Moved {
+                            actor: Actor::Mob(*id),
+                            from: mob.position,
+                            to: *next_position,
+                        });
                         mob.position = *next_position;
+                        arrived.push((*id, *next_position));
                         break;
                     }
                 }
             }
         }
+        for event in moved {
+            self.push_event(event);
+        }
+        // 移動先の地形の効果を受け、罠があれば作動させる
+        for (id, position) in arrived {
+            self.apply_terrain_status(Actor::Mob(id), position);
+            self.trigger_trap(Actor::Mob(id), position);
+        }
+    }
+
+    /// プレイヤーが行動した後、次にプレイヤーが行動できるようになるまでのモブの行動順を決める
+    ///
+    /// # Returns
+    /// 同時に行動するモブのIDのまとまりを行動順に並べたもの
+    pub fn schedule_mob_actions(&mut self) -> Vec<Vec<i32>> {
+        // 加速と鈍足の状態異常を素早さに反映する
+        let player = &mut self.dynamic_map_manager.player;
+        player.speed.effect = player.status_effects.speed_effect();
+        let mut mobs: Vec<_> = self.dynamic_map_manager.mob_list.iter()
+            .map(|mob_rc| mob_rc.borrow_mut())
+            .collect();
+        let mut entries: Vec<(i32, &mut Speed)> = mobs.iter_mut()
+            .map(|mob| {
+                let mob = &mut **mob;
+                mob.speed.effect = mob.status_effects.speed_effect();
+                (mob.id, &mut mob.speed)
+            })
+            .collect();
+        schedule_turn(&mut self.dynamic_map_manager.player.speed, &mut entries)
+    }
+
+    /// 現在の設定から経路探索を行うオブジェクトを作る
+    pub fn path_finder(&self) -> PathFinder {
+        PathFinder {
+            diagonal_rule: DiagonalRule::from_i32(self.diagonal_rule),
+            ..Default::default()
+        }
+    }
+
+    /// 2点間の経路を求める、クリックした位置への移動などに使う
+    ///
+    /// # Returns
+    /// 始点を含まず終点を含む経路、到達できない場合は空の配列を返す
+    #[func]
+    pub fn find_path(&self, start: Vector2i, goal: Vector2i) -> Array<Vector2i> {
+        let mut path = array![];
+        if let Some(positions) = self.path_finder().find_path(
+            &self.static_map_manager.dungeon_map_2d, (start.x, start.y), (goal.x, goal.y)) {
+            for (x, y) in positions {
+                path.push(Vector2i::new(x, y));
+            }
+        }
+        path
     }
 
     /// mobのアイテム使用時のsideeffectの反映
@@ -645,24 +1928,309 @@ impl GameMaster {
 
     /// mobのattack_infoの反映
     pub fn applyMobAttackInfo(&mut self) {
-        for (x, y, damage, mob_id) in &self.mob_attack_info {
+        let formula = self.damage_formula();
+        // 反映中に出来事を記録するため、攻撃情報は取り出しておく
+        let attack_info = std::mem::take(&mut self.mob_attack_info);
+        for (x, y, attack, mob_id) in &attack_info {
+            // 力尽きた後の攻撃は反映しない
+            if !self.game_state.can_act() {
+                break;
+            }
             // プレイヤーの位置と一致するものがあればダメージを与える
             if self.dynamic_map_manager.player.position == (*x, *y) {
-                self.message.push(format!("プレイヤーはID{}から{}ダメージを受けた。", mob_id, damage).into());
-                self.dynamic_map_manager.player.hp -= damage;
+                // モブの攻撃力とプレイヤーの装備を含めた防御力からダメージを計算する
+                let defense = self.dynamic_map_manager.player.total_defense();
+                let result = formula.calculate(*attack, defense, &mut self.rng);
+                if result == AttackResult::Miss {
+                    self.push_event(GameEvent::Missed { attacker: Actor::Mob(*mob_id), target: Some(Actor::Player) });
+                    continue;
+                }
+                let damage = self.dynamic_map_manager.player.take_damage(result.damage());
+                self.push_event(GameEvent::Attacked {
+                    attacker: Actor::Mob(*mob_id),
+                    target: Actor::Player,
+                    damage,
+                    critical: matches!(result, AttackResult::Critical(_)),
+                });
+                // 眠っていたら目を覚まし、攻撃に状態異常の効果があればかかる
+                if self.dynamic_map_manager.player.status_effects.remove(StatusKind::Sleep) {
+                    self.push_event(GameEvent::StatusExpired { actor: Actor::Player, kind: StatusKind::Sleep });
+                }
+                let on_hit = self.dynamic_map_manager.mob_list.iter()
+                    .find(|mob_rc| mob_rc.borrow().id == *mob_id)
+                    .and_then(|mob_rc| mob_rc.borrow().on_hit);
+                if let Some(effect) = on_hit {
+                    if self.dynamic_map_manager.player.hp > 0 {
+                        self.add_status_effect(Actor::Player, effect);
+                    }
+                }
                 if self.dynamic_map_manager.player.hp <= 0 {
-                    // ゲームオーバー
-                    godot_print!("Game Over");
-                    self.message.push("力尽きた。".into());
+                    let mob_name = self.dynamic_map_manager.mob_list.iter()
+                        .find(|mob_rc| mob_rc.borrow().id == *mob_id)
+                        .and_then(|mob_rc| self.mob_catalog.get(&mob_rc.borrow().species).map(|species| species.name.clone()))
+                        .unwrap_or_default();
+                    self.kill_player(CauseOfDeath::new(DeathCause::Mob { id: *mob_id, name: mob_name }, self.current_level));
                 }
             }
         }
-        self.mob_attack_info.clear();
+    }
+
+    /// プレイヤーが力尽きたときの処理、以降の行動は受け付けない
+    fn kill_player(&mut self, cause: CauseOfDeath) {
+        self.game_state = GameState::Dead;
+        self.cause_of_death = Some(cause);
+        let position = self.dynamic_map_manager.player.position;
+        self.push_event(GameEvent::Died { actor: Actor::Player, position, defeated: false });
+        self.push_event(GameEvent::GameOver);
+        self.write_run_summary();
+    }
+
+    /// プレイヤーと敵の状態異常を1ターン進める
+    ///
+    /// 毒で力尽きた敵は倒したものとして扱う。
+    pub fn tick_status_effects(&mut self) {
+        let ticks = self.dynamic_map_manager.player.status_effects.tick();
+        for tick in ticks {
+            if tick.hp_delta != 0 {
+                let player = &mut self.dynamic_map_manager.player;
+                player.hp = (player.hp + tick.hp_delta).min(player.max_hp);
+                self.push_event(GameEvent::StatusTicked { actor: Actor::Player, kind: tick.kind, amount: tick.hp_delta });
+            }
+            if tick.expired {
+                self.push_event(GameEvent::StatusExpired { actor: Actor::Player, kind: tick.kind });
+            }
+            if self.dynamic_map_manager.player.hp <= 0 && self.game_state.can_act() {
+                self.kill_player(CauseOfDeath::new(DeathCause::Status(tick.kind), self.current_level));
+            }
+        }
+        if !self.game_state.can_act() {
+            return;
+        }
+
+        let mut idx = 0;
+        while idx < self.dynamic_map_manager.mob_list.len() {
+            let (id, ticks) = {
+                let mut mob = self.dynamic_map_manager.mob_list[idx].borrow_mut();
+                (mob.id, mob.status_effects.tick())
+            };
+            for tick in ticks {
+                if tick.hp_delta != 0 {
+                    let mut mob = self.dynamic_map_manager.mob_list[idx].borrow_mut();
+                    // 古いセーブデータでは最大HPが0なので、今のHPを上限にする
+                    mob.hp = (mob.hp + tick.hp_delta).min(mob.max_hp.max(mob.hp));
+                    drop(mob);
+                    self.push_event(GameEvent::StatusTicked { actor: Actor::Mob(id), kind: tick.kind, amount: tick.hp_delta });
+                }
+                if tick.expired {
+                    self.push_event(GameEvent::StatusExpired { actor: Actor::Mob(id), kind: tick.kind });
+                }
+            }
+            if self.dynamic_map_manager.mob_list[idx].borrow().hp <= 0 {
+                self.defeat_mob(idx);
+            } else {
+                idx += 1;
+            }
+        }
+    }
+
+    /// 指定したマスに罠があれば作動させる
+    ///
+    /// 敵が踏んだ罠は、プレイヤーから見えている場合だけ見つかったことにして出来事に記録する。
+    /// 警報だけはどこで鳴っても記録する。
+    fn trigger_trap(&mut self, actor: Actor, position: (i32, i32)) {
+        let seen = actor == Actor::Player || self.is_visible_to_player(position);
+        let Some(kind) = self.dynamic_map_manager.traps.trigger(position, seen) else {
+            return;
+        };
+        if seen || kind == TrapKind::Alarm {
+            self.push_event(GameEvent::TrapTriggered { actor, kind, position });
+        }
+        match kind {
+            TrapKind::Damage => self.apply_trap_damage(actor, seen),
+            TrapKind::Teleport => self.teleport(actor),
+            TrapKind::Alarm => self.wake_all_mobs(position),
+            TrapKind::Pit => match actor {
+                // 次の階層への移動はGodot側からfall_into_pitで行う
+                Actor::Player => self.falling = true,
+                // 落とし穴に落ちた敵はこの階層からいなくなる
+                Actor::Mob(id) => self.remove_mob(id, GameEvent::Fell { actor, position }),
+            },
+        }
+    }
+
+    /// 罠でダメージを与える
+    ///
+    /// 力尽きた敵はプレイヤーが倒したわけではないので、経験値やアイテムは得られず倒した数にも数えない。
+    /// プレイヤーから見えているところで力尽きた場合だけ、罠で力尽きたことを記録する。
+    fn apply_trap_damage(&mut self, actor: Actor, seen: bool) {
+        let Actor::Mob(id) = actor else {
+            let damage = self.dynamic_map_manager.player.take_damage(self.trap_damage);
+            self.push_event(GameEvent::TrapDamaged { actor, kind: TrapKind::Damage, damage });
+            if self.dynamic_map_manager.player.hp <= 0 {
+                self.kill_player(CauseOfDeath::new(DeathCause::Trap(TrapKind::Damage), self.current_level));
+            }
+            return;
+        };
+        let Some(idx) = self.mob_index(id) else {
+            return;
+        };
+        let damage = self.trap_damage.max(0);
+        let hp = {
+            let mut mob = self.dynamic_map_manager.mob_list[idx].borrow_mut();
+            mob.hp -= damage;
+            mob.hp
+        };
+        if seen {
+            self.push_event(GameEvent::TrapDamaged { actor, kind: TrapKind::Damage, damage });
+        }
+        if hp <= 0 {
+            if seen {
+                self.push_event(GameEvent::TrapKilled { actor, kind: TrapKind::Damage });
+            }
+            let position = self.dynamic_map_manager.mob_list[idx].borrow().position;
+            self.remove_mob(id, GameEvent::Died { actor, position, defeated: false });
+        }
+    }
+
+    /// 罠で階層のどこかの空いている床へ飛ばす
+    fn teleport(&mut self, actor: Actor) {
+        let mut occupied: Vec<(i32, i32)> = self.dynamic_map_manager.mob_list.iter()
+            .map(|mob_rc| mob_rc.borrow().position)
+            .collect();
+        occupied.push(self.dynamic_map_manager.player.position);
+        let mut candidates = vec![];
+        for x in 0..self.dungeon_width {
+            for y in 0..self.dungeon_height {
+                if self.static_map_manager.tile(x, y) == Tile::Floor
+                    && !occupied.contains(&(x, y))
+                    && self.dynamic_map_manager.traps.get((x, y)).is_none() {
+                    candidates.push((x, y));
+                }
+            }
+        }
+        let Some(&to) = candidates.choose(&mut self.rng) else {
+            return;
+        };
+        let from = match actor {
+            Actor::Player => std::mem::replace(&mut self.dynamic_map_manager.player.position, to),
+            Actor::Mob(id) => {
+                let Some(idx) = self.mob_index(id) else {
+                    return;
+                };
+                std::mem::replace(&mut self.dynamic_map_manager.mob_list[idx].borrow_mut().position, to)
+            },
+        };
+        self.push_event(GameEvent::Moved { actor, from, to });
+        if actor == Actor::Player {
+            self.update_field_of_view();
+            if self.is_player_on_goal() {
+                self.push_event(GameEvent::GoalReached { position: to });
+            }
+        }
+    }
+
+    /// 警報で階層の敵をすべて目覚めさせ、鳴った場所へ向かわせる
+    fn wake_all_mobs(&mut self, position: (i32, i32)) {
+        let mut woken = vec![];
+        for mob_rc in &self.dynamic_map_manager.mob_list {
+            let mut mob = mob_rc.borrow_mut();
+            if mob.status_effects.remove(StatusKind::Sleep) {
+                woken.push(mob.id);
+            }
+            if matches!(mob.ai_state, MobState::Idle | MobState::Sleeping | MobState::Wandering) {
+                mob.ai_state = MobState::Chasing { target: position };
+            }
+        }
+        for id in woken {
+            self.push_event(GameEvent::StatusExpired { actor: Actor::Mob(id), kind: StatusKind::Sleep });
+        }
+    }
+
+    /// 敵をこの階層から取り除き、いなくなった理由を出来事として記録する
+    ///
+    /// 経験値やアイテムのドロップは扱わないので、プレイヤーが倒した場合はdefeat_mobを使う。
+    fn remove_mob(&mut self, id: i32, event: GameEvent) {
+        let Some(idx) = self.mob_index(id) else {
+            return;
+        };
+        self.dynamic_map_manager.mob_list.remove(idx);
+        self.dynamic_map_manager.defeated_mob_id.push(id);
+        self.push_event(event);
+    }
+
+    /// IDから敵のリスト上の位置を求める
+    fn mob_index(&self, id: i32) -> Option<usize> {
+        self.dynamic_map_manager.mob_list.iter().position(|mob_rc| mob_rc.borrow().id == id)
+    }
+
+    /// 扉を開けてGodot側のマップにも反映する
+    fn open_door(&mut self, actor: Actor, position: (i32, i32), unlocked: bool) {
+        if !self.static_map_manager.open_door(position.0, position.1) {
+            return;
+        }
+        let tile = self.static_map_manager.tile(position.0, position.1);
+        self.set_tile(position.0, position.1, tile);
+        self.push_event(GameEvent::DoorOpened { actor, position, unlocked });
+        // 扉が開くと見通せる範囲が変わる
+        self.update_field_of_view();
+    }
+
+    /// 踏み込んだ地形に状態異常の効果があればかける
+    fn apply_terrain_status(&mut self, actor: Actor, position: (i32, i32)) {
+        if let Some(effect) = self.static_map_manager.tile(position.0, position.1).entry_status() {
+            self.add_status_effect(actor, effect);
+        }
+    }
+
+    /// プレイヤーのいるマスの地形からダメージを受ける
+    ///
+    /// # Returns
+    /// ダメージで力尽きた場合はtrue
+    fn apply_terrain_damage(&mut self) -> bool {
+        let (x, y) = self.dynamic_map_manager.player.position;
+        let tile = tile_at(&self.static_map_manager.dungeon_map_2d, x, y);
+        if tile.entry_damage() <= 0 {
+            return false;
+        }
+        let damage = self.dynamic_map_manager.player.take_damage(tile.entry_damage());
+        self.push_event(GameEvent::TerrainDamaged { actor: Actor::Player, tile, damage });
+        if self.dynamic_map_manager.player.hp > 0 {
+            return false;
+        }
+        self.kill_player(CauseOfDeath::new(DeathCause::Terrain(tile), self.current_level));
+        true
+    }
+
+    /// 1ターン分だけプレイヤーの満腹度を減らす、満腹度が0の場合はダメージを受ける
+    pub fn drain_satiety(&mut self) {
+        let player = &mut self.dynamic_map_manager.player;
+        let before = player.satiety;
+        let starving = player.digest(self.satiety_drain_rate);
+        let after = player.satiety;
+        if before > HUNGRY_SATIETY && after <= HUNGRY_SATIETY && !starving {
+            self.push_event(GameEvent::Hungry);
+        }
+        if !starving {
+            return;
+        }
+        if before > 0.0 {
+            self.push_event(GameEvent::Starving);
+        }
+        let damage = self.dynamic_map_manager.player.take_damage(self.starvation_damage);
+        self.push_event(GameEvent::StarvationDamaged { damage });
+        if self.dynamic_map_manager.player.hp <= 0 {
+            self.kill_player(CauseOfDeath::new(DeathCause::Starvation, self.current_level));
+        }
     }
 
     /// 1ターンを定義、godot側から進めるかどうかを決めて呼び出す。
     #[func]
     pub fn process(&mut self) {
+        // 落とし穴に落ちたプレイヤーはもうこの階層にいないので、ターンを進めない
+        if !self.game_state.can_act() || self.falling {
+            return;
+        }
+        self.run_stats.turns += 1;
         // プレイヤーの行動はすでに反映された状態を起点とする。
         // プレイヤーのアイテム使用時のsideeffectの反映
         self.applyPlayerSideEffect();
@@ -671,22 +2239,34 @@ impl GameMaster {
 
         // TODO: プレイヤーのレベルアップ判定
         if self.dynamic_map_manager.player.check_level_up() {
-            self.message.push("レベルアップした。".into());
+            let level = self.dynamic_map_manager.player.level;
+            self.push_event(GameEvent::LevelUp { level });
         }
 
-        // TODO: モブの行動を決定
-        self.decideMobAction();
+        // 次にプレイヤーが行動できるようになるまでのモブの行動順を決める
+        // 素早いモブは複数回、遅いモブは数ターンに1回行動する
+        for acting_mob_ids in self.schedule_mob_actions() {
+            // モブの行動を決定
+            self.decideMobAction(&acting_mob_ids);
 
-        // モブのアイテム使用時のsideeffectの反映
-        self.applyMobSideEffect();
-        // モブのattack_infoの反映
-        self.applyMobAttackInfo();
+            // モブのアイテム使用時のsideeffectの反映
+            self.applyMobSideEffect();
+            // モブのattack_infoの反映
+            self.applyMobAttackInfo();
+
+            if !self.game_state.can_act() {
+                break;
+            }
+        }
+
+        // ターンの終わりに状態異常を進め、お腹を空かせる
+        if self.game_state.can_act() {
+            self.tick_status_effects();
+        }
+        if self.game_state.can_act() {
+            self.drain_satiety();
+        }
 
-        // TODO: ターンの処理の結果、起きた結果をgodotに通知
-        // プレイヤーのHP<=0でゲーム終了
-        // モブのHP<=0でそのモブは削除
-        // プレイヤーがアイテムを拾った場合、そのアイテムはマップ上からは削除
-        // etc...
         match self.dynamic_map_manager.player.direction {
             Direction::Up => godot_print!("Player Direcction: up"),
             Direction::UpRight => godot_print!("Player Direcction: up right"),
@@ -743,13 +2323,33 @@ impl GameMaster {
         self.print_player_status();
     }
 
+    /// 見つかっている罠をgodotに渡す
+    ///
+    /// 罠ごとのDictionary、"position"に位置、"trap"に種類、"name"に表示名が入る
+    #[func]
+    pub fn get_known_traps(&self) -> Array<Dictionary> {
+        let mut result = Array::new();
+        for trap in self.dynamic_map_manager.traps.known() {
+            let mut dict = Dictionary::new();
+            dict.set("position", Vector2i::new(trap.position.0, trap.position.1));
+            dict.set("trap", trap.kind.key());
+            dict.set("name", self.messages().trap_text(trap.kind));
+            result.push(dict);
+        }
+        result
+    }
+
     // 落ちているアイテムの情報を取得
+    // 位置とIDはどちらもプレイヤーから見えているものだけを同じ順番で返す
     /// 落ちているアイテムの位置を取得
     #[func]
     pub fn get_dropped_item_positions(&self) -> Array<Vector2i> {
         let mut positions = array![];
         for item_rc in &self.dynamic_map_manager.item_list {
             let item = item_rc.borrow();
+            if !self.is_visible_to_player(item.position) {
+                continue;
+            }
             positions.push(Vector2i::new(item.position.0, item.position.1));
         }
         positions
@@ -761,6 +2361,9 @@ impl GameMaster {
         let mut ids = array![];
         for item_rc in &self.dynamic_map_manager.item_list {
             let item = item_rc.borrow();
+            if !self.is_visible_to_player(item.position) {
+                continue;
+            }
             ids.push(item.id);
         }
         ids
@@ -787,12 +2390,16 @@ impl GameMaster {
     }
 
     // 敵の情報を取得する関数群
+    // 倒された敵のID以外は、プレイヤーから見えている敵だけを同じ順番で返す
     /// 敵の位置を取得
     #[func]
     pub fn get_mob_positions(&self) -> Array<Vector2i> {
         let mut positions = array![];
         for mob_rc in &self.dynamic_map_manager.mob_list {
             let mob = mob_rc.borrow();
+            if !self.is_visible_to_player(mob.position) {
+                continue;
+            }
             positions.push(Vector2i::new(mob.position.0, mob.position.1));
         }
         positions
@@ -804,6 +2411,9 @@ impl GameMaster {
         let mut directions = array![];
         for mob_rc in &self.dynamic_map_manager.mob_list {
             let mob = mob_rc.borrow();
+            if !self.is_visible_to_player(mob.position) {
+                continue;
+            }
             let dir = match mob.direction {
                 Direction::Up => 0,
                 Direction::UpRight => 1,
@@ -826,11 +2436,46 @@ impl GameMaster {
         let mut ids = array![];
         for mob_rc in &self.dynamic_map_manager.mob_list {
             let mob = mob_rc.borrow();
+            if !self.is_visible_to_player(mob.position) {
+                continue;
+            }
             ids.push(mob.id);
         }
         ids
     }
 
+    /// 敵の種類のキーを取得、get_mob_idsと同じ順番で返す
+    #[func]
+    pub fn get_mob_species(&self) -> Array<GString> {
+        let mut species = array![];
+        for mob_rc in &self.dynamic_map_manager.mob_list {
+            let mob = mob_rc.borrow();
+            if !self.is_visible_to_player(mob.position) {
+                continue;
+            }
+            species.push(mob.species.clone().into());
+        }
+        species
+    }
+
+    /// 敵の見た目のキーを取得、get_mob_idsと同じ順番で返す
+    ///
+    /// カタログに見つからない種類の場合は空文字列を返す。
+    #[func]
+    pub fn get_mob_models(&self) -> Array<GString> {
+        let mut models = array![];
+        for mob_rc in &self.dynamic_map_manager.mob_list {
+            let mob = mob_rc.borrow();
+            if !self.is_visible_to_player(mob.position) {
+                continue;
+            }
+            let model = self.mob_catalog.get(&mob.species)
+                .map_or(String::new(), |species| species.model.clone());
+            models.push(model.into());
+        }
+        models
+    }
+
     /// このターンに倒された敵のIDを取得
     #[func]
     pub fn get_defeated_mob_ids(&self) -> Array<i32> {
@@ -841,12 +2486,54 @@ impl GameMaster {
         ids
     }
 
+    /// プレイヤーから現在見えているマスを取得
+    #[func]
+    pub fn get_visible_tiles(&self) -> Array<Vector2i> {
+        let mut tiles = array![];
+        for (x, y) in self.field_of_view.visible_tiles() {
+            tiles.push(Vector2i::new(x, y));
+        }
+        tiles
+    }
+
+    /// 探索済みのマスをdungeon_map_1dと同じ並びで取得、探索済みなら1、未探索なら0
+    #[func]
+    pub fn get_explored_map_1d(&self) -> Array<i32> {
+        let mut explored = array![];
+        for y in 0..self.dungeon_height {
+            for x in 0..self.dungeon_width {
+                explored.push(if self.field_of_view.is_explored(x, y) { 1 } else { 0 });
+            }
+        }
+        explored
+    }
+
+    /// プレイヤーの位置から視界を計算し直す
+    fn update_field_of_view(&mut self) {
+        self.field_of_view.compute(
+            &self.static_map_manager.dungeon_map_2d,
+            self.dynamic_map_manager.player.position,
+            self.view_radius);
+    }
+
+    /// 指定した位置がプレイヤーから見えているかどうか
+    fn is_visible_to_player(&self, position: (i32, i32)) -> bool {
+        self.field_of_view.is_visible(position.0, position.1)
+    }
+
+    /// ゴールのマスに階段を置く
+    fn place_stairs(&mut self) {
+        let (x, y) = self.dynamic_map_manager.goal_position;
+        self.static_map_manager.dungeon_map_2d[x as usize][y as usize] = Tile::Stairs;
+        self.set_tile(x, y, Tile::Stairs);
+    }
+
     // StaticMapManagerのdungeon_map_2dをコピーしてGodotからアクセスできるdungeon_map_1dにセットする
     // これは一度作成したら変わらないので、exportした変数にアクセスしてもらう
-    fn set_tile(&mut self, x: i32, y: i32, tile: i32) {
+    fn set_tile(&mut self, x: i32, y: i32, tile: Tile) {
         self.dungeon_map_1d.set(
             (y * self.dungeon_width + x).try_into().unwrap(),
-            tile);
+            tile.to_i32());
     }
 
     fn copy_from_static_map_manager(&mut self) {
@@ -0,0 +1,214 @@
+//! ゲームの進行状態を管理するモジュール
+
+use crate::locale::MessageCatalog;
+use crate::status_effect::StatusKind;
+use crate::tile::Tile;
+use crate::trap::TrapKind;
+
+use serde::{Deserialize, Serialize};
+
+/// ゲームの進行状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GameState {
+    /// プレイ中、プレイヤーが行動できる
+    #[default]
+    Playing,
+    /// プレイヤーが力尽きた
+    Dead,
+    /// 次の階層へ移動している
+    Descending,
+    /// 最後の階層を踏破した
+    Won,
+}
+
+impl GameState {
+    /// Godot側に渡すための値
+    /// 0: プレイ中、1: 力尽きた、2: 次の階層へ移動中、3: 踏破した
+    pub fn to_i32(&self) -> i32 {
+        match self {
+            GameState::Playing => 0,
+            GameState::Dead => 1,
+            GameState::Descending => 2,
+            GameState::Won => 3,
+        }
+    }
+
+    /// プレイヤーが行動できるかどうか
+    pub fn can_act(&self) -> bool {
+        *self == GameState::Playing
+    }
+
+    /// ゲームの状態を保存できるかどうか
+    ///
+    /// 力尽きた後や踏破した後、階層の移動中に保存すると再開できなくなるので、プレイ中だけ保存できる。
+    pub fn can_save(&self) -> bool {
+        self.can_act()
+    }
+
+    /// 階層を踏破したときの次の状態
+    ///
+    /// # Arguments
+    /// * `cleared_level` - 踏破した階層
+    /// * `final_floor` - 最後の階層、0以下の場合は終わりがない
+    pub fn after_floor_cleared(cleared_level: i32, final_floor: i32) -> Self {
+        if final_floor > 0 && cleared_level >= final_floor {
+            GameState::Won
+        } else {
+            GameState::Descending
+        }
+    }
+}
+
+/// プレイヤーが力尽きた原因の種類
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DeathCause {
+    /// 敵に倒された、名前はカタログにない種類の場合は空
+    Mob { id: i32, name: String },
+    /// 状態異常で力尽きた
+    Status(StatusKind),
+    /// 飢えて力尽きた
+    Starvation,
+    /// 地形のダメージで力尽きた
+    Terrain(Tile),
+    /// 罠にかかって力尽きた
+    Trap(TrapKind),
+}
+
+/// プレイヤーが力尽きた原因
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "CauseOfDeathRecord")]
+pub struct CauseOfDeath {
+    /// 力尽きた原因の種類
+    pub cause: DeathCause,
+    /// 力尽きた階層
+    pub level: i32,
+}
+
+/// 読み込み用の力尽きた原因、原因の種類がない以前の形式も読めるようにする
+#[derive(Deserialize)]
+struct CauseOfDeathRecord {
+    #[serde(default)]
+    cause: Option<DeathCause>,
+    level: i32,
+    #[serde(default)]
+    mob_id: i32,
+    #[serde(default)]
+    mob_name: String,
+    #[serde(default)]
+    status: Option<StatusKind>,
+    #[serde(default)]
+    starved: bool,
+    #[serde(default)]
+    terrain: Option<Tile>,
+}
+
+impl From<CauseOfDeathRecord> for CauseOfDeath {
+    fn from(record: CauseOfDeathRecord) -> Self {
+        // 以前の形式は飢え、地形、状態異常、敵の順に原因を決めていた
+        let cause = if let Some(cause) = record.cause {
+            cause
+        } else if record.starved {
+            DeathCause::Starvation
+        } else if let Some(tile) = record.terrain {
+            DeathCause::Terrain(tile)
+        } else if let Some(kind) = record.status {
+            DeathCause::Status(kind)
+        } else {
+            DeathCause::Mob { id: record.mob_id, name: record.mob_name }
+        };
+        Self { cause, level: record.level }
+    }
+}
+
+impl CauseOfDeath {
+    pub fn new(cause: DeathCause, level: i32) -> Self {
+        Self { cause, level }
+    }
+
+    /// 表示用の文字列に変換する
+    pub fn text(&self, messages: &MessageCatalog) -> String {
+        let level = ("level", self.level.to_string());
+        match &self.cause {
+            DeathCause::Mob { id, name } => messages.text("cause_of_death", &[
+                level,
+                ("id", id.to_string()),
+                ("name", name.clone()),
+            ]),
+            DeathCause::Status(kind) => messages.text("cause_of_death_status", &[
+                level,
+                ("status", messages.status_text(*kind)),
+            ]),
+            DeathCause::Starvation => messages.text("cause_of_death_starved", &[level]),
+            DeathCause::Terrain(tile) => messages.text("cause_of_death_terrain", &[
+                level,
+                ("terrain", messages.tile_text(*tile)),
+            ]),
+            DeathCause::Trap(kind) => messages.text("cause_of_death_trap", &[
+                level,
+                ("trap", messages.trap_text(*kind)),
+            ]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_can_act() {
+        assert!(GameState::default().can_act());
+        assert!(!GameState::Dead.can_act());
+        assert!(!GameState::Descending.can_act());
+        assert!(!GameState::Won.can_act());
+    }
+
+    #[test]
+    fn test_can_save() {
+        assert!(GameState::Playing.can_save());
+        assert!(!GameState::Dead.can_save());
+        assert!(!GameState::Descending.can_save());
+        assert!(!GameState::Won.can_save());
+    }
+
+    #[test]
+    fn test_after_floor_cleared() {
+        assert_eq!(GameState::after_floor_cleared(1, 10), GameState::Descending);
+        assert_eq!(GameState::after_floor_cleared(10, 10), GameState::Won);
+        // 最後の階層を指定しない場合はどこまでも進む
+        assert_eq!(GameState::after_floor_cleared(100, 0), GameState::Descending);
+    }
+
+    #[test]
+    fn test_cause_of_death_text() {
+        let messages = MessageCatalog::default_catalog("ja").unwrap();
+        let cause = CauseOfDeath::new(DeathCause::Mob { id: 2, name: "スライム".to_string() }, 1);
+        assert_eq!(cause.text(&messages), "1階でID2のスライムに倒された。");
+        // 毒で力尽きた場合は敵の名前を出さない
+        let cause = CauseOfDeath::new(DeathCause::Status(StatusKind::Poison), 3);
+        assert_eq!(cause.text(&messages), "3階で毒状態のまま力尽きた。");
+        let cause = CauseOfDeath::new(DeathCause::Starvation, 5);
+        assert_eq!(cause.text(&messages), "5階で飢えて力尽きた。");
+        let cause = CauseOfDeath::new(DeathCause::Terrain(Tile::Lava), 2);
+        assert_eq!(cause.text(&messages), "2階で溶岩に呑まれて力尽きた。");
+        let cause = CauseOfDeath::new(DeathCause::Trap(TrapKind::Damage), 4);
+        assert_eq!(cause.text(&messages), "4階でトゲの罠にかかって力尽きた。");
+    }
+
+    #[test]
+    fn test_cause_of_death_serialize() {
+        let cause = CauseOfDeath::new(DeathCause::Terrain(Tile::Lava), 2);
+        let json = serde_json::to_string(&cause).unwrap();
+        assert_eq!(serde_json::from_str::<CauseOfDeath>(&json).unwrap(), cause);
+
+        // 原因の種類がない以前の形式も読み込める
+        let old = r#"{"mob_id": 4, "mob_name": "ゴブリン", "level": 3}"#;
+        assert_eq!(serde_json::from_str::<CauseOfDeath>(old).unwrap(),
+            CauseOfDeath::new(DeathCause::Mob { id: 4, name: "ゴブリン".to_string() }, 3));
+        let old = r#"{"mob_id": -1, "mob_name": "", "level": 5, "status": null, "starved": true}"#;
+        assert_eq!(serde_json::from_str::<CauseOfDeath>(old).unwrap(), CauseOfDeath::new(DeathCause::Starvation, 5));
+        let old = r#"{"mob_id": -1, "mob_name": "", "level": 3, "status": "Poison", "starved": false, "terrain": null}"#;
+        assert_eq!(serde_json::from_str::<CauseOfDeath>(old).unwrap(),
+            CauseOfDeath::new(DeathCause::Status(StatusKind::Poison), 3));
+    }
+}
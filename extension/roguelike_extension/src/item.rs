@@ -1,10 +1,14 @@
 //! アイテムを定義するモジュール
 
+use crate::attack_pattern::AttackPattern;
+use crate::status_effect::StatusEffect;
+
+use serde::{Deserialize, Serialize};
 use std::rc::Rc;
 use std::cell::RefCell;
 
 /// アイテム管理用クラス
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum GameItem {
     /// 無
     Null,
@@ -14,26 +18,84 @@ pub enum GameItem {
     Sword(Sword),
     /// 防具
     Shield(Shield),
+    /// 食料
+    Food(Food),
+    /// 鍵
+    Key(Key),
+    /// 薬
+    Elixir(Elixir),
+}
+
+impl GameItem {
+    /// 装備品の場合は装備する箇所を返す
+    pub fn equip_slot(&self) -> Option<EquipSlot> {
+        match self {
+            GameItem::Sword(_) => Some(EquipSlot::Weapon),
+            GameItem::Shield(_) => Some(EquipSlot::Armor),
+            _ => None,
+        }
+    }
+
+    /// 投げて当たったときに攻撃力に加える値、装備品はその性能の分だけ強くなる
+    pub fn throw_bonus(&self) -> i32 {
+        match self {
+            GameItem::Sword(sword) => sword.attack_bonus,
+            GameItem::Shield(shield) => shield.defense_bonus,
+            _ => 0,
+        }
+    }
+}
+
+/// 装備する箇所
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EquipSlot {
+    /// 武器
+    Weapon,
+    /// 防具
+    Armor,
 }
 
 /// 回復薬
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct HealthPotion {
     pub heal_amount: i32,
 }
 
 /// 武器
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Sword {
     pub attack_bonus: i32,
+    /// 攻撃の届く範囲
+    #[serde(default)]
+    pub pattern: AttackPattern,
 }
 
 /// 防具
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Shield {
     pub defense_bonus: i32,
 }
 
+/// 食料
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Food {
+    /// 食べたときに回復する満腹度
+    pub nutrition: i32,
+}
+
+/// 鍵、鍵のかかった扉を開けるのに使う
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Key {
+    /// 残りの使用回数
+    pub uses: i32,
+}
+
+/// 薬、飲むと状態異常にかかる
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Elixir {
+    pub effect: StatusEffect,
+}
+
 /// アイテムの効果が自分以外に及ぶ場合のクラス
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SideEffect {
@@ -45,6 +107,7 @@ pub enum SideEffect {
 
 
 /// アイテムに座標系と管理IDを割り付けたクラス
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DroppedItem {
     pub id: i32,
     pub position: (i32, i32),
@@ -7,10 +7,29 @@ struct RogueLikeExtension;
 #[gdextension]
 unsafe impl ExtensionLibrary for RogueLikeExtension {}
 
+pub mod tile;
+pub mod trap;
 pub mod static_map;
 pub mod map_generator;
+pub mod cave_generator;
+pub mod maze_generator;
+pub mod vault_generator;
 pub mod dynamic_map;
 pub mod game_master;
 pub mod player;
 pub mod mob;
 pub mod item;
+pub mod save_data;
+pub mod combat;
+pub mod catalog;
+pub mod fov;
+pub mod pathfinding;
+pub mod ai;
+pub mod scheduler;
+pub mod event;
+pub mod locale;
+pub mod game_state;
+pub mod run_summary;
+pub mod projectile;
+pub mod attack_pattern;
+pub mod status_effect;
@@ -0,0 +1,135 @@
+//! 表示するメッセージを言語ごとに管理するモジュール
+//!
+//! メッセージはIDをキーにしたJSONのカタログに記述し、言語ごとに別のファイルにする。
+//! メッセージ中の`{name}`は引数で置き換える。
+
+use std::collections::HashMap;
+
+use crate::item::GameItem;
+use crate::status_effect::StatusKind;
+use crate::tile::Tile;
+use crate::trap::TrapKind;
+
+use serde::{Deserialize, Serialize};
+
+/// 既定の言語
+pub const DEFAULT_LOCALE: &str = "ja";
+/// 用意している言語
+pub const LOCALES: [&str; 2] = ["ja", "en"];
+
+/// 既定の日本語のメッセージ
+pub const DEFAULT_MESSAGES_JA: &str = include_str!("../../../project/data/messages_ja.json");
+/// 既定の英語のメッセージ
+pub const DEFAULT_MESSAGES_EN: &str = include_str!("../../../project/data/messages_en.json");
+
+/// 1つの言語のメッセージのカタログ
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MessageCatalog {
+    /// メッセージIDとメッセージの対応
+    pub messages: HashMap<String, String>,
+}
+
+impl MessageCatalog {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| e.to_string())
+    }
+
+    /// 埋め込みの既定のカタログ、用意していない言語の場合はNone
+    pub fn default_catalog(locale: &str) -> Option<Self> {
+        let json = match locale {
+            "ja" => DEFAULT_MESSAGES_JA,
+            "en" => DEFAULT_MESSAGES_EN,
+            _ => return None,
+        };
+        Some(Self::from_json(json).unwrap())
+    }
+
+    /// メッセージIDからメッセージを作る
+    ///
+    /// # Arguments
+    /// * `key` - メッセージID、カタログにない場合はIDをそのまま返す
+    /// * `params` - 置き換える引数の名前と値の組
+    pub fn text(&self, key: &str, params: &[(&str, String)]) -> String {
+        let mut text = match self.messages.get(key) {
+            Some(text) => text.clone(),
+            None => return key.to_string(),
+        };
+        for (name, value) in params {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        text
+    }
+
+    /// アイテムを表示用の文字列に変換する
+    pub fn item_text(&self, item: &GameItem) -> String {
+        let (key, value) = match item {
+            GameItem::HealthPotion(potion) => ("item_health_potion", potion.heal_amount),
+            GameItem::Sword(sword) => ("item_sword", sword.attack_bonus),
+            GameItem::Shield(shield) => ("item_shield", shield.defense_bonus),
+            GameItem::Food(food) => ("item_food", food.nutrition),
+            GameItem::Key(key) => ("item_key", key.uses),
+            GameItem::Elixir(elixir) => {
+                let status = self.status_text(elixir.effect.kind);
+                return self.text("item_elixir", &[("status", status), ("value", elixir.effect.turns.to_string())]);
+            },
+            _ => return self.text("item_none", &[]),
+        };
+        self.text(key, &[("value", value.to_string())])
+    }
+
+    /// 状態異常の名前
+    pub fn status_text(&self, kind: StatusKind) -> String {
+        self.text(&format!("status_{}", kind.key()), &[])
+    }
+
+    /// 罠の名前
+    pub fn trap_text(&self, kind: TrapKind) -> String {
+        self.text(&format!("trap_{}", kind.key()), &[])
+    }
+
+    /// 地形の名前
+    pub fn tile_text(&self, tile: Tile) -> String {
+        self.text(&format!("tile_{}", tile.key()), &[])
+    }
+}
+
+/// 用意している言語の既定のカタログをすべて読み込む
+pub fn default_catalogs() -> HashMap<String, MessageCatalog> {
+    LOCALES.iter()
+        .filter_map(|locale| MessageCatalog::default_catalog(locale).map(|catalog| (locale.to_string(), catalog)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        let catalog = MessageCatalog::default_catalog("ja").unwrap();
+        let params = [("id", 3.to_string()), ("damage", 12.to_string())];
+        assert_eq!(catalog.text("player_attack_hit", &params), "ID3に12ダメージを与えた。");
+        assert_eq!(catalog.text("item_picked_up", &[]), "アイテムを拾った。");
+        // カタログにないIDはそのまま返す
+        assert_eq!(catalog.text("unknown_message", &[]), "unknown_message");
+
+        let catalog = MessageCatalog::default_catalog("en").unwrap();
+        assert_eq!(catalog.text("player_attack_hit", &params), "You deal 12 damage to ID3.");
+        assert!(MessageCatalog::default_catalog("fr").is_none());
+    }
+
+    #[test]
+    fn test_locales_have_same_keys() {
+        // どの言語にも同じメッセージIDがそろっている
+        let catalogs = default_catalogs();
+        assert_eq!(catalogs.len(), LOCALES.len());
+        let mut expected: Vec<_> = catalogs[DEFAULT_LOCALE].messages.keys().collect();
+        expected.sort();
+        for catalog in catalogs.values() {
+            let mut keys: Vec<_> = catalog.messages.keys().collect();
+            keys.sort();
+            assert_eq!(keys, expected);
+        }
+    }
+}
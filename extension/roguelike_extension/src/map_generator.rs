@@ -1,6 +1,22 @@
 //! マップ生成アルゴリズムを提供するモジュール
 
+use crate::cave_generator::CaveGenerator;
+use crate::cave_generator::DrunkardWalkGenerator;
+use crate::maze_generator::MazeGenerator;
+use crate::pathfinding::DiagonalRule;
+use crate::pathfinding::PathFinder;
+use crate::tile::tile_at;
+use crate::tile::DoorState;
+use crate::tile::Tile;
+use crate::vault_generator::VaultGenerator;
+
 use rand;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::io::Cursor;
 use std::cmp::{
 max, min
@@ -8,7 +24,7 @@ max, min
   
   
 /// 部屋をつなぐための方向を定義したenum
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Direction {
     /// 上
     North,
@@ -23,6 +39,7 @@ pub enum Direction {
 }
   
 /// 二分木が持つべき構造体を定義
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BSPNodeParams {
     /// 部屋の左上のx座標
     pub x: i32,
@@ -72,11 +89,108 @@ pub fn print_tree(tree: &BSPTree) {
     }
 }
   
+/// 階層ごとのシード値を求める関数
+///
+/// 同じシード値でも階層ごとに異なるマップが生成されるように、階層番号を混ぜる。
+pub fn floor_seed(seed: u64, level: i32) -> u64 {
+    seed ^ (level as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+/// プレイヤーの初期位置とゴールを置く部屋を選ぶ関数
+///
+/// `separate`がtrueで部屋が2つ以上ある場合は、必ず別の部屋を選ぶ。
+///
+/// # Returns
+/// 初期位置の部屋とゴールの部屋の番号の組
+pub fn choose_start_and_goal_rooms<R: Rng>(room_count: usize, separate: bool, rng: &mut R) -> (usize, usize) {
+    let start = rng.gen_range(0..room_count);
+    let goal = if separate && room_count > 1 {
+        // 初期位置の部屋を除いた中から選ぶ
+        let idx = rng.gen_range(0..room_count - 1);
+        if idx >= start { idx + 1 } else { idx }
+    } else {
+        rng.gen_range(0..room_count)
+    };
+    (start, goal)
+}
+
+/// プレイヤーの初期位置とゴールを置く部屋を、ゴールにたどり着けるように選ぶ関数
+///
+/// `choose_start_and_goal_rooms`で選んだゴールが初期位置からたどり着けない場合は、
+/// たどり着ける部屋の中から選び直す。たどり着ける部屋がほかにない場合は初期位置と同じ部屋にする。
+///
+/// # Returns
+/// 初期位置の部屋とゴールの部屋の番号の組、部屋がない場合はNone
+pub fn choose_reachable_start_and_goal_rooms<R: Rng>(
+    dungeon: &[Vec<Tile>],
+    rooms: &[BSPNodeParams],
+    separate: bool,
+    rng: &mut R
+) -> Option<(usize, usize)> {
+    if rooms.is_empty() {
+        return None;
+    }
+    let (start, goal) = choose_start_and_goal_rooms(rooms.len(), separate, rng);
+    let reachable = reachable_rooms(dungeon, rooms, start);
+    if reachable.contains(&goal) {
+        return Some((start, goal));
+    }
+    let candidates: Vec<usize> = reachable.into_iter().filter(|idx| *idx != start).collect();
+    Some((start, candidates.choose(rng).copied().unwrap_or(start)))
+}
+
+/// 通路が部屋に入るマスに閉じた扉を置く関数
+///
+/// 両脇が壁になっている1マス幅の通路のうち、進んだ先が部屋の床になっているマスを部屋の入口とみなす。
+/// 部屋の床かどうかは、そのマスと1つ奥のマスの両脇まで床が広がっているかで判定する。
+/// 通路の端ごとに扉は1つだけ置き、すぐ隣に扉を置いたマスには置かない。
+///
+/// # Returns
+/// 扉を置いたマス
+pub fn place_doors(dungeon: &mut [Vec<Tile>]) -> Vec<(i32, i32)> {
+    fn is_floor(dungeon: &[Vec<Tile>], x: i32, y: i32) -> bool {
+        tile_at(dungeon, x, y) == Tile::Floor
+    }
+    // (x, y)から(dx, dy)の向きに2マス分、両脇(sx, sy)まで床になっているかどうか
+    fn is_room(dungeon: &[Vec<Tile>], (x, y): (i32, i32), (dx, dy): (i32, i32), (sx, sy): (i32, i32)) -> bool {
+        (0..2).all(|step| {
+            let (cx, cy) = (x + dx * step, y + dy * step);
+            is_floor(dungeon, cx, cy) && is_floor(dungeon, cx + sx, cy + sy) && is_floor(dungeon, cx - sx, cy - sy)
+        })
+    }
+
+    let mut doors = vec![];
+    for x in 0..dungeon.len() as i32 {
+        for y in 0..dungeon[x as usize].len() as i32 {
+            if !is_floor(dungeon, x, y) {
+                continue;
+            }
+            // 通路の向きと、その両脇の向きの組
+            for ((dx, dy), (sx, sy)) in [((1, 0), (0, 1)), ((0, 1), (1, 0))] {
+                let walled = tile_at(dungeon, x + sx, y + sy) == Tile::Wall
+                    && tile_at(dungeon, x - sx, y - sy) == Tile::Wall;
+                let passage = is_floor(dungeon, x + dx, y + dy) && is_floor(dungeon, x - dx, y - dy);
+                let entrance = is_room(dungeon, (x + dx, y + dy), (dx, dy), (sx, sy))
+                    || is_room(dungeon, (x - dx, y - dy), (-dx, -dy), (sx, sy));
+                let next_to_door = doors.contains(&(x - dx, y - dy)) || doors.contains(&(x + dx, y + dy));
+                if walled && passage && entrance && !next_to_door {
+                    doors.push((x, y));
+                }
+            }
+        }
+    }
+    for (x, y) in &doors {
+        dungeon[*x as usize][*y as usize] = Tile::Door(DoorState::Closed);
+    }
+    doors
+}
+
 /// 二分木を生成する再帰関数
 ///
 /// ノードが必ず左右に存在することを保証しなければならない。
+/// 分割方向と分割位置は引数で渡された乱数生成器から決める。
 ///
-pub fn generate_bsp_tree(x: i32, y: i32, width: i32, height: i32, connect_to: Direction, level: i32) -> BSPTree {
+pub fn generate_bsp_tree<R: Rng>(x: i32, y: i32, width: i32, height: i32, connect_to: Direction, level: i32, rng: &mut R) -> BSPTree {
     let min_room_size = 16;
     if width < min_room_size || height < min_room_size {
         return BSPTree::Nil;
@@ -88,15 +202,15 @@ pub fn generate_bsp_tree(x: i32, y: i32, width: i32, height: i32, connect_to: Di
     }
   
     // let split = level % 2 == 0;
-    let split = rand::random::<i32>() % 2 == 0;
+    let split = rng.gen::<i32>() % 2 == 0;
     if split {
-        let split_x = max(min(x + 1 + rand::random::<i32>() % (width - 2), x + width - min_room_size), x + min_room_size);
+        let split_x = max(min(x + 1 + rng.gen::<i32>() % (width - 2), x + width - min_room_size), x + min_room_size);
         // let split_x = width / 2 + x;
         // for i in y..y + height {
         //   dungeon[split_x as usize][i as usize] = 0;
         // }
-        let mut left = generate_bsp_tree(x, y, split_x - x, height, Direction::East, level + 1);
-        let mut right = generate_bsp_tree(split_x, y, x + width - split_x, height, Direction::West, level + 1);
+        let mut left = generate_bsp_tree(x, y, split_x - x, height, Direction::East, level + 1, rng);
+        let mut right = generate_bsp_tree(split_x, y, x + width - split_x, height, Direction::West, level + 1, rng);
         if let BSPTree::Nil = left {
             right = BSPTree::Nil;
         }
@@ -117,13 +231,13 @@ pub fn generate_bsp_tree(x: i32, y: i32, width: i32, height: i32, connect_to: Di
             right: Box::new(right),
         };
     } else {
-        let split_y = max(min(y + 1 + rand::random::<i32>() % (height - 2), y + height - min_room_size), y + min_room_size);
+        let split_y = max(min(y + 1 + rng.gen::<i32>() % (height - 2), y + height - min_room_size), y + min_room_size);
         // let split_y = height / 2 + y;
         // for i in x..x + width {
         //   dungeon[i as usize][split_y as usize] = 0;
         // }
-        let mut left = generate_bsp_tree(x, y, width, split_y - y, Direction::South, level + 1);
-        let mut right = generate_bsp_tree(x, split_y, width, y + height - split_y, Direction::North, level + 1);
+        let mut left = generate_bsp_tree(x, y, width, split_y - y, Direction::South, level + 1, rng);
+        let mut right = generate_bsp_tree(x, split_y, width, y + height - split_y, Direction::North, level + 1, rng);
         if let BSPTree::Nil = left {
             right = BSPTree::Nil;
         }
@@ -146,18 +260,29 @@ pub fn generate_bsp_tree(x: i32, y: i32, width: i32, height: i32, connect_to: Di
     }
 }
   
-/// ダンジョンを生成する関数
+/// シード値を指定してダンジョンを生成する関数
+///
+/// シード値を指定しなかった場合はランダムに決める。
+/// 同じシード値からは常に同じマップが生成されるので、使ったシード値も返す。
 pub fn generate_dungeon(
     width: i32,
-    height: i32
-) -> (Vec<Vec<i32>>, Vec<BSPNodeParams>) {
+    height: i32,
+    seed: Option<u64>
+) -> (Vec<Vec<Tile>>, Vec<BSPNodeParams>, u64) {
+    let seed = seed.unwrap_or_else(rand::random::<u64>);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (dungeon, room_params) = generate_dungeon_with_rng(width, height, &mut rng);
+    (dungeon, room_params, seed)
+}
+
+/// 乱数生成器を指定してダンジョンを生成する関数
+pub fn generate_dungeon_with_rng<R: Rng>(
+    width: i32,
+    height: i32,
+    rng: &mut R
+) -> (Vec<Vec<Tile>>, Vec<BSPNodeParams>) {
     // dense matrixとしてdungeonを定義
-    let mut dungeon = vec![vec![0; height as usize]; width as usize];
-    for i in 0..width {
-        for j in 0..height {
-            dungeon[i as usize][j as usize] = 1;
-        }
-    }
+    let mut dungeon = vec![vec![Tile::Wall; height as usize]; width as usize];
     // 確実に壁に埋まらない場所として、BSPTreeで確保した部屋の中心をすべて戻す
     // 各部屋の大きさがわかれば、部屋の中心から壁ではない場所を容易に探せる
     let mut room_params = vec![];
@@ -165,9 +290,9 @@ pub fn generate_dungeon(
     println!("generate dungeon");
   
     // 二分木を生成する関数を使う
-    let mut tree = generate_bsp_tree(0, 0, width, height, Direction::None, 0);
+    let mut tree = generate_bsp_tree(0, 0, width, height, Direction::None, 0, rng);
     // BSPTreeを使ってdungeonに反映
-    fn fill_minimum_nodes(dungeon: &mut Vec<Vec<i32>>, tree: &mut BSPTree) {
+    fn fill_minimum_nodes<R: Rng>(dungeon: &mut Vec<Vec<Tile>>, tree: &mut BSPTree, rng: &mut R) {
         // 各部屋の塗りつぶしのアルゴリズム
         // 自分が終端ノードだった場合、そこで初めて塗りつぶしを行う。
         // それ以外の場合、左右の子ノードに対して再帰的に塗りつぶしを行う。
@@ -179,10 +304,10 @@ pub fn generate_dungeon(
     
                 // 先に再帰呼び出しを行わないと、子ノードのroom_center_x, room_center_yが更新されない。
                 if let BSPTree::Node { value: left_value, left: left_left, right: left_right } = left.as_ref() {
-                    fill_minimum_nodes(dungeon, left);
+                    fill_minimum_nodes(dungeon, left, rng);
                 }
                 if let BSPTree::Node { value: right_value, left: right_left, right: right_right } = right.as_ref() {
-                    fill_minimum_nodes(dungeon, right);
+                    fill_minimum_nodes(dungeon, right, rng);
                 }
   
                 // 左右の子ノードのroom_center_x, room_center_yを確認して、自分のconnect_toに近いほうを選び、
@@ -247,10 +372,10 @@ pub fn generate_dungeon(
                 if let BSPTree::Nil = left.as_ref() {
                     // 内側をborderサイズ分だけ残して0で塗りつぶす
                     // borderは2--(2+3)のうちランダムで決める
-                    let border = 2 + rand::random::<u8>() % 3;
+                    let border = 2 + rng.gen::<u8>() % 3;
                     for i in value.x + border as i32..value.x + value.width - border as i32 {
                         for j in value.y + border as i32..value.y + value.height - border as i32 {
-                            dungeon[i as usize][j as usize] = 0;
+                            dungeon[i as usize][j as usize] = Tile::Floor;
                         }
                     }
                 }
@@ -260,11 +385,11 @@ pub fn generate_dungeon(
             }
         }
     }
-    fill_minimum_nodes(&mut dungeon, &mut tree);
+    fill_minimum_nodes(&mut dungeon, &mut tree, rng);
     print_tree(&tree);
   
     // room_center_x, room_center_yを使って部屋同士をつなぐ
-    fn connect_rooms(dungeon: &mut Vec<Vec<i32>>, tree: &mut BSPTree) {
+    fn connect_rooms(dungeon: &mut Vec<Vec<Tile>>, tree: &mut BSPTree) {
         match tree {
             BSPTree::Node { value, left, right } => {
                 if let BSPTree::Node { value: left_value, left: left_left, right: left_right } = left.as_ref() {
@@ -288,28 +413,28 @@ pub fn generate_dungeon(
                                 println!("north");
                                 left_connect_x = Some(left_value.room_center_x);
                                 for i in (right_value.y + right_value.height)..left_value.room_center_y {
-                                    dungeon[left_value.room_center_x as usize][i as usize] = 0;
+                                    dungeon[left_value.room_center_x as usize][i as usize] = Tile::Floor;
                                 }
                             },
                             Direction::South => {
                                 println!("south");
                                 left_connect_x = Some(left_value.room_center_x);
                                 for i in left_value.room_center_y..right_value.y {
-                                    dungeon[left_value.room_center_x as usize][i as usize] = 0;
+                                    dungeon[left_value.room_center_x as usize][i as usize] = Tile::Floor;
                                 }
                             },
                             Direction::East => {
                                 println!("east");
                                 left_connect_y = Some(left_value.room_center_y);
                                 for i in left_value.room_center_x..right_value.x {
-                                    dungeon[i as usize][left_value.room_center_y as usize] = 0;
+                                    dungeon[i as usize][left_value.room_center_y as usize] = Tile::Floor;
                                 }
                             },
                             Direction::West => {
                                 println!("west");
                                 left_connect_y = Some(left_value.room_center_y);
                                 for i in (right_value.x + right_value.width)..left_value.room_center_x {
-                                    dungeon[i as usize][left_value.room_center_y as usize] = 0;
+                                    dungeon[i as usize][left_value.room_center_y as usize] = Tile::Floor;
                                 }
                             },
                             _ => {
@@ -325,28 +450,28 @@ pub fn generate_dungeon(
                                 println!("to = {}", right_value.room_center_y);
                                 right_connect_x = Some(right_value.room_center_x);
                                 for i in (left_value.y + left_value.height)..right_value.room_center_y {
-                                    dungeon[right_value.room_center_x as usize][i as usize] = 0;
+                                    dungeon[right_value.room_center_x as usize][i as usize] = Tile::Floor;
                                 }
                             },
                             Direction::South => {
                                 println!("south");
                                 right_connect_x = Some(right_value.room_center_x);
                                 for i in right_value.room_center_y..left_value.y {
-                                    dungeon[right_value.room_center_x as usize][i as usize] = 0;
+                                    dungeon[right_value.room_center_x as usize][i as usize] = Tile::Floor;
                                 }
                             },
                             Direction::East => {
                                 println!("east");
                                 right_connect_y = Some(right_value.room_center_y);
                                 for i in right_value.room_center_x..left_value.x {
-                                    dungeon[i as usize][right_value.room_center_y as usize] = 0;
+                                    dungeon[i as usize][right_value.room_center_y as usize] = Tile::Floor;
                                 }
                             },
                             Direction::West => {
                                 println!("west");
                                 right_connect_y = Some(right_value.room_center_y);
                                 for i in (left_value.x + left_value.width)..right_value.room_center_x {
-                                    dungeon[i as usize][right_value.room_center_y as usize] = 0;
+                                    dungeon[i as usize][right_value.room_center_y as usize] = Tile::Floor;
                                 }
                             },
                             _ => {
@@ -361,11 +486,11 @@ pub fn generate_dungeon(
                                 let y = if (left_value.y > right_value.y) { left_value.y } else { right_value.y };
                                 if left_connect_x.unwrap() < right_connect_x.unwrap() {
                                     for i in left_connect_x.unwrap()..(right_connect_x.unwrap() + 1) {
-                                        dungeon[i as usize][y as usize] = 0;
+                                        dungeon[i as usize][y as usize] = Tile::Floor;
                                     }
                                 } else {
                                     for i in right_connect_x.unwrap()..(left_connect_x.unwrap() + 1) {
-                                        dungeon[i as usize][y as usize] = 0;
+                                        dungeon[i as usize][y as usize] = Tile::Floor;
                                     }
                                 }
                             }
@@ -376,11 +501,11 @@ pub fn generate_dungeon(
                                 let x = if (left_value.x > right_value.x) { left_value.x } else { right_value.x };
                                 if left_connect_y.unwrap() < right_connect_y.unwrap() {
                                     for i in left_connect_y.unwrap()..(right_connect_y.unwrap() + 1) {
-                                        dungeon[x as usize][i as usize] = 0;
+                                        dungeon[x as usize][i as usize] = Tile::Floor;
                                     }
                                 } else {
                                     for i in right_connect_y.unwrap()..(left_connect_y.unwrap() + 1) {
-                                        dungeon[x as usize][i as usize] = 0;
+                                        dungeon[x as usize][i as usize] = Tile::Floor;
                                     }
                                 }
                             }
@@ -393,6 +518,8 @@ pub fn generate_dungeon(
         }
     }
     connect_rooms(&mut dungeon, &mut tree);
+    // 通路と部屋の境目に扉を置く
+    place_doors(&mut dungeon);
 
     // room_paramsに部屋の情報をすべて格納する
     fn get_room_dimensions(
@@ -431,14 +558,321 @@ pub fn generate_dungeon(
     (dungeon, room_params)
 }
 
+/// マップ生成アルゴリズムが共通で実装するtrait
+///
+/// どのアルゴリズムも地形と部屋の一覧を返す。部屋の中心は床にし、部屋の範囲はマップの内側に収める。
+/// 部屋の中心はプレイヤーの初期位置とゴールの候補に、部屋の範囲はアイテムや敵の配置に使う。
+pub trait MapGenerator {
+    /// 地形と部屋の一覧を生成する
+    fn generate(&self, width: i32, height: i32, rng: &mut StdRng) -> (Vec<Vec<Tile>>, Vec<BSPNodeParams>);
+}
+
+/// 二分木で区切った部屋を通路でつなぐ生成アルゴリズム
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BspGenerator;
+
+impl MapGenerator for BspGenerator {
+    fn generate(&self, width: i32, height: i32, rng: &mut StdRng) -> (Vec<Vec<Tile>>, Vec<BSPNodeParams>) {
+        generate_dungeon_with_rng(width, height, rng)
+    }
+}
+
+/// マップ生成アルゴリズムの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GeneratorKind {
+    /// 二分木で区切った部屋と通路
+    Bsp,
+    /// セルオートマトンで削った洞窟
+    Cave,
+    /// ランダムウォークで掘り進めた洞窟
+    DrunkardWalk,
+    /// 部屋の間を迷路で埋めたもの
+    RoomsAndMaze,
+    /// 用意した型の部屋を並べて通路でつないだもの
+    Vaults,
+}
+
+impl GeneratorKind {
+    pub const ALL: [GeneratorKind; 5] = [
+        GeneratorKind::Bsp,
+        GeneratorKind::Cave,
+        GeneratorKind::DrunkardWalk,
+        GeneratorKind::RoomsAndMaze,
+        GeneratorKind::Vaults,
+    ];
+
+    /// Godot側から指定するときに使う文字列
+    pub fn key(&self) -> &'static str {
+        match self {
+            GeneratorKind::Bsp => "bsp",
+            GeneratorKind::Cave => "cave",
+            GeneratorKind::DrunkardWalk => "drunkard",
+            GeneratorKind::RoomsAndMaze => "maze",
+            GeneratorKind::Vaults => "vault",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<GeneratorKind> {
+        GeneratorKind::ALL.iter().copied().find(|kind| kind.key() == key)
+    }
+
+    /// 既定の設定の生成アルゴリズム
+    pub fn generator(&self) -> Box<dyn MapGenerator> {
+        match self {
+            GeneratorKind::Bsp => Box::new(BspGenerator),
+            GeneratorKind::Cave => Box::new(CaveGenerator::default()),
+            GeneratorKind::DrunkardWalk => Box::new(DrunkardWalkGenerator::default()),
+            GeneratorKind::RoomsAndMaze => Box::new(MazeGenerator::default()),
+            GeneratorKind::Vaults => Box::new(VaultGenerator::default()),
+        }
+    }
+}
+
+/// つながっているかどうかの判定に使う経路探索の設定
+///
+/// 斜め移動のルールはGodot側から変えられるので、どのルールでも通れるよう縦横の移動だけでつながっていることを求める。
+fn connectivity_finder() -> PathFinder {
+    PathFinder {
+        diagonal_rule: DiagonalRule::Never,
+        ..Default::default()
+    }
+}
+
+/// 2点の間をL字の通路でつなぐ関数
+///
+/// 通れないマスだけを床にするので、途中にある水や扉はそのまま残る。
+pub fn carve_corridor<R: Rng>(dungeon: &mut [Vec<Tile>], from: (i32, i32), to: (i32, i32), rng: &mut R) {
+    // 横に進んでから縦に進むか、縦に進んでから横に進むかを選ぶ
+    let corner = if rng.gen::<bool>() { (to.0, from.1) } else { (from.0, to.1) };
+    for ((x0, y0), (x1, y1)) in [(from, corner), (corner, to)] {
+        for x in min(x0, x1)..=max(x0, x1) {
+            for y in min(y0, y1)..=max(y0, y1) {
+                if !PathFinder::is_passable(dungeon, x, y) {
+                    dungeon[x as usize][y as usize] = Tile::Floor;
+                }
+            }
+        }
+    }
+}
+
+/// 指定した範囲を部屋とする関数
+///
+/// 部屋の中心は範囲の中心に最も近い床にする。
+///
+/// # Returns
+/// 範囲に床がない場合はNone
+pub fn room_around(dungeon: &[Vec<Tile>], x: i32, y: i32, width: i32, height: i32) -> Option<BSPNodeParams> {
+    let (cx, cy) = (x + width / 2, y + height / 2);
+    let mut center = None;
+    let mut best = i32::MAX;
+    for i in x..x + width {
+        for j in y..y + height {
+            let distance = (i - cx).abs() + (j - cy).abs();
+            if tile_at(dungeon, i, j) == Tile::Floor && distance < best {
+                best = distance;
+                center = Some((i, j));
+            }
+        }
+    }
+    let (room_center_x, room_center_y) = center?;
+    Some(BSPNodeParams {
+        x,
+        y,
+        width,
+        height,
+        room_center_x,
+        room_center_y,
+        connect_to: Direction::None,
+    })
+}
+
+/// マップを区画に分け、床が十分にある区画を部屋とみなす関数
+///
+/// 洞窟のように部屋の区切りがないマップで、初期位置やアイテムの配置の候補を散らばらせるのに使う。
+pub fn rooms_from_sectors(dungeon: &[Vec<Tile>], sector_size: i32) -> Vec<BSPNodeParams> {
+    let width = dungeon.len() as i32;
+    let height = dungeon.first().map_or(0, |column| column.len() as i32);
+    let sector_size = sector_size.max(1);
+    let mut rooms = vec![];
+    for x in (0..width).step_by(sector_size as usize) {
+        for y in (0..height).step_by(sector_size as usize) {
+            let (w, h) = (min(sector_size, width - x), min(sector_size, height - y));
+            let floor_count = (x..x + w)
+                .flat_map(|i| (y..y + h).map(move |j| (i, j)))
+                .filter(|&(i, j)| dungeon[i as usize][j as usize] == Tile::Floor)
+                .count() as i32;
+            // 床がわずかしかない区画は部屋にしない
+            if floor_count * 8 < w * h {
+                continue;
+            }
+            rooms.extend(room_around(dungeon, x, y, w, h));
+        }
+    }
+    rooms
+}
+
+/// 条件を満たすマスが縦横につながった領域をすべて求める
+fn regions_by(dungeon: &[Vec<Tile>], belongs: impl Fn(i32, i32) -> bool) -> Vec<Vec<(i32, i32)>> {
+    let mut visited: Vec<Vec<bool>> = dungeon.iter().map(|column| vec![false; column.len()]).collect();
+    let mut regions = vec![];
+    for x in 0..dungeon.len() {
+        for y in 0..dungeon[x].len() {
+            if visited[x][y] || !belongs(x as i32, y as i32) {
+                continue;
+            }
+            let mut region = vec![];
+            let mut open = VecDeque::from([(x as i32, y as i32)]);
+            visited[x][y] = true;
+            while let Some((cx, cy)) = open.pop_front() {
+                region.push((cx, cy));
+                for (dx, dy) in [(0, -1), (1, 0), (0, 1), (-1, 0)] {
+                    let (nx, ny) = (cx + dx, cy + dy);
+                    if belongs(nx, ny) && !visited[nx as usize][ny as usize] {
+                        visited[nx as usize][ny as usize] = true;
+                        open.push_back((nx, ny));
+                    }
+                }
+            }
+            regions.push(region);
+        }
+    }
+    regions
+}
+
+/// 縦横につながった床の領域をすべて求める関数
+///
+/// # Returns
+/// 領域ごとのマスの一覧、マップを左上から順に走査して見つかった順
+pub fn floor_regions(dungeon: &[Vec<Tile>]) -> Vec<Vec<(i32, i32)>> {
+    regions_by(dungeon, |x, y| tile_at(dungeon, x, y) == Tile::Floor)
+}
+
+/// 縦横につながった通れるマスの領域をすべて求める関数
+///
+/// 水や開けられる扉も通れるものとみなす。
+///
+/// # Returns
+/// 領域ごとのマスの一覧、マップを左上から順に走査して見つかった順
+pub fn passable_regions(dungeon: &[Vec<Tile>]) -> Vec<Vec<(i32, i32)>> {
+    regions_by(dungeon, |x, y| PathFinder::is_passable(dungeon, x, y))
+}
+
+/// 指定した部屋の中心からたどり着ける部屋を求める関数
+///
+/// 閉じた扉は開けて通れるものとみなす。
+///
+/// # Returns
+/// たどり着ける部屋の番号、指定した部屋自身も含む
+pub fn reachable_rooms(dungeon: &[Vec<Tile>], rooms: &[BSPNodeParams], from: usize) -> Vec<usize> {
+    let Some(room) = rooms.get(from) else {
+        return vec![];
+    };
+    let distances = connectivity_finder().dijkstra_map(dungeon, &[(room.room_center_x, room.room_center_y)]);
+    rooms.iter()
+        .enumerate()
+        .filter(|(_, room)| distances.distance(room.room_center_x, room.room_center_y).is_some())
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// 最初の部屋の中心からたどり着けない部屋を求める関数
+///
+/// 閉じた扉は開けて通れるものとみなす。
+///
+/// # Returns
+/// たどり着けない部屋の番号
+pub fn unreachable_rooms(dungeon: &[Vec<Tile>], rooms: &[BSPNodeParams]) -> Vec<usize> {
+    let reachable = reachable_rooms(dungeon, rooms, 0);
+    (0..rooms.len()).filter(|idx| !reachable.contains(idx)).collect()
+}
+
+/// 離れた領域を通路でつないで、通れるマスをすべて1つながりにする関数
+///
+/// 最初の部屋の中心を含む領域を本体とし、ほかの領域はそれぞれ本体の最も近いマスと通路でつなぐ。
+/// 部屋がない場合は最も大きい領域を本体とする。
+///
+/// # Returns
+/// 掘った通路の数
+pub fn repair_connectivity<R: Rng>(dungeon: &mut [Vec<Tile>], rooms: &[BSPNodeParams], rng: &mut R) -> usize {
+    let mut regions = passable_regions(dungeon);
+    if regions.len() <= 1 {
+        return 0;
+    }
+    let main_idx = rooms.first()
+        .and_then(|room| {
+            let center = (room.room_center_x, room.room_center_y);
+            regions.iter().position(|region| region.contains(&center))
+        })
+        .or_else(|| (0..regions.len()).max_by_key(|idx| regions[*idx].len()))
+        .unwrap_or(0);
+    let mut main = regions.swap_remove(main_idx);
+
+    let mut corridors = 0;
+    for region in regions {
+        // 本体と最も近いマスの組を通路でつなぐ
+        let mut best = None;
+        let mut best_distance = i32::MAX;
+        for &from in &region {
+            for &to in &main {
+                let distance = (from.0 - to.0).abs() + (from.1 - to.1).abs();
+                if distance < best_distance {
+                    best_distance = distance;
+                    best = Some((from, to));
+                }
+            }
+        }
+        let Some((from, to)) = best else {
+            continue;
+        };
+        carve_corridor(dungeon, from, to, rng);
+        corridors += 1;
+        // つないだ領域は本体の一部として、次の領域からの距離の計算に使う
+        main.extend(region);
+    }
+    corridors
+}
+
+/// 生成を試す回数の上限
+pub const MAX_GENERATION_ATTEMPTS: usize = 10;
+
+/// すべての部屋がつながったマップを生成する関数
+///
+/// 生成したマップに離れた領域があれば通路を掘ってつなぎ、それでもつながらない部屋があるマップは作り直す。
+/// 上限まで試してもつながらない場合は、最初の部屋からたどり着ける部屋だけを残すので、
+/// どの部屋を初期位置やゴールに選んでもたどり着ける。
+pub fn generate_connected(
+    generator: &dyn MapGenerator,
+    width: i32,
+    height: i32,
+    rng: &mut StdRng
+) -> (Vec<Vec<Tile>>, Vec<BSPNodeParams>) {
+    let mut attempt = 1;
+    loop {
+        let (mut dungeon, rooms) = generator.generate(width, height, rng);
+        repair_connectivity(&mut dungeon, &rooms, rng);
+        let unreachable = unreachable_rooms(&dungeon, &rooms);
+        if (unreachable.is_empty() && !rooms.is_empty()) || attempt >= MAX_GENERATION_ATTEMPTS {
+            let rooms = rooms.into_iter()
+                .enumerate()
+                .filter(|(idx, _)| !unreachable.contains(idx))
+                .map(|(_, room)| room)
+                .collect();
+            return (dungeon, rooms);
+        }
+        attempt += 1;
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tile::parse_map;
   
     #[test]
     fn test_generate_bsp_tree() {
-        let tree = generate_bsp_tree(0, 0, 64, 64, Direction::None, 0);
+        let mut rng = StdRng::seed_from_u64(0);
+        let tree = generate_bsp_tree(0, 0, 64, 64, Direction::None, 0, &mut rng);
         fn check_tree_params(tree: &BSPTree) {
             match tree {
                 BSPTree::Node { value, left, right } => {
@@ -461,9 +895,9 @@ mod tests {
   
     #[test]
     fn test_generate_dungeon() {
-        let (dungeon, room_params) = generate_dungeon(64, 64);
+        let (dungeon, room_params, _) = generate_dungeon(64, 64, None);
         // dungeonの中身を確認
-        // dungeonのサイズは64x64で、壁は1、通路は0で表現されている
+        // dungeonのサイズは64x64で、壁と床と閉じた扉だけで表現されている
         assert_eq!(dungeon.len(), 64);
         for i in 0..64 {
             assert_eq!(dungeon[i].len(), 64);
@@ -471,7 +905,7 @@ mod tests {
         // dungeonの全要素を確認
         for i in 0..64 {
             for j in 0..64 {
-                assert!(dungeon[i][j] == 0 || dungeon[i][j] == 1);
+                assert!(matches!(dungeon[i][j], Tile::Floor | Tile::Wall | Tile::Door(DoorState::Closed)));
             }
         }
 
@@ -485,4 +919,186 @@ mod tests {
             assert_eq!(room.room_center_y, room.y + room.height / 2);
         }
     }
+
+    #[test]
+    fn test_generate_dungeon_with_same_seed() {
+        // 同じシード値からは同じマップと部屋が生成されること
+        let (dungeon_a, room_params_a, seed_a) = generate_dungeon(64, 64, Some(12345));
+        let (dungeon_b, room_params_b, seed_b) = generate_dungeon(64, 64, Some(12345));
+        assert_eq!(seed_a, 12345);
+        assert_eq!(seed_b, 12345);
+        assert_eq!(dungeon_a, dungeon_b);
+        assert_eq!(room_params_a, room_params_b);
+
+        // シード値を指定しなかった場合でも、返されたシード値で再現できること
+        let (dungeon_c, room_params_c, seed_c) = generate_dungeon(64, 64, None);
+        let (dungeon_d, room_params_d, _) = generate_dungeon(64, 64, Some(seed_c));
+        assert_eq!(dungeon_c, dungeon_d);
+        assert_eq!(room_params_c, room_params_d);
+    }
+
+    #[test]
+    fn test_place_doors() {
+        // 左右の部屋を1マス幅の通路でつないだマップ
+        let mut dungeon = parse_map(&[
+            "##########",
+            "#...##...#",
+            "#........#",
+            "#...##...#",
+            "##########",
+        ]);
+        // 部屋の入口になる通路のマスだけに扉を置き、扉を2つ続けては置かない
+        let doors = place_doors(&mut dungeon);
+        assert_eq!(doors, vec![(4, 2)]);
+        assert_eq!(dungeon[4][2], Tile::Door(DoorState::Closed));
+        assert_eq!(dungeon[5][2], Tile::Floor);
+
+        // 生成したマップの扉は両脇が壁で、前後は壁ではない
+        let mut door_count = 0;
+        for seed in 0..20 {
+            let (dungeon, _, _) = generate_dungeon(64, 64, Some(seed));
+            for x in 0..64 {
+                for y in 0..64 {
+                    if dungeon[x as usize][y as usize] != Tile::Door(DoorState::Closed) {
+                        continue;
+                    }
+                    let horizontal = tile_at(&dungeon, x, y - 1) == Tile::Wall && tile_at(&dungeon, x, y + 1) == Tile::Wall
+                        && tile_at(&dungeon, x - 1, y) != Tile::Wall && tile_at(&dungeon, x + 1, y) != Tile::Wall;
+                    let vertical = tile_at(&dungeon, x - 1, y) == Tile::Wall && tile_at(&dungeon, x + 1, y) == Tile::Wall
+                        && tile_at(&dungeon, x, y - 1) != Tile::Wall && tile_at(&dungeon, x, y + 1) != Tile::Wall;
+                    assert!(horizontal || vertical);
+                    // 扉が2つ続くことはない
+                    for (dx, dy) in [(1, 0), (0, 1), (-1, 0), (0, -1)] {
+                        assert!(!matches!(tile_at(&dungeon, x + dx, y + dy), Tile::Door(_)));
+                    }
+                    door_count += 1;
+                }
+            }
+        }
+        assert!(door_count > 0);
+    }
+
+    #[test]
+    fn test_generator_kind() {
+        for kind in GeneratorKind::ALL {
+            assert_eq!(GeneratorKind::from_key(kind.key()), Some(kind));
+        }
+        assert_eq!(GeneratorKind::from_key("unknown"), None);
+    }
+
+    #[test]
+    fn test_generate_connected() {
+        // どのアルゴリズムでも、部屋の中心は床で、最初の部屋からすべての部屋にたどり着ける
+        for kind in GeneratorKind::ALL {
+            let generator = kind.generator();
+            for seed in 0..5 {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let (dungeon, rooms) = generate_connected(generator.as_ref(), 64, 64, &mut rng);
+                assert!(rooms.len() >= 2, "{:?}", kind);
+                for room in &rooms {
+                    assert!(room.x >= 0 && room.y >= 0 && room.x + room.width <= 64 && room.y + room.height <= 64);
+                    assert_eq!(dungeon[room.room_center_x as usize][room.room_center_y as usize], Tile::Floor);
+                }
+                assert!(unreachable_rooms(&dungeon, &rooms).is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_unreachable_rooms() {
+        // 壁で仕切られた2つの部屋
+        let mut dungeon = vec![vec![Tile::Wall; 5]; 9];
+        for x in [1, 2, 3, 5, 6, 7] {
+            for tile in &mut dungeon[x][1..4] {
+                *tile = Tile::Floor;
+            }
+        }
+        let rooms = vec![
+            room_around(&dungeon, 1, 1, 3, 3).unwrap(),
+            room_around(&dungeon, 5, 1, 3, 3).unwrap(),
+        ];
+        assert_eq!(unreachable_rooms(&dungeon, &rooms), vec![1]);
+        assert_eq!(floor_regions(&dungeon).len(), 2);
+        // 通路を掘るとつながる
+        let mut rng = StdRng::seed_from_u64(0);
+        carve_corridor(&mut dungeon, (2, 2), (6, 2), &mut rng);
+        assert!(unreachable_rooms(&dungeon, &rooms).is_empty());
+        // 閉じた扉は通れるものとみなす
+        dungeon[4][2] = Tile::Door(DoorState::Closed);
+        assert!(unreachable_rooms(&dungeon, &rooms).is_empty());
+    }
+
+    #[test]
+    fn test_repair_connectivity() {
+        // 壁で仕切られた2つの部屋と、どちらにも属さない1マスの床
+        let mut dungeon = vec![vec![Tile::Wall; 7]; 9];
+        for x in [1, 2, 3, 5, 6, 7] {
+            for tile in &mut dungeon[x][1..4] {
+                *tile = Tile::Floor;
+            }
+        }
+        dungeon[2][5] = Tile::Floor;
+        let rooms = vec![
+            room_around(&dungeon, 1, 1, 3, 3).unwrap(),
+            room_around(&dungeon, 5, 1, 3, 3).unwrap(),
+        ];
+        assert_eq!(passable_regions(&dungeon).len(), 3);
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(repair_connectivity(&mut dungeon, &rooms, &mut rng), 2);
+        assert_eq!(passable_regions(&dungeon).len(), 1);
+        assert!(unreachable_rooms(&dungeon, &rooms).is_empty());
+        // つながっている場合は何もしない
+        assert_eq!(repair_connectivity(&mut dungeon, &rooms, &mut rng), 0);
+    }
+
+    #[test]
+    fn test_choose_reachable_start_and_goal_rooms() {
+        // 左の2部屋と右の1部屋が壁で仕切られている
+        let mut dungeon = vec![vec![Tile::Wall; 5]; 13];
+        for x in [1, 2, 3, 4, 5, 6, 7, 9, 10, 11] {
+            for tile in &mut dungeon[x][1..4] {
+                *tile = Tile::Floor;
+            }
+        }
+        let rooms = vec![
+            room_around(&dungeon, 1, 1, 3, 3).unwrap(),
+            room_around(&dungeon, 5, 1, 3, 3).unwrap(),
+            room_around(&dungeon, 9, 1, 3, 3).unwrap(),
+        ];
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let (start, goal) = choose_reachable_start_and_goal_rooms(&dungeon, &rooms, true, &mut rng).unwrap();
+            if start == 2 {
+                // ほかにたどり着ける部屋がない場合は同じ部屋になる
+                assert_eq!(goal, 2);
+            } else {
+                assert!(goal < 2 && goal != start);
+            }
+        }
+        assert_eq!(choose_reachable_start_and_goal_rooms(&dungeon, &[], true, &mut rng), None);
+    }
+
+    #[test]
+    fn test_floor_seed() {
+        // 階層が違えばシード値も変わること
+        assert_ne!(floor_seed(1, 1), floor_seed(1, 2));
+        assert_eq!(floor_seed(1, 3), floor_seed(1, 3));
+    }
+
+    #[test]
+    fn test_choose_start_and_goal_rooms() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut goal_rooms = [false; 4];
+        for _ in 0..200 {
+            let (start, goal) = choose_start_and_goal_rooms(4, true, &mut rng);
+            assert_ne!(start, goal);
+            assert!(start < 4 && goal < 4);
+            goal_rooms[goal] = true;
+        }
+        // 最後の部屋も含めてどの部屋も選ばれうる
+        assert!(goal_rooms.iter().all(|chosen| *chosen));
+
+        // 部屋が1つしかない場合は同じ部屋になる
+        assert_eq!(choose_start_and_goal_rooms(1, true, &mut rng), (0, 0));
+    }
 }
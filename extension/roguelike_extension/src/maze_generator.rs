@@ -0,0 +1,292 @@
+//! 部屋の間を迷路で埋めたマップを生成するモジュール
+//!
+//! 部屋を重ならないように置いてから、残りを迷路で埋める。
+//! 部屋と迷路の境目の壁を選んで開け、すべてをつないだ後で行き止まりを埋め戻す。
+//! 部屋も迷路も奇数の座標にそろえて置くので、間には必ず1マスの壁が残る。
+
+use crate::map_generator::{
+    place_doors,
+    BSPNodeParams,
+    Direction,
+    MapGenerator,
+};
+use crate::tile::tile_at;
+use crate::tile::Tile;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// 縦横の移動量
+const STEPS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+/// 部屋と迷路を組み合わせてマップを生成するアルゴリズム
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MazeGenerator {
+    /// 部屋を置こうとする回数、重なる場合は置かない
+    pub room_attempts: usize,
+    /// 部屋の一辺の最小値、奇数にそろえる
+    pub min_room_size: i32,
+    /// 部屋の一辺の最大値、奇数にそろえる
+    pub max_room_size: i32,
+    /// すでにつながっている部屋や迷路の間にも通り道を開ける確率
+    pub extra_connection_probability: f32,
+}
+
+impl Default for MazeGenerator {
+    fn default() -> Self {
+        Self {
+            room_attempts: 60,
+            min_room_size: 5,
+            max_room_size: 11,
+            extra_connection_probability: 0.05,
+        }
+    }
+}
+
+/// 迷路を掘りながら、どのマスがどの部屋や迷路に属するかを記録する
+struct Carver {
+    dungeon: Vec<Vec<Tile>>,
+    regions: Vec<Vec<Option<usize>>>,
+    region_count: usize,
+}
+
+impl Carver {
+    fn carve(&mut self, (x, y): (i32, i32), region: usize) {
+        self.dungeon[x as usize][y as usize] = Tile::Floor;
+        self.regions[x as usize][y as usize] = Some(region);
+    }
+
+    fn new_region(&mut self) -> usize {
+        self.region_count += 1;
+        self.region_count - 1
+    }
+
+    fn region(&self, x: i32, y: i32) -> Option<usize> {
+        if tile_at(&self.dungeon, x, y) != Tile::Floor {
+            return None;
+        }
+        self.regions[x as usize][y as usize]
+    }
+}
+
+/// 部屋や迷路がどれとつながったかを管理するUnion-Find
+struct RegionSet {
+    parents: Vec<usize>,
+}
+
+impl RegionSet {
+    fn find(&mut self, region: usize) -> usize {
+        if self.parents[region] != region {
+            let root = self.find(self.parents[region]);
+            self.parents[region] = root;
+        }
+        self.parents[region]
+    }
+
+    /// 2つをつなぐ
+    ///
+    /// # Returns
+    /// まだつながっていなかった場合はtrue
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parents[a] = b;
+        a != b
+    }
+}
+
+impl MazeGenerator {
+    /// 奇数にそろえた部屋の一辺の長さを選ぶ
+    fn room_size<R: Rng>(&self, rng: &mut R) -> i32 {
+        let min_half = self.min_room_size.max(1) / 2;
+        let max_half = (self.max_room_size / 2).max(min_half);
+        rng.gen_range(min_half..=max_half) * 2 + 1
+    }
+
+    /// 重ならないように部屋を置く
+    fn place_rooms<R: Rng>(&self, carver: &mut Carver, width: i32, height: i32, rng: &mut R) -> Vec<BSPNodeParams> {
+        let mut rooms: Vec<BSPNodeParams> = vec![];
+        for _ in 0..self.room_attempts {
+            let (room_width, room_height) = (self.room_size(rng), self.room_size(rng));
+            if room_width > width - 2 || room_height > height - 2 {
+                continue;
+            }
+            let x = rng.gen_range(0..=(width - 2 - room_width) / 2) * 2 + 1;
+            let y = rng.gen_range(0..=(height - 2 - room_height) / 2) * 2 + 1;
+            // 間に1マスの壁が残らない場合は置かない
+            let overlapped = rooms.iter().any(|room| {
+                x <= room.x + room.width && room.x <= x + room_width
+                    && y <= room.y + room.height && room.y <= y + room_height
+            });
+            if overlapped {
+                continue;
+            }
+            let region = carver.new_region();
+            for i in x..x + room_width {
+                for j in y..y + room_height {
+                    carver.carve((i, j), region);
+                }
+            }
+            rooms.push(BSPNodeParams {
+                x,
+                y,
+                width: room_width,
+                height: room_height,
+                room_center_x: x + room_width / 2,
+                room_center_y: y + room_height / 2,
+                connect_to: Direction::None,
+            });
+        }
+        rooms
+    }
+
+    /// 部屋の置かれていない場所を迷路で埋める
+    fn fill_maze<R: Rng>(carver: &mut Carver, width: i32, height: i32, rng: &mut R) {
+        let in_bounds = |x: i32, y: i32| x >= 1 && x <= width - 2 && y >= 1 && y <= height - 2;
+        for x in (1..width - 1).step_by(2) {
+            for y in (1..height - 1).step_by(2) {
+                if carver.dungeon[x as usize][y as usize] != Tile::Wall {
+                    continue;
+                }
+                // 行き止まりまで掘り進めては戻る、深さ優先の迷路
+                let region = carver.new_region();
+                carver.carve((x, y), region);
+                let mut stack = vec![(x, y)];
+                while let Some(&(cx, cy)) = stack.last() {
+                    let candidates: Vec<(i32, i32)> = STEPS.iter()
+                        .copied()
+                        .filter(|(dx, dy)| {
+                            let (nx, ny) = (cx + dx * 2, cy + dy * 2);
+                            in_bounds(nx, ny) && carver.dungeon[nx as usize][ny as usize] == Tile::Wall
+                        })
+                        .collect();
+                    let Some(&(dx, dy)) = candidates.choose(rng) else {
+                        stack.pop();
+                        continue;
+                    };
+                    carver.carve((cx + dx, cy + dy), region);
+                    carver.carve((cx + dx * 2, cy + dy * 2), region);
+                    stack.push((cx + dx * 2, cy + dy * 2));
+                }
+            }
+        }
+    }
+
+    /// 異なる部屋や迷路に挟まれた壁を開けて、すべてをつなぐ
+    fn connect_regions<R: Rng>(&self, carver: &mut Carver, width: i32, height: i32, rng: &mut R) {
+        let mut connectors = vec![];
+        for x in 1..width - 1 {
+            for y in 1..height - 1 {
+                if carver.dungeon[x as usize][y as usize] != Tile::Wall {
+                    continue;
+                }
+                for (dx, dy) in [(1, 0), (0, 1)] {
+                    let a = carver.region(x - dx, y - dy);
+                    let b = carver.region(x + dx, y + dy);
+                    if let (Some(a), Some(b)) = (a, b) {
+                        if a != b {
+                            connectors.push(((x, y), a, b));
+                        }
+                    }
+                }
+            }
+        }
+        connectors.shuffle(rng);
+
+        let mut regions = RegionSet { parents: (0..carver.region_count).collect() };
+        for (position, a, b) in connectors {
+            // つながっていなければ必ず開け、つながっていてもたまに開けて回り道を作る
+            if regions.union(a, b) || rng.gen::<f32>() < self.extra_connection_probability {
+                carver.carve(position, a);
+            }
+        }
+    }
+
+    /// 行き止まりを埋め戻す
+    fn remove_dead_ends(dungeon: &mut [Vec<Tile>]) {
+        let width = dungeon.len() as i32;
+        let height = dungeon[0].len() as i32;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for x in 1..width - 1 {
+                for y in 1..height - 1 {
+                    if dungeon[x as usize][y as usize] != Tile::Floor {
+                        continue;
+                    }
+                    let walls = STEPS.iter()
+                        .filter(|(dx, dy)| tile_at(dungeon, x + dx, y + dy) == Tile::Wall)
+                        .count();
+                    if walls >= 3 {
+                        dungeon[x as usize][y as usize] = Tile::Wall;
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl MapGenerator for MazeGenerator {
+    fn generate(&self, width: i32, height: i32, rng: &mut StdRng) -> (Vec<Vec<Tile>>, Vec<BSPNodeParams>) {
+        let mut carver = Carver {
+            dungeon: vec![vec![Tile::Wall; height as usize]; width as usize],
+            regions: vec![vec![None; height as usize]; width as usize],
+            region_count: 0,
+        };
+        let rooms = self.place_rooms(&mut carver, width, height, rng);
+        Self::fill_maze(&mut carver, width, height, rng);
+        self.connect_regions(&mut carver, width, height, rng);
+        let mut dungeon = carver.dungeon;
+        Self::remove_dead_ends(&mut dungeon);
+        // 通路と部屋の境目に扉を置く
+        place_doors(&mut dungeon);
+        (dungeon, rooms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_generator::unreachable_rooms;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_maze_generator() {
+        let generator = MazeGenerator::default();
+        for seed in 0..10 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (dungeon, rooms) = generator.generate(64, 64, &mut rng);
+            assert!(rooms.len() >= 2);
+            for (idx, room) in rooms.iter().enumerate() {
+                // 部屋は奇数の座標と大きさにそろっていて、ほかの部屋と重ならない
+                assert_eq!(room.x % 2, 1);
+                assert_eq!(room.width % 2, 1);
+                assert_eq!(dungeon[room.room_center_x as usize][room.room_center_y as usize], Tile::Floor);
+                for other in &rooms[idx + 1..] {
+                    assert!(room.x + room.width < other.x || other.x + other.width < room.x
+                        || room.y + room.height < other.y || other.y + other.height < room.y);
+                }
+            }
+            assert!(unreachable_rooms(&dungeon, &rooms).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_remove_dead_ends() {
+        let generator = MazeGenerator::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let (dungeon, _) = generator.generate(41, 31, &mut rng);
+        // 床のマスはどれも2方向以上に進める
+        for x in 1..40 {
+            for y in 1..30 {
+                if dungeon[x as usize][y as usize] == Tile::Floor {
+                    let walls = STEPS.iter()
+                        .filter(|(dx, dy)| tile_at(&dungeon, x + dx, y + dy) == Tile::Wall)
+                        .count();
+                    assert!(walls <= 2);
+                }
+            }
+        }
+    }
+}
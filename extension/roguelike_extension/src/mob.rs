@@ -1,17 +1,53 @@
 //! 敵のステータスを管理するモジュール
 
 use crate::player::Direction;
+use crate::catalog::MobSpecies;
+use crate::ai::MobBehavior;
+use crate::ai::MobState;
+use crate::scheduler::Speed;
+use crate::attack_pattern::AttackPattern;
+use crate::status_effect::StatusEffect;
+use crate::status_effect::StatusEffects;
+
+use serde::{Deserialize, Serialize};
 
 /// 敵のステータス
-// TODO: 敵の種類を増やしたいときはどうするかを決める
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GameMob {
     pub id: i32,
     pub position: (i32, i32),
     pub direction: Direction,
     pub hp: i32,
+    /// 最大HP、古いセーブデータでは読み込んだ時点のHPになる
+    #[serde(default)]
+    pub max_hp: i32,
     pub attack: i32,
     pub defense: i32,
     pub exp_point: i32,
+    /// 敵の種類のキー、カタログを使わずに生成した場合は空
+    #[serde(default)]
+    pub species: String,
+    /// 思考ルーチンの種類
+    #[serde(default)]
+    pub behavior: MobBehavior,
+    /// 思考ルーチンの状態
+    #[serde(default)]
+    pub ai_state: MobState,
+    /// 素早さ
+    #[serde(default)]
+    pub speed: Speed,
+    /// 遠距離攻撃の射程、0の場合は隣接した相手にしか攻撃しない
+    #[serde(default)]
+    pub range: i32,
+    /// 近接攻撃の範囲
+    #[serde(default)]
+    pub attack_pattern: AttackPattern,
+    /// 攻撃が当たった相手にかける状態異常
+    #[serde(default)]
+    pub on_hit: Option<StatusEffect>,
+    /// かかっている状態異常
+    #[serde(default)]
+    pub status_effects: StatusEffects,
 }
 
 impl GameMob {
@@ -29,6 +65,21 @@ impl GameMob {
         Self::new_from_status(id, x, y, hp, attack, defense, exp_point)
     }
 
+    /// カタログの定義と現在階層から新しいインスタンスを生成する
+    pub fn new_from_species(id: i32, x: i32, y: i32, species: &MobSpecies, level: i32) -> Self {
+        let stats = species.stats_at(level);
+        let mut mob = Self::new_from_status(
+            id, x, y, stats.hp, stats.attack, stats.defense, stats.exp_point);
+        mob.species = species.key.clone();
+        mob.behavior = species.behavior;
+        mob.ai_state = species.behavior.initial_state();
+        mob.speed = Speed::new(species.speed);
+        mob.range = species.range;
+        mob.attack_pattern = species.attack_pattern;
+        mob.on_hit = species.on_hit;
+        mob
+    }
+
     /// ステータスを指定して新しいインスタンスを生成する
     pub fn new_from_status(id: i32, x: i32, y: i32, hp: i32, attack: i32, defense: i32, exp_point: i32) -> Self {
         Self {
@@ -36,41 +87,24 @@ impl GameMob {
             position: (x, y),
             direction: Direction::Up,
             hp: hp,
+            max_hp: hp,
             attack: attack,
             defense: defense,
             exp_point: exp_point,
+            species: String::new(),
+            behavior: MobBehavior::default(),
+            ai_state: MobState::default(),
+            speed: Speed::default(),
+            range: 0,
+            attack_pattern: AttackPattern::Single,
+            on_hit: None,
+            status_effects: StatusEffects::default(),
         }
     }
 
-    /// 攻撃を行う
+    /// 攻撃を行う、攻撃の届くマスは種類ごとの攻撃範囲で決まる
     pub fn attack(&self, result: &mut Vec<(i32, i32, i32)>) {
-        let (mut x, mut y) = self.position;
-        let mut damage = self.attack;
-
-        // Directionに応じて座標を変更
-        match self.direction {
-            Direction::Up => y -= 1,
-            Direction::UpRight => {
-                x += 1;
-                y -= 1;
-            }
-            Direction::Right => x += 1,
-            Direction::DownRight => {
-                x += 1;
-                y += 1;
-            }
-            Direction::Down => y += 1,
-            Direction::DownLeft => {
-                x -= 1;
-                y += 1;
-            }
-            Direction::Left => x -= 1,
-            Direction::UpLeft => {
-                x -= 1;
-                y -= 1;
-            }
-        }
-        result.push((x, y, damage));
+        self.attack_pattern.attack_info(self.position, self.direction, self.attack, result);
     }
 }
 
@@ -90,6 +124,28 @@ mod tests {
         assert_eq!(mob.exp_point, 1);
     }
 
+    #[test]
+    fn test_new_from_species() {
+        let catalog = crate::catalog::MobCatalog::default_catalog();
+        let species = catalog.get("mob").unwrap();
+        let mob = GameMob::new_from_species(2, 3, 4, species, 3);
+        let stats = species.stats_at(3);
+        assert_eq!(mob.id, 2);
+        assert_eq!(mob.position, (3, 4));
+        assert_eq!(mob.species, "mob");
+        assert_eq!(mob.hp, stats.hp);
+        assert_eq!(mob.attack, stats.attack);
+        assert_eq!(mob.defense, stats.defense);
+        assert_eq!(mob.exp_point, stats.exp_point);
+        assert_eq!(mob.max_hp, stats.hp);
+        assert_eq!(mob.behavior, species.behavior);
+        assert_eq!(mob.ai_state, species.behavior.initial_state());
+        assert_eq!(mob.speed.base, species.speed);
+        assert_eq!(mob.range, species.range);
+        assert_eq!(mob.attack_pattern, species.attack_pattern);
+        assert_eq!(mob.on_hit, species.on_hit);
+    }
+
     #[test]
     fn test_attack() {
         let mut mob = GameMob::new(1, 10, 20);
@@ -97,5 +153,11 @@ mod tests {
         mob.attack(&mut result);
         assert_eq!(result.len(), 1);
         assert_eq!(result[0], (10, 19, 5));
+
+        // なぎ払う敵は正面と斜め前の3マスに攻撃する
+        mob.attack_pattern = AttackPattern::Sweep;
+        let mut result = vec![];
+        mob.attack(&mut result);
+        assert_eq!(result, vec![(9, 19, 5), (10, 19, 5), (11, 19, 5)]);
     }
 }
\ No newline at end of file
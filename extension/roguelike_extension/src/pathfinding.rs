@@ -0,0 +1,385 @@
+//! マップ上の経路探索を行うモジュール
+//!
+//! 1組の始点と終点の経路を求めるA*と、
+//! 目標地点からの距離をマップ全体について求めるDijkstraマップを提供する。
+//! どちらも8方向の移動を扱い、斜め移動で壁の角をすり抜けてよいかは`DiagonalRule`で決める。
+
+use crate::tile::tile_at;
+use crate::tile::DoorState;
+use crate::tile::Tile;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// 8方向の移動量
+const NEIGHBORS: [(i32, i32); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+/// 斜め移動の可否を決めるルール
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagonalRule {
+    /// 斜め移動しない
+    Never,
+    /// 斜め移動の両脇がどちらも床の場合だけ斜め移動できる
+    NoCornerCutting,
+    /// 斜め移動の両脇のどちらかが床なら斜め移動できる
+    AllowOneCorner,
+    /// 両脇が壁でも斜め移動できる
+    Always,
+}
+
+impl DiagonalRule {
+    /// Godot側から指定される整数値から変換する、範囲外の値はNoCornerCuttingとする
+    pub fn from_i32(value: i32) -> Self {
+        match value {
+            0 => DiagonalRule::Never,
+            1 => DiagonalRule::NoCornerCutting,
+            2 => DiagonalRule::AllowOneCorner,
+            3 => DiagonalRule::Always,
+            _ => DiagonalRule::NoCornerCutting,
+        }
+    }
+}
+
+/// 経路探索の設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathFinder {
+    /// 斜め移動のルール
+    pub diagonal_rule: DiagonalRule,
+    /// 縦横に1マス移動するコスト
+    pub straight_cost: u32,
+    /// 斜めに1マス移動するコスト
+    pub diagonal_cost: u32,
+}
+
+impl Default for PathFinder {
+    fn default() -> Self {
+        Self {
+            diagonal_rule: DiagonalRule::NoCornerCutting,
+            straight_cost: 10,
+            diagonal_cost: 14,
+        }
+    }
+}
+
+/// 目標地点からの距離をマップ全体について求めたもの
+///
+/// 各マスから距離が小さくなる方向へ進むと最短で目標地点にたどり着く。
+#[derive(Debug, Clone, PartialEq)]
+pub struct DijkstraMap {
+    /// 距離を求めたときの設定
+    pub finder: PathFinder,
+    /// 目標地点からの距離、到達できないマスはNone、[x][y]の順で参照する
+    pub distances: Vec<Vec<Option<u32>>>,
+}
+
+impl DijkstraMap {
+    /// 指定したマスの目標地点からの距離、到達できない場合やマップ外はNone
+    pub fn distance(&self, x: i32, y: i32) -> Option<u32> {
+        if x < 0 || y < 0 {
+            return None;
+        }
+        self.distances.get(x as usize)?.get(y as usize).copied().flatten()
+    }
+
+    /// 指定したマスから目標地点に近づくための次のマス
+    ///
+    /// すでに目標地点にいる場合や、それ以上近づけない場合はNoneを返す。
+    pub fn next_step(&self, map: &[Vec<Tile>], from: (i32, i32)) -> Option<(i32, i32)> {
+        let mut best = (from, self.distance(from.0, from.1)?);
+        for next in self.finder.neighbors(map, from) {
+            if let Some(distance) = self.distance(next.0, next.1) {
+                if distance < best.1 {
+                    best = (next, distance);
+                }
+            }
+        }
+        if best.0 == from {
+            None
+        } else {
+            Some(best.0)
+        }
+    }
+}
+
+impl PathFinder {
+    /// 指定したマスが通行可能かどうか、歩いて入れてダメージを受けない地形のみ通行できる
+    ///
+    /// 閉じた扉は開ければ通れるので通行可能とみなす。鍵のかかった扉は通れない。
+    pub fn is_passable(map: &[Vec<Tile>], x: i32, y: i32) -> bool {
+        let tile = tile_at(map, x, y);
+        tile.is_safe() || tile == Tile::Door(DoorState::Closed)
+    }
+
+    /// 指定したマスから1歩で移動できるマスの一覧
+    pub fn neighbors(&self, map: &[Vec<Tile>], (x, y): (i32, i32)) -> Vec<(i32, i32)> {
+        let mut result = vec![];
+        for (dx, dy) in NEIGHBORS {
+            let (nx, ny) = (x + dx, y + dy);
+            if !Self::is_passable(map, nx, ny) {
+                continue;
+            }
+            if dx != 0 && dy != 0 {
+                let side_a = Self::is_passable(map, x + dx, y);
+                let side_b = Self::is_passable(map, x, y + dy);
+                let allowed = match self.diagonal_rule {
+                    DiagonalRule::Never => false,
+                    DiagonalRule::NoCornerCutting => side_a && side_b,
+                    DiagonalRule::AllowOneCorner => side_a || side_b,
+                    DiagonalRule::Always => true,
+                };
+                if !allowed {
+                    continue;
+                }
+            }
+            result.push((nx, ny));
+        }
+        result
+    }
+
+    /// 隣接するマスへの移動コスト
+    fn step_cost(&self, from: (i32, i32), to: (i32, i32)) -> u32 {
+        if from.0 != to.0 && from.1 != to.1 {
+            self.diagonal_cost
+        } else {
+            self.straight_cost
+        }
+    }
+
+    /// 残りのコストの見積もり、実際のコストを超えないようにする
+    fn heuristic(&self, from: (i32, i32), to: (i32, i32)) -> u32 {
+        let dx = (from.0 - to.0).unsigned_abs();
+        let dy = (from.1 - to.1).unsigned_abs();
+        let (long, short) = if dx > dy { (dx, dy) } else { (dy, dx) };
+        if self.diagonal_rule == DiagonalRule::Never {
+            return self.straight_cost * (long + short);
+        }
+        let diagonal_cost = self.diagonal_cost.min(self.straight_cost * 2);
+        self.straight_cost * (long - short) + diagonal_cost * short
+    }
+
+    /// A*で始点から終点までの経路を求める
+    ///
+    /// # Returns
+    /// 始点を含まず終点を含む経路、始点と終点が同じ場合は空、到達できない場合はNone
+    pub fn find_path(
+        &self,
+        map: &[Vec<Tile>],
+        start: (i32, i32),
+        goal: (i32, i32),
+    ) -> Option<Vec<(i32, i32)>> {
+        if !Self::is_passable(map, start.0, start.1) || !Self::is_passable(map, goal.0, goal.1) {
+            return None;
+        }
+        if start == goal {
+            return Some(vec![]);
+        }
+        let width = map.len();
+        let height = map[0].len();
+        let index = |(x, y): (i32, i32)| x as usize * height + y as usize;
+
+        let mut costs: Vec<Option<u32>> = vec![None; width * height];
+        let mut came_from: Vec<Option<(i32, i32)>> = vec![None; width * height];
+        let mut open = BinaryHeap::new();
+        costs[index(start)] = Some(0);
+        open.push(Reverse((self.heuristic(start, goal), 0, start)));
+
+        while let Some(Reverse((_, cost, current))) = open.pop() {
+            if current == goal {
+                let mut path = vec![goal];
+                let mut position = goal;
+                while let Some(previous) = came_from[index(position)] {
+                    if previous == start {
+                        break;
+                    }
+                    path.push(previous);
+                    position = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            // すでにより短い経路で訪れている場合は無視する
+            if matches!(costs[index(current)], Some(best) if cost > best) {
+                continue;
+            }
+            for next in self.neighbors(map, current) {
+                let next_cost = cost + self.step_cost(current, next);
+                if !matches!(costs[index(next)], Some(best) if next_cost >= best) {
+                    costs[index(next)] = Some(next_cost);
+                    came_from[index(next)] = Some(current);
+                    open.push(Reverse((next_cost + self.heuristic(next, goal), next_cost, next)));
+                }
+            }
+        }
+        None
+    }
+
+    /// 目標地点からの距離をマップ全体について求める
+    ///
+    /// 目標地点が複数ある場合は、最も近い目標地点までの距離になる。
+    pub fn dijkstra_map(&self, map: &[Vec<Tile>], goals: &[(i32, i32)]) -> DijkstraMap {
+        let mut distances: Vec<Vec<Option<u32>>> =
+            map.iter().map(|column| vec![None; column.len()]).collect();
+        let mut open = BinaryHeap::new();
+        for &(x, y) in goals {
+            if Self::is_passable(map, x, y) {
+                distances[x as usize][y as usize] = Some(0);
+                open.push(Reverse((0, (x, y))));
+            }
+        }
+
+        while let Some(Reverse((cost, current))) = open.pop() {
+            if matches!(distances[current.0 as usize][current.1 as usize], Some(best) if cost > best) {
+                continue;
+            }
+            // 斜め移動のルールは対称なので、目標地点から逆向きにたどってもよい
+            for next in self.neighbors(map, current) {
+                let next_cost = cost + self.step_cost(current, next);
+                let distance = &mut distances[next.0 as usize][next.1 as usize];
+                if !matches!(*distance, Some(best) if next_cost >= best) {
+                    *distance = Some(next_cost);
+                    open.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+        DijkstraMap {
+            finder: *self,
+            distances,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::parse_map;
+
+    #[test]
+    fn test_find_path_straight() {
+        let map = parse_map(&[
+            "#######",
+            "#.....#",
+            "#######",
+        ]);
+        let finder = PathFinder::default();
+        let path = finder.find_path(&map, (1, 1), (5, 1)).unwrap();
+        assert_eq!(path, vec![(2, 1), (3, 1), (4, 1), (5, 1)]);
+        assert_eq!(finder.find_path(&map, (1, 1), (1, 1)), Some(vec![]));
+    }
+
+    #[test]
+    fn test_find_path_around_wall() {
+        // 壁を回り込む経路が見つかること
+        let map = parse_map(&[
+            "#######",
+            "#..#..#",
+            "#..#..#",
+            "#.....#",
+            "#######",
+        ]);
+        let finder = PathFinder::default();
+        let path = finder.find_path(&map, (1, 1), (5, 1)).unwrap();
+        assert_eq!(*path.last().unwrap(), (5, 1));
+        for pair in path.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            assert!((a.0 - b.0).abs() <= 1 && (a.1 - b.1).abs() <= 1);
+            assert!(PathFinder::is_passable(&map, b.0, b.1));
+        }
+    }
+
+    #[test]
+    fn test_no_path() {
+        let map = parse_map(&[
+            "#####",
+            "#.#.#",
+            "#####",
+        ]);
+        let finder = PathFinder::default();
+        assert_eq!(finder.find_path(&map, (1, 1), (3, 1)), None);
+        assert_eq!(finder.find_path(&map, (1, 1), (2, 1)), None);
+    }
+
+    #[test]
+    fn test_path_through_door() {
+        // 閉じた扉は開けて通れるが、鍵のかかった扉は通れない
+        let map = parse_map(&[
+            "#######",
+            "#..+..#",
+            "#######",
+        ]);
+        let finder = PathFinder::default();
+        assert_eq!(finder.find_path(&map, (1, 1), (5, 1)).unwrap().len(), 4);
+        let map = parse_map(&[
+            "#######",
+            "#..L..#",
+            "#######",
+        ]);
+        assert_eq!(finder.find_path(&map, (1, 1), (5, 1)), None);
+    }
+
+    #[test]
+    fn test_diagonal_rule() {
+        // (1, 2)から(2, 1)へは壁の角をすり抜ける斜め移動が必要
+        let map = parse_map(&[
+            "####",
+            "##.#",
+            "#..#",
+            "####",
+        ]);
+        let mut finder = PathFinder {
+            diagonal_rule: DiagonalRule::Never,
+            ..Default::default()
+        };
+        assert_eq!(finder.find_path(&map, (1, 2), (2, 1)), Some(vec![(2, 2), (2, 1)]));
+        finder.diagonal_rule = DiagonalRule::NoCornerCutting;
+        assert_eq!(finder.find_path(&map, (1, 2), (2, 1)), Some(vec![(2, 2), (2, 1)]));
+        finder.diagonal_rule = DiagonalRule::AllowOneCorner;
+        assert_eq!(finder.find_path(&map, (1, 2), (2, 1)), Some(vec![(2, 1)]));
+
+        // 両脇が壁の場合はAlwaysのときだけ通れる
+        let map = parse_map(&[
+            "####",
+            "##.#",
+            "#.##",
+            "####",
+        ]);
+        finder.diagonal_rule = DiagonalRule::AllowOneCorner;
+        assert_eq!(finder.find_path(&map, (1, 2), (2, 1)), None);
+        finder.diagonal_rule = DiagonalRule::Always;
+        assert_eq!(finder.find_path(&map, (1, 2), (2, 1)), Some(vec![(2, 1)]));
+    }
+
+    #[test]
+    fn test_dijkstra_map() {
+        let map = parse_map(&[
+            "#######",
+            "#..#..#",
+            "#..#..#",
+            "#.....#",
+            "#######",
+        ]);
+        let finder = PathFinder::default();
+        let dijkstra = finder.dijkstra_map(&map, &[(5, 1)]);
+        assert_eq!(dijkstra.distance(5, 1), Some(0));
+        assert_eq!(dijkstra.distance(3, 1), None);
+        assert_eq!(dijkstra.distance(-1, 0), None);
+
+        // 次のマスをたどるとA*と同じコストで目標地点にたどり着く
+        let mut position = (1, 1);
+        let mut steps = 0;
+        while let Some(next) = dijkstra.next_step(&map, position) {
+            position = next;
+            steps += 1;
+        }
+        assert_eq!(position, (5, 1));
+        assert_eq!(steps, finder.find_path(&map, (1, 1), (5, 1)).unwrap().len());
+    }
+}
@@ -2,12 +2,19 @@
 
 use crate::item::GameItem;
 use crate::item::SideEffect;
+use crate::item::EquipSlot;
+use crate::item::Sword;
+use crate::item::Shield;
+use crate::scheduler::Speed;
+use crate::attack_pattern::AttackPattern;
+use crate::status_effect::StatusEffects;
 
+use serde::{Deserialize, Serialize};
 use std::rc::Rc;
 use std::cell::RefCell;
 
 /// プレイヤーの向きを定義する列挙型
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     UpRight,
@@ -19,11 +26,57 @@ pub enum Direction {
     UpLeft,
 }
 
+impl Direction {
+    /// 移動量から向きを求める、移動していない場合はNone
+    pub fn from_delta(dx: i32, dy: i32) -> Option<Direction> {
+        match (dx.signum(), dy.signum()) {
+            (0, -1) => Some(Direction::Up),
+            (1, -1) => Some(Direction::UpRight),
+            (1, 0) => Some(Direction::Right),
+            (1, 1) => Some(Direction::DownRight),
+            (0, 1) => Some(Direction::Down),
+            (-1, 1) => Some(Direction::DownLeft),
+            (-1, 0) => Some(Direction::Left),
+            (-1, -1) => Some(Direction::UpLeft),
+            _ => None,
+        }
+    }
+
+    /// 向きに1マス進んだときの移動量
+    pub fn delta(&self) -> (i32, i32) {
+        match self {
+            Direction::Up => (0, -1),
+            Direction::UpRight => (1, -1),
+            Direction::Right => (1, 0),
+            Direction::DownRight => (1, 1),
+            Direction::Down => (0, 1),
+            Direction::DownLeft => (-1, 1),
+            Direction::Left => (-1, 0),
+            Direction::UpLeft => (-1, -1),
+        }
+    }
+
+    /// 時計回りにsteps回だけ45度回した向き、負の場合は反時計回り
+    pub fn rotated(&self, steps: i32) -> Direction {
+        const ORDER: [Direction; 8] = [
+            Direction::Up,
+            Direction::UpRight,
+            Direction::Right,
+            Direction::DownRight,
+            Direction::Down,
+            Direction::DownLeft,
+            Direction::Left,
+            Direction::UpLeft,
+        ];
+        let index = ORDER.iter().position(|direction| direction == self).unwrap() as i32;
+        ORDER[(index + steps).rem_euclid(8) as usize]
+    }
+}
+
 
 /// プレイヤーのステータス
 // TODO: 経験値とレベルの概念を追加する
-// TODO: 装備品の概念を追加する
-//  item.rsのSwrod, Shieldを保持できるようにする。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GamePlayer {
     pub position: (i32, i32),
     pub direction: Direction,
@@ -36,6 +89,35 @@ pub struct GamePlayer {
     pub exp_point: i32,
     pub is_heal_when_level_up: bool,
     pub level: i32,
+    /// 装備中の武器
+    #[serde(default)]
+    pub weapon: Option<Sword>,
+    /// 装備中の防具
+    #[serde(default)]
+    pub armor: Option<Shield>,
+    /// 素早さ
+    #[serde(default)]
+    pub speed: Speed,
+    /// かかっている状態異常
+    #[serde(default)]
+    pub status_effects: StatusEffects,
+    /// 満腹度、0になると毎ターンダメージを受ける
+    #[serde(default = "default_satiety")]
+    pub satiety: f32,
+    /// 満腹度の上限
+    #[serde(default = "default_satiety")]
+    pub max_satiety: f32,
+}
+
+/// 満腹度の上限の初期値
+pub const DEFAULT_MAX_SATIETY: f32 = 100.0;
+/// 空腹を知らせる満腹度
+pub const HUNGRY_SATIETY: f32 = 20.0;
+/// レベルアップで増える満腹度の上限
+const MAX_SATIETY_PER_LEVEL: f32 = 5.0;
+
+fn default_satiety() -> f32 {
+    DEFAULT_MAX_SATIETY
 }
 
 impl GamePlayer {
@@ -53,6 +135,12 @@ impl GamePlayer {
             exp_point: 0,
             is_heal_when_level_up: false,
             level: 1,
+            weapon: None,
+            armor: None,
+            speed: Speed::default(),
+            status_effects: StatusEffects::default(),
+            satiety: DEFAULT_MAX_SATIETY,
+            max_satiety: DEFAULT_MAX_SATIETY,
         };
         obj.init_items(8);
         obj
@@ -107,7 +195,8 @@ impl GamePlayer {
         if self.active_item_index < self.items.len() {
             let mut item_used = false;
             // アイテムの種類によって処理を変える
-            match *self.items[self.active_item_index].borrow() {
+            let item = *self.items[self.active_item_index].borrow();
+            match item {
                 GameItem::HealthPotion(potion) => {
                     self.hp += potion.heal_amount;
                     if self.hp > self.max_hp {
@@ -116,6 +205,21 @@ impl GamePlayer {
                     result = SideEffect::None;
                     item_used = true;
                 }
+                GameItem::Food(food) => {
+                    self.eat(food.nutrition);
+                    result = SideEffect::None;
+                    item_used = true;
+                }
+                GameItem::Elixir(elixir) => {
+                    self.status_effects.add(elixir.effect);
+                    result = SideEffect::None;
+                    item_used = true;
+                }
+                // 装備品は使うと装備する、スロットには外した装備品が入る
+                GameItem::Sword(_) | GameItem::Shield(_) => {
+                    let equipped = self.equip(self.active_item_index);
+                    result = if equipped { SideEffect::None } else { SideEffect::Fault };
+                }
                 _ => {}
             }
             if item_used {
@@ -125,42 +229,126 @@ impl GamePlayer {
         result
     }
 
+    /// アイテムスロットの装備品を装備する
+    ///
+    /// すでに同じ箇所に装備している場合は、外した装備品をそのスロットに戻す。
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - 装備するアイテムのスロット番号
+    ///
+    /// # Returns
+    ///
+    /// * 装備できた場合はtrue、装備品でない場合はfalse
+    pub fn equip(&mut self, index: usize) -> bool {
+        if index >= self.items.len() {
+            return false;
+        }
+        let item = *self.items[index].borrow();
+        let unequipped = match item {
+            GameItem::Sword(sword) => self.weapon.replace(sword).map(GameItem::Sword),
+            GameItem::Shield(shield) => self.armor.replace(shield).map(GameItem::Shield),
+            _ => return false,
+        };
+        self.items[index] = RefCell::new(unequipped.unwrap_or(GameItem::Null));
+        true
+    }
+
+    /// 装備を外してアイテムスロットに戻す
+    ///
+    /// # Arguments
+    ///
+    /// * `slot` - 外す装備の箇所
+    ///
+    /// # Returns
+    ///
+    /// * 外せた場合はtrue、何も装備していないか持ち物がいっぱいの場合はfalse
+    pub fn unequip(&mut self, slot: EquipSlot) -> bool {
+        let item = match slot {
+            EquipSlot::Weapon => self.weapon.map(GameItem::Sword),
+            EquipSlot::Armor => self.armor.map(GameItem::Shield),
+        };
+        let Some(item) = item else {
+            return false;
+        };
+        if !self.add_item(&RefCell::new(item)) {
+            return false;
+        }
+        match slot {
+            EquipSlot::Weapon => self.weapon = None,
+            EquipSlot::Armor => self.armor = None,
+        }
+        true
+    }
+
+    /// 装備品の補正を含めた攻撃力
+    pub fn total_attack(&self) -> i32 {
+        self.attack + self.weapon.map_or(0, |sword| sword.attack_bonus)
+    }
+
+    /// 装備品の補正を含めた防御力
+    pub fn total_defense(&self) -> i32 {
+        self.defense + self.armor.map_or(0, |shield| shield.defense_bonus)
+    }
+
+    /// ダメージを受ける
+    ///
+    /// 防御力による軽減はcombatモジュールのダメージ計算で行うので、
+    /// ここでは受け取ったダメージをそのままHPから引く。
+    ///
+    /// # Returns
+    ///
+    /// * 実際に受けたダメージ
+    pub fn take_damage(&mut self, damage: i32) -> i32 {
+        let damage = damage.max(0);
+        self.hp -= damage;
+        damage
+    }
+
     /// 攻撃を行った場合、ダメージとそれを与える座標をセットにして、リストで返す。
+    /// 攻撃の届くマスは装備している武器の攻撃範囲で決まる。
     pub fn attack(&self, result: &mut Vec<(i32, i32, i32)>) {
-        let (mut x, mut y) = self.position;
-        let mut damage = self.attack;
-        if self.active_item_index < self.items.len() {
-            match *self.items[self.active_item_index].borrow() {
-                GameItem::Sword(sword) => {
-                    damage += sword.attack_bonus;
+        let pattern = self.weapon.map_or(AttackPattern::default(), |sword| sword.pattern);
+        pattern.attack_info(self.position, self.direction, self.total_attack(), result);
+    }
+
+    /// 食べて満腹度を回復する、上限を超えた分は捨てる
+    pub fn eat(&mut self, nutrition: i32) {
+        self.satiety = (self.satiety + nutrition as f32).min(self.max_satiety);
+    }
+
+    /// 時間の経過で満腹度を減らす
+    ///
+    /// # Returns
+    ///
+    /// * 満腹度が0になっていればtrue
+    pub fn digest(&mut self, amount: f32) -> bool {
+        self.satiety = (self.satiety - amount).max(0.0);
+        self.satiety <= 0.0
+    }
+
+    /// 持っている鍵を1回分使う、使い切った鍵は持ち物から取り除く
+    ///
+    /// # Returns
+    ///
+    /// * 鍵を持っていて使えた場合はtrue
+    pub fn use_key(&mut self) -> bool {
+        for slot in &self.items {
+            let mut item = slot.borrow_mut();
+            if let GameItem::Key(key) = &mut *item {
+                key.uses -= 1;
+                if key.uses <= 0 {
+                    *item = GameItem::Null;
                 }
-                _ => {}
-            }
-        }
-        // Directionに応じて座標を変更
-        match self.direction {
-            Direction::Up => y -= 1,
-            Direction::UpRight => {
-                x += 1;
-                y -= 1;
-            }
-            Direction::Right => x += 1,
-            Direction::DownRight => {
-                x += 1;
-                y += 1;
-            }
-            Direction::Down => y += 1,
-            Direction::DownLeft => {
-                x -= 1;
-                y += 1;
-            }
-            Direction::Left => x -= 1,
-            Direction::UpLeft => {
-                x -= 1;
-                y -= 1;
+                return true;
             }
         }
-        result.push((x, y, damage));
+        false
+    }
+
+    /// 表示用の満腹度、切り上げて整数にする
+    pub fn satiety_display(&self) -> i32 {
+        self.satiety.ceil() as i32
     }
 
     /// レベルアップ判定を行う
@@ -176,6 +364,7 @@ impl GamePlayer {
             }
             self.attack += 2;
             self.defense += 1;
+            self.max_satiety += MAX_SATIETY_PER_LEVEL;
         }
         is_level_up
     }
@@ -186,6 +375,12 @@ impl GamePlayer {
 mod tests {
     use super::*;
     use crate::item::HealthPotion;
+    use crate::item::Food;
+    use crate::item::Key;
+    use crate::item::Elixir;
+    use crate::scheduler::SpeedEffect;
+    use crate::status_effect::StatusEffect;
+    use crate::status_effect::StatusKind;
 
     #[test]
     fn test_new() {
@@ -269,6 +464,53 @@ mod tests {
         player.attack(&mut result);
         assert_eq!(result.len(), 1);
         assert_eq!(result[0], (5, 4, 10));
+
+        // 武器の攻撃範囲のマスすべてに攻撃する
+        player.weapon = Some(Sword { attack_bonus: 2, pattern: AttackPattern::Pierce(2) });
+        let mut result = vec![];
+        player.attack(&mut result);
+        assert_eq!(result, vec![(5, 4, 12), (5, 3, 12)]);
+    }
+
+    #[test]
+    fn test_satiety() {
+        let mut player = GamePlayer::new();
+        assert_eq!(player.satiety, DEFAULT_MAX_SATIETY);
+        assert!(!player.digest(30.5));
+        assert_eq!(player.satiety_display(), 70);
+        // 食べても上限を超えない
+        player.add_item(&RefCell::new(GameItem::Food(Food { nutrition: 50 })));
+        player.select_item(0);
+        assert_eq!(player.use_item(), SideEffect::None);
+        assert_eq!(*player.items[0].borrow(), GameItem::Null);
+        assert_eq!(player.satiety, DEFAULT_MAX_SATIETY);
+        // 0より下には減らない
+        assert!(player.digest(1000.0));
+        assert_eq!(player.satiety, 0.0);
+    }
+
+    #[test]
+    fn test_use_elixir() {
+        // 加速の薬を飲むと素早さが2倍になる
+        let mut player = GamePlayer::new();
+        player.add_item(&RefCell::new(GameItem::Elixir(Elixir { effect: StatusEffect::new(StatusKind::Haste, 10, 0) })));
+        player.select_item(0);
+        assert_eq!(player.use_item(), SideEffect::None);
+        assert_eq!(*player.items[0].borrow(), GameItem::Null);
+        assert_eq!(player.status_effects.speed_effect(), Some(SpeedEffect::Haste));
+    }
+
+    #[test]
+    fn test_use_key() {
+        let mut player = GamePlayer::new();
+        assert!(!player.use_key());
+        player.add_item(&RefCell::new(GameItem::Key(Key { uses: 2 })));
+        assert!(player.use_key());
+        assert_eq!(*player.items[0].borrow(), GameItem::Key(Key { uses: 1 }));
+        // 使い切った鍵はなくなる
+        assert!(player.use_key());
+        assert_eq!(*player.items[0].borrow(), GameItem::Null);
+        assert!(!player.use_key());
     }
 
     #[test]
@@ -286,6 +528,7 @@ mod tests {
         assert_eq!(player.hp, 100);
         assert_eq!(player.attack, 12);
         assert_eq!(player.defense, 6);
+        assert_eq!(player.max_satiety, DEFAULT_MAX_SATIETY + MAX_SATIETY_PER_LEVEL);
     }
 
     #[test]
@@ -302,4 +545,96 @@ mod tests {
         assert_eq!(player.attack, 12);
         assert_eq!(player.defense, 6);
     }
+
+    #[test]
+    fn test_equip() {
+        let mut player = GamePlayer::new();
+        player.add_item(&RefCell::new(GameItem::Sword(Sword { attack_bonus: 3, pattern: AttackPattern::Single })));
+        player.add_item(&RefCell::new(GameItem::Shield(Shield { defense_bonus: 2 })));
+        player.add_item(&RefCell::new(GameItem::Sword(Sword { attack_bonus: 5, pattern: AttackPattern::Single })));
+        player.add_item(&RefCell::new(GameItem::HealthPotion(HealthPotion { heal_amount: 10 })));
+
+        // 装備するとスロットは空になる
+        assert!(player.equip(0));
+        assert_eq!(player.weapon, Some(Sword { attack_bonus: 3, pattern: AttackPattern::Single }));
+        assert_eq!(*player.items[0].borrow(), GameItem::Null);
+        assert!(player.equip(1));
+        assert_eq!(player.armor, Some(Shield { defense_bonus: 2 }));
+        assert_eq!(player.total_attack(), 13);
+        assert_eq!(player.total_defense(), 7);
+
+        // 別の武器を装備すると、外した武器がスロットに戻る
+        assert!(player.equip(2));
+        assert_eq!(player.weapon, Some(Sword { attack_bonus: 5, pattern: AttackPattern::Single }));
+        assert_eq!(*player.items[2].borrow(), GameItem::Sword(Sword { attack_bonus: 3, pattern: AttackPattern::Single }));
+
+        // 装備品でないものは装備できない
+        assert!(!player.equip(3));
+        assert!(!player.equip(100));
+    }
+
+    #[test]
+    fn test_unequip() {
+        let mut player = GamePlayer::new();
+        assert!(!player.unequip(EquipSlot::Weapon));
+
+        player.weapon = Some(Sword { attack_bonus: 3, pattern: AttackPattern::Single });
+        assert!(player.unequip(EquipSlot::Weapon));
+        assert_eq!(player.weapon, None);
+        assert_eq!(*player.items[0].borrow(), GameItem::Sword(Sword { attack_bonus: 3, pattern: AttackPattern::Single }));
+
+        // 持ち物がいっぱいの場合は外せない
+        player.armor = Some(Shield { defense_bonus: 2 });
+        for _ in 0..8 {
+            player.add_item(&RefCell::new(GameItem::HealthPotion(HealthPotion { heal_amount: 10 })));
+        }
+        assert!(!player.unequip(EquipSlot::Armor));
+        assert_eq!(player.armor, Some(Shield { defense_bonus: 2 }));
+    }
+
+    #[test]
+    fn test_use_equipment() {
+        // 装備品を使うと装備される
+        let mut player = GamePlayer::new();
+        player.add_item(&RefCell::new(GameItem::Shield(Shield { defense_bonus: 2 })));
+        player.select_item(0);
+        assert_eq!(player.use_item(), SideEffect::None);
+        assert_eq!(player.armor, Some(Shield { defense_bonus: 2 }));
+        assert_eq!(*player.items[0].borrow(), GameItem::Null);
+    }
+
+    #[test]
+    fn test_attack_with_weapon() {
+        // 装備中の武器の攻撃力が加算される
+        let mut player = GamePlayer::new();
+        player.position = (5, 5);
+        player.weapon = Some(Sword { attack_bonus: 4, pattern: AttackPattern::Single });
+        let mut result = vec![];
+        player.attack(&mut result);
+        assert_eq!(result[0], (5, 4, 14));
+    }
+
+    #[test]
+    fn test_take_damage() {
+        let mut player = GamePlayer::new();
+        assert_eq!(player.take_damage(5), 5);
+        assert_eq!(player.hp, 95);
+
+        // 負のダメージで回復はしない
+        assert_eq!(player.take_damage(-3), 0);
+        assert_eq!(player.hp, 95);
+    }
+
+    #[test]
+    fn test_direction_from_delta() {
+        assert_eq!(Direction::from_delta(0, -1), Some(Direction::Up));
+        assert_eq!(Direction::from_delta(3, 2), Some(Direction::DownRight));
+        assert_eq!(Direction::from_delta(-2, 0), Some(Direction::Left));
+        assert_eq!(Direction::from_delta(0, 0), None);
+        assert_eq!(Direction::DownLeft.delta(), (-1, 1));
+        assert_eq!(Direction::from_delta(Direction::UpRight.delta().0, Direction::UpRight.delta().1), Some(Direction::UpRight));
+        assert_eq!(Direction::Up.rotated(1), Direction::UpRight);
+        assert_eq!(Direction::Up.rotated(-1), Direction::UpLeft);
+        assert_eq!(Direction::Left.rotated(10), Direction::Up);
+    }
 }
@@ -0,0 +1,166 @@
+//! 飛び道具の軌道を求めるモジュール
+//!
+//! 軌道は始点から目標までの直線上のマスをたどり、見通せない地形の手前か最初の生き物のいるマスで止まる。
+
+use crate::player::Direction;
+use crate::tile::tile_at;
+use crate::tile::Tile;
+
+/// 飛び道具の軌道
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trajectory {
+    /// 通ったマス、始点を含まず止まったマスを含む
+    pub path: Vec<(i32, i32)>,
+    /// 当たった生き物のいるマス、何にも当たらなかった場合はNone
+    pub hit: Option<(i32, i32)>,
+}
+
+impl Trajectory {
+    /// 飛び道具が止まったマス、始点から動かなかった場合はNone
+    pub fn end(&self) -> Option<(i32, i32)> {
+        self.path.last().copied()
+    }
+
+    /// 投げた物が落ちるマス
+    ///
+    /// 裂け目のように見通せても歩いて入れないマスで止まった場合は、通ったマスを戻って
+    /// 最後に歩いて入れたマスに落とす。そのようなマスがない場合はNone
+    pub fn landing(&self, map: &[Vec<Tile>]) -> Option<(i32, i32)> {
+        self.path.iter()
+            .rev()
+            .find(|(x, y)| tile_at(map, *x, *y).is_walkable())
+            .copied()
+    }
+}
+
+/// 2点を結ぶ直線上のマス、始点を含まず終点を含む
+pub fn line(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let mut error = dx + dy;
+    let (mut x, mut y) = from;
+    let mut result = vec![];
+    while (x, y) != to {
+        let e2 = 2 * error;
+        if e2 >= dy {
+            error += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            error += dx;
+            y += sy;
+        }
+        result.push((x, y));
+    }
+    result
+}
+
+/// 向きに沿ってrangeマス分のマス、始点を含まない
+pub fn line_in_direction(from: (i32, i32), direction: Direction, range: i32) -> Vec<(i32, i32)> {
+    let (dx, dy) = direction.delta();
+    (1..=range).map(|i| (from.0 + dx * i, from.1 + dy * i)).collect()
+}
+
+/// 飛び道具を遮る地形かどうか、マップの外は壁とみなす
+fn is_blocked(map: &[Vec<Tile>], (x, y): (i32, i32)) -> bool {
+    !tile_at(map, x, y).is_transparent()
+}
+
+/// 直線に沿って飛び道具を飛ばす
+///
+/// # Arguments
+/// * `map` - 静的マップ
+/// * `line` - 飛ばす直線上のマス、射程はこの長さで決まる
+/// * `is_occupied` - 生き物がいるマスかどうか
+pub fn trace<F: Fn((i32, i32)) -> bool>(map: &[Vec<Tile>], line: &[(i32, i32)], is_occupied: F) -> Trajectory {
+    let mut path = vec![];
+    for position in line {
+        if is_blocked(map, *position) {
+            break;
+        }
+        path.push(*position);
+        if is_occupied(*position) {
+            return Trajectory { path, hit: Some(*position) };
+        }
+    }
+    Trajectory { path, hit: None }
+}
+
+/// 壁に遮られずに射程内の目標まで届くかどうか、生き物による遮蔽は考えない
+pub fn has_line_of_fire(map: &[Vec<Tile>], from: (i32, i32), to: (i32, i32), range: i32) -> bool {
+    let line = line(from, to);
+    if line.is_empty() || line.len() > range as usize {
+        return false;
+    }
+    line.iter().all(|position| !is_blocked(map, *position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::parse_map;
+
+    #[test]
+    fn test_line() {
+        assert_eq!(line((0, 0), (3, 0)), vec![(1, 0), (2, 0), (3, 0)]);
+        assert_eq!(line((0, 0), (2, 2)), vec![(1, 1), (2, 2)]);
+        assert_eq!(line((2, 1), (0, 0)).last(), Some(&(0, 0)));
+        assert!(line((1, 1), (1, 1)).is_empty());
+        assert_eq!(line_in_direction((1, 1), Direction::DownRight, 2), vec![(2, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn test_trace() {
+        let map = parse_map(&[
+            "#######",
+            "#.....#",
+            "#..#..#",
+            "#######",
+        ]);
+        // 壁の手前で止まる
+        let trajectory = trace(&map, &line_in_direction((1, 2), Direction::Right, 5), |_| false);
+        assert_eq!(trajectory.path, vec![(2, 2)]);
+        assert_eq!(trajectory.hit, None);
+        // 最初の生き物に当たって止まる
+        let trajectory = trace(&map, &line((1, 1), (5, 1)), |p| p == (3, 1) || p == (4, 1));
+        assert_eq!(trajectory.path, vec![(2, 1), (3, 1)]);
+        assert_eq!(trajectory.hit, Some((3, 1)));
+        assert_eq!(trajectory.end(), Some((3, 1)));
+        // 射程の分だけ飛ぶ
+        let trajectory = trace(&map, &line_in_direction((1, 1), Direction::Right, 2), |_| false);
+        assert_eq!(trajectory.end(), Some((3, 1)));
+    }
+
+    #[test]
+    fn test_landing() {
+        let map = parse_map(&[
+            "#######",
+            "#..:..#",
+            "#::...#",
+            "#######",
+        ]);
+        // 裂け目の上で止まった場合は手前の床に落ちる
+        let trajectory = trace(&map, &line_in_direction((1, 1), Direction::Right, 2), |_| false);
+        assert_eq!(trajectory.end(), Some((3, 1)));
+        assert_eq!(trajectory.landing(&map), Some((2, 1)));
+        // 床で止まった場合はそのマスに落ちる
+        let trajectory = trace(&map, &line_in_direction((1, 1), Direction::Right, 3), |_| false);
+        assert_eq!(trajectory.landing(&map), Some((4, 1)));
+        // 手前に歩いて入れるマスがない場合は落ちるマスがない
+        let trajectory = trace(&map, &line_in_direction((0, 2), Direction::Right, 2), |_| false);
+        assert_eq!(trajectory.landing(&map), None);
+    }
+
+    #[test]
+    fn test_line_of_fire() {
+        let map = parse_map(&[
+            "#######",
+            "#.....#",
+            "#..#..#",
+            "#######",
+        ]);
+        assert!(has_line_of_fire(&map, (1, 1), (5, 1), 5));
+        assert!(!has_line_of_fire(&map, (1, 1), (5, 1), 3));
+        assert!(!has_line_of_fire(&map, (1, 2), (5, 2), 5));
+    }
+}
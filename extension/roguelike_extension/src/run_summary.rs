@@ -0,0 +1,294 @@
+//! 1回のプレイの記録をまとめるモジュール
+//!
+//! プレイ中は`RunStats`に集計しておき、力尽きたときや踏破したときに
+//! `RunSummary`にまとめて、人が読むための文章とバランス調整用のJSONとして書き出す。
+
+use std::collections::BTreeMap;
+
+use crate::catalog::MobCatalog;
+use crate::event::Actor;
+use crate::event::GameEvent;
+use crate::game_state::CauseOfDeath;
+use crate::game_state::GameState;
+use crate::item::GameItem;
+use crate::item::Shield;
+use crate::item::Sword;
+use crate::locale::MessageCatalog;
+use crate::player::GamePlayer;
+
+use serde::{Deserialize, Serialize};
+
+/// カタログを使わずに生成した敵を倒した場合のキー
+pub const UNKNOWN_SPECIES: &str = "unknown";
+
+/// プレイ中に集計する記録
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunStats {
+    /// 経過したターン数
+    pub turns: i32,
+    /// 種類ごとの倒した敵の数、キーは敵の種類のキー
+    pub kills: BTreeMap<String, i32>,
+    /// プレイヤーが与えたダメージの合計
+    pub damage_dealt: i32,
+    /// プレイヤーが受けたダメージの合計
+    pub damage_taken: i32,
+}
+
+impl RunStats {
+    /// 出来事からダメージを集計する
+    ///
+    /// 受けたダメージには攻撃のほか、毒などの状態異常や空腹、地形や罠によるものも含める。
+    pub fn record_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::Attacked { attacker, target, damage, .. } => {
+                if *attacker == Actor::Player {
+                    self.damage_dealt += damage;
+                }
+                if *target == Actor::Player {
+                    self.damage_taken += damage;
+                }
+            },
+            // 回復は負のダメージとして数えない
+            GameEvent::StatusTicked { actor: Actor::Player, amount, .. } if *amount < 0 => {
+                self.damage_taken -= amount;
+            },
+            GameEvent::StarvationDamaged { damage } => self.damage_taken += damage,
+            GameEvent::TerrainDamaged { actor: Actor::Player, damage, .. } => self.damage_taken += damage,
+            GameEvent::TrapDamaged { actor: Actor::Player, damage, .. } => self.damage_taken += damage,
+            _ => {},
+        }
+    }
+
+    /// 倒した敵を数える
+    ///
+    /// # Arguments
+    /// * `species` - 敵の種類のキー、空の場合はUNKNOWN_SPECIESとして数える
+    pub fn record_kill(&mut self, species: &str) {
+        let key = if species.is_empty() { UNKNOWN_SPECIES } else { species };
+        *self.kills.entry(key.to_string()).or_insert(0) += 1;
+    }
+}
+
+/// 1回のプレイの記録
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunSummary {
+    /// 乱数のシード値
+    pub seed: i64,
+    /// 到達した階層
+    pub level: i32,
+    /// プレイの結果
+    pub outcome: GameState,
+    /// 力尽きた原因
+    pub cause_of_death: Option<CauseOfDeath>,
+    /// 経過したターン数
+    pub turns: i32,
+    /// プレイヤーのレベル
+    pub player_level: i32,
+    pub hp: i32,
+    pub max_hp: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub exp_point: i32,
+    /// 満腹度
+    #[serde(default)]
+    pub satiety: i32,
+    #[serde(default)]
+    pub max_satiety: i32,
+    /// 持ち物
+    pub inventory: Vec<GameItem>,
+    /// 装備している武器
+    pub weapon: Option<Sword>,
+    /// 装備している防具
+    pub armor: Option<Shield>,
+    /// 種類ごとの倒した敵の数
+    pub kills: BTreeMap<String, i32>,
+    /// 与えたダメージの合計
+    pub damage_dealt: i32,
+    /// 受けたダメージの合計
+    pub damage_taken: i32,
+}
+
+impl RunSummary {
+    /// 現在の状態から記録を作成する
+    pub fn new(
+        seed: i64,
+        level: i32,
+        outcome: GameState,
+        cause_of_death: Option<&CauseOfDeath>,
+        stats: &RunStats,
+        player: &GamePlayer,
+    ) -> Self {
+        Self {
+            seed,
+            level,
+            outcome,
+            cause_of_death: cause_of_death.cloned(),
+            turns: stats.turns,
+            player_level: player.level,
+            hp: player.hp,
+            max_hp: player.max_hp,
+            attack: player.attack,
+            defense: player.defense,
+            exp_point: player.exp_point,
+            satiety: player.satiety_display(),
+            max_satiety: player.max_satiety as i32,
+            inventory: player.items.iter().map(|item| *item.borrow()).collect(),
+            weapon: player.weapon,
+            armor: player.armor,
+            kills: stats.kills.clone(),
+            damage_dealt: stats.damage_dealt,
+            damage_taken: stats.damage_taken,
+        }
+    }
+
+    /// バランス調整用のJSON文字列に変換する
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// 人が読むための文章に変換する
+    ///
+    /// # Arguments
+    /// * `messages` - 表示する言語のメッセージのカタログ
+    /// * `mobs` - 倒した敵の名前を引くための敵のカタログ
+    pub fn to_text(&self, messages: &MessageCatalog, mobs: &MobCatalog) -> String {
+        let mut lines = vec![messages.text("summary_header", &[])];
+        let level = [("level", self.level.to_string())];
+        match self.outcome {
+            GameState::Dead => {
+                lines.push(messages.text("summary_dead", &level));
+                if let Some(cause) = &self.cause_of_death {
+                    lines.push(cause.text(messages));
+                }
+            },
+            GameState::Won => lines.push(messages.text("summary_won", &level)),
+            _ => lines.push(messages.text("summary_in_progress", &level)),
+        }
+        lines.push(messages.text("summary_seed", &[("seed", self.seed.to_string())]));
+        lines.push(messages.text("summary_turns", &[("turns", self.turns.to_string())]));
+        lines.push(String::new());
+
+        lines.push(messages.text("player_status", &[
+            ("level", self.player_level.to_string()),
+            ("hp", self.hp.to_string()),
+            ("max_hp", self.max_hp.to_string()),
+            ("attack", self.attack.to_string()),
+            ("defense", self.defense.to_string()),
+            ("exp", self.exp_point.to_string()),
+            ("satiety", self.satiety.to_string()),
+            ("max_satiety", self.max_satiety.to_string()),
+        ]));
+        let weapon = self.weapon.map_or(GameItem::Null, GameItem::Sword);
+        let armor = self.armor.map_or(GameItem::Null, GameItem::Shield);
+        lines.push(messages.text("summary_equipment", &[
+            ("weapon", messages.item_text(&weapon)),
+            ("armor", messages.item_text(&armor)),
+        ]));
+        lines.push(messages.text("summary_inventory", &[]));
+        for item in &self.inventory {
+            lines.push(format!("  {}", messages.item_text(item)));
+        }
+        lines.push(String::new());
+
+        lines.push(messages.text("summary_kills", &[]));
+        for (species, count) in &self.kills {
+            let name = mobs.get(species).map_or(species.clone(), |species| species.name.clone());
+            lines.push(messages.text("summary_kill_entry", &[("name", name), ("count", count.to_string())]));
+        }
+        lines.push(messages.text("summary_damage", &[
+            ("dealt", self.damage_dealt.to_string()),
+            ("taken", self.damage_taken.to_string()),
+        ]));
+        lines.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::DeathCause;
+    use crate::item::HealthPotion;
+    use crate::status_effect::StatusKind;
+    use crate::tile::Tile;
+    use crate::trap::TrapKind;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_record() {
+        let mut stats = RunStats::default();
+        stats.record_event(&GameEvent::Attacked {
+            attacker: Actor::Player,
+            target: Actor::Mob(1),
+            damage: 5,
+            critical: false,
+        });
+        stats.record_event(&GameEvent::Attacked {
+            attacker: Actor::Mob(1),
+            target: Actor::Player,
+            damage: 3,
+            critical: true,
+        });
+        stats.record_event(&GameEvent::Missed { attacker: Actor::Player, target: None });
+        stats.record_kill("slime");
+        stats.record_kill("slime");
+        stats.record_kill("");
+        assert_eq!(stats.damage_dealt, 5);
+        assert_eq!(stats.damage_taken, 3);
+        assert_eq!(stats.kills["slime"], 2);
+        assert_eq!(stats.kills[UNKNOWN_SPECIES], 1);
+    }
+
+    #[test]
+    fn test_record_status_damage() {
+        let mut stats = RunStats::default();
+        stats.record_event(&GameEvent::StatusTicked { actor: Actor::Player, kind: StatusKind::Poison, amount: -2 });
+        // 回復や敵の毒は数えない
+        stats.record_event(&GameEvent::StatusTicked { actor: Actor::Player, kind: StatusKind::Regen, amount: 3 });
+        stats.record_event(&GameEvent::StatusTicked { actor: Actor::Mob(1), kind: StatusKind::Poison, amount: -4 });
+        assert_eq!(stats.damage_taken, 2);
+    }
+
+    #[test]
+    fn test_record_starvation_damage() {
+        let mut stats = RunStats::default();
+        stats.record_event(&GameEvent::StarvationDamaged { damage: 1 });
+        stats.record_event(&GameEvent::StarvationDamaged { damage: 1 });
+        assert_eq!(stats.damage_taken, 2);
+    }
+
+    #[test]
+    fn test_record_terrain_damage() {
+        let mut stats = RunStats::default();
+        stats.record_event(&GameEvent::TerrainDamaged { actor: Actor::Player, tile: Tile::Lava, damage: 10 });
+        stats.record_event(&GameEvent::TrapDamaged { actor: Actor::Player, kind: TrapKind::Damage, damage: 5 });
+        // 溶岩や罠にかかった敵のダメージは数えない
+        stats.record_event(&GameEvent::TerrainDamaged { actor: Actor::Mob(1), tile: Tile::Lava, damage: 8 });
+        stats.record_event(&GameEvent::TrapDamaged { actor: Actor::Mob(1), kind: TrapKind::Damage, damage: 5 });
+        assert_eq!(stats.damage_taken, 15);
+        assert_eq!(stats.damage_dealt, 0);
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut stats = RunStats { turns: 120, ..Default::default() };
+        stats.record_kill("slime");
+        let mut player = GamePlayer::new();
+        player.items.push(RefCell::new(GameItem::HealthPotion(HealthPotion { heal_amount: 10 })));
+        let cause = CauseOfDeath::new(DeathCause::Mob { id: 4, name: "ゴブリン".to_string() }, 3);
+        let summary = RunSummary::new(42, 3, GameState::Dead, Some(&cause), &stats, &player);
+
+        // JSONから同じ記録を復元できる
+        let loaded: RunSummary = serde_json::from_str(&summary.to_json()).unwrap();
+        assert_eq!(loaded, summary);
+
+        let messages = MessageCatalog::default_catalog("ja").unwrap();
+        let text = summary.to_text(&messages, &MobCatalog::default_catalog());
+        assert!(text.contains("3階で力尽きた。"));
+        assert!(text.contains("3階でID4のゴブリンに倒された。"));
+        assert!(text.contains("シード値: 42"));
+        assert!(text.contains("回復薬: 10"));
+        // 倒した敵はカタログの名前で表示する
+        let slime = MobCatalog::default_catalog().get("slime").unwrap().name.clone();
+        assert!(text.contains(&format!("{}: 1", slime)));
+    }
+}
@@ -0,0 +1,226 @@
+//! ゲームの状態を保存・読み込みするモジュール
+
+use crate::static_map::StaticMapManager;
+use crate::dynamic_map::DynamicMapManager;
+use crate::fov::FieldOfView;
+use crate::run_summary::RunStats;
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// セーブデータの形式のバージョン
+///
+/// 以前のセーブデータが読み込めなくなる変更をした場合はこの値を上げる。
+/// 項目を追加するだけの場合は`#[serde(default)]`を付けて互換性を保つ。
+pub const SAVE_VERSION: u32 = 1;
+
+/// セーブデータの読み込みに失敗した場合のエラー
+#[derive(Debug, PartialEq)]
+pub enum SaveError {
+    /// JSONとして解釈できなかった
+    Parse(String),
+    /// 対応していないバージョンのセーブデータだった
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Parse(msg) => write!(f, "failed to parse save data: {}", msg),
+            SaveError::UnsupportedVersion(version) => write!(
+                f, "unsupported save data version: {} (supported: {})", version, SAVE_VERSION),
+        }
+    }
+}
+
+/// バージョンだけを先に読み出すためのヘッダ
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+/// 1回のプレイを再開するのに必要な情報をまとめたもの
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveData {
+    /// セーブデータの形式のバージョン
+    pub version: u32,
+    /// 乱数のシード値
+    pub seed: i64,
+    /// 現在の階層
+    pub current_level: i32,
+    /// 現在落ちているアイテムのIDの最大値
+    pub current_item_id_max: i32,
+    /// 静的マップ
+    pub static_map: StaticMapManager,
+    /// 動的マップ
+    pub dynamic_map: DynamicMapManager,
+    /// プレイヤーの視界と探索済みの範囲
+    #[serde(default)]
+    pub field_of_view: FieldOfView,
+    /// ここまでのプレイの記録
+    #[serde(default)]
+    pub run_stats: RunStats,
+    /// プレイヤーが落とし穴に落ちて、次の階層へ移動するのを待っているかどうか
+    #[serde(default)]
+    pub falling: bool,
+}
+
+impl SaveData {
+    /// 現在の状態からセーブデータを作成する
+    pub fn new(
+        seed: i64,
+        current_level: i32,
+        current_item_id_max: i32,
+        static_map: &StaticMapManager,
+        dynamic_map: &DynamicMapManager,
+        field_of_view: &FieldOfView,
+        run_stats: &RunStats,
+    ) -> Self {
+        Self {
+            version: SAVE_VERSION,
+            seed,
+            current_level,
+            current_item_id_max,
+            static_map: static_map.clone(),
+            dynamic_map: dynamic_map.clone(),
+            field_of_view: field_of_view.clone(),
+            run_stats: run_stats.clone(),
+            falling: false,
+        }
+    }
+
+    /// JSON文字列に変換する
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// JSON文字列から読み込む
+    ///
+    /// 自分より新しいバージョンで保存されたデータは読み込まない。
+    /// 最大HPを持たない古いデータの敵は、読み込んだ時点のHPを最大HPとする。
+    pub fn from_json(json: &str) -> Result<Self, SaveError> {
+        let header: SaveHeader = serde_json::from_str(json)
+            .map_err(|e| SaveError::Parse(e.to_string()))?;
+        if header.version == 0 || header.version > SAVE_VERSION {
+            return Err(SaveError::UnsupportedVersion(header.version));
+        }
+        let data: Self = serde_json::from_str(json).map_err(|e| SaveError::Parse(e.to_string()))?;
+        for mob in &data.dynamic_map.mob_list {
+            let mut mob = mob.borrow_mut();
+            if mob.max_hp <= 0 {
+                mob.max_hp = mob.hp;
+            }
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::{DroppedItem, GameItem, HealthPotion, Shield, Sword};
+    use crate::mob::GameMob;
+    use crate::player::Direction;
+    use crate::attack_pattern::AttackPattern;
+
+    use std::cell::RefCell;
+
+    fn create_state() -> (StaticMapManager, DynamicMapManager, FieldOfView) {
+        let mut static_map = StaticMapManager::new(64, 64);
+        static_map.generate_dungeon(64, 64, Some(7));
+
+        let mut dynamic_map = DynamicMapManager::new();
+        dynamic_map.player.position = (3, 4);
+        dynamic_map.player.direction = Direction::DownLeft;
+        dynamic_map.player.hp = 42;
+        dynamic_map.player.exp_point = 2;
+        dynamic_map.player.level = 3;
+        dynamic_map.player.add_item(&RefCell::new(GameItem::HealthPotion(HealthPotion { heal_amount: 10 })));
+        dynamic_map.player.add_item(&RefCell::new(GameItem::Sword(Sword { attack_bonus: 4, pattern: AttackPattern::Single })));
+        dynamic_map.player.add_item(&RefCell::new(GameItem::Shield(Shield { defense_bonus: 2 })));
+        dynamic_map.player.select_item(1);
+
+        let mut mob = GameMob::new_from_level(5, 10, 11, 2);
+        mob.hp = 7;
+        mob.direction = Direction::Right;
+        dynamic_map.mob_list.push(RefCell::new(mob));
+        dynamic_map.mob_list.push(RefCell::new(GameMob::new(6, 12, 13)));
+
+        dynamic_map.item_list.push(RefCell::new(DroppedItem {
+            id: 8,
+            position: (20, 21),
+            item: RefCell::new(GameItem::HealthPotion(HealthPotion { heal_amount: 10 })),
+        }));
+        dynamic_map.goal_position = (30, 31);
+
+        let mut field_of_view = FieldOfView::new(64, 64);
+        field_of_view.compute(&static_map.dungeon_map_2d, dynamic_map.player.position, 8);
+        (static_map, dynamic_map, field_of_view)
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        // 保存して読み込んだ結果が元の状態と一致すること
+        let (static_map, dynamic_map, field_of_view) = create_state();
+        let data = SaveData::new(123, 4, 9, &static_map, &dynamic_map, &field_of_view, &RunStats::default());
+        let json = data.to_json();
+        let loaded = SaveData::from_json(&json).unwrap();
+        assert_eq!(loaded, data);
+        assert_eq!(loaded.version, SAVE_VERSION);
+        assert_eq!(loaded.seed, 123);
+        assert_eq!(loaded.current_level, 4);
+        assert_eq!(loaded.current_item_id_max, 9);
+        assert_eq!(loaded.static_map, static_map);
+        assert_eq!(loaded.dynamic_map, dynamic_map);
+        assert_eq!(loaded.field_of_view, field_of_view);
+    }
+
+    #[test]
+    fn test_save_falling() {
+        // 落とし穴に落ちた状態も保存され、古いセーブデータでは落ちていないことになる
+        let (static_map, dynamic_map, field_of_view) = create_state();
+        let mut data = SaveData::new(1, 1, 0, &static_map, &dynamic_map, &field_of_view, &RunStats::default());
+        data.falling = true;
+        assert!(SaveData::from_json(&data.to_json()).unwrap().falling);
+
+        let mut value: serde_json::Value = serde_json::from_str(&data.to_json()).unwrap();
+        value.as_object_mut().unwrap().remove("falling");
+        assert!(!SaveData::from_json(&value.to_string()).unwrap().falling);
+    }
+
+    #[test]
+    fn test_load_mob_without_max_hp() {
+        // 最大HPを持たない古いデータの敵は、読み込んだ時点のHPが最大HPになる
+        let (static_map, dynamic_map, field_of_view) = create_state();
+        dynamic_map.mob_list[0].borrow_mut().hp = 3;
+        let data = SaveData::new(1, 1, 0, &static_map, &dynamic_map, &field_of_view, &RunStats::default());
+        let mut value: serde_json::Value = serde_json::from_str(&data.to_json()).unwrap();
+        for mob in value["dynamic_map"]["mob_list"].as_array_mut().unwrap() {
+            mob.as_object_mut().unwrap().remove("max_hp");
+        }
+        let loaded = SaveData::from_json(&value.to_string()).unwrap();
+        assert_eq!(loaded.dynamic_map.mob_list[0].borrow().max_hp, 3);
+    }
+
+    #[test]
+    fn test_defeated_mob_id_is_not_saved() {
+        let (static_map, mut dynamic_map, field_of_view) = create_state();
+        dynamic_map.defeated_mob_id.push(1);
+        let data = SaveData::new(1, 1, 0, &static_map, &dynamic_map, &field_of_view, &RunStats::default());
+        let loaded = SaveData::from_json(&data.to_json()).unwrap();
+        assert!(loaded.dynamic_map.defeated_mob_id.is_empty());
+    }
+
+    #[test]
+    fn test_load_invalid_data() {
+        assert!(matches!(SaveData::from_json("not json"), Err(SaveError::Parse(_))));
+
+        // 新しいバージョンのデータは読み込まない
+        let (static_map, dynamic_map, field_of_view) = create_state();
+        let mut data = SaveData::new(1, 1, 0, &static_map, &dynamic_map, &field_of_view, &RunStats::default());
+        data.version = SAVE_VERSION + 1;
+        assert_eq!(
+            SaveData::from_json(&data.to_json()),
+            Err(SaveError::UnsupportedVersion(SAVE_VERSION + 1)));
+    }
+}
@@ -0,0 +1,201 @@
+//! 素早さとエネルギーに基づいて行動順を決めるモジュール
+//!
+//! 行動するたびにエネルギーをACTION_COSTだけ消費し、時間が1単位進むごとに素早さの分だけ回復する。
+//! エネルギーがACTION_COST以上になった者が行動できる。素早さがNORMAL_SPEEDの2倍なら
+//! プレイヤーの1ターンの間に2回行動し、半分なら2ターンに1回行動する。
+
+use serde::{Deserialize, Serialize};
+
+/// 標準の素早さ
+pub const NORMAL_SPEED: i32 = 100;
+/// 1回の行動に必要なエネルギー
+pub const ACTION_COST: i32 = 100;
+
+/// 素早さを一時的に変化させる効果、加速と鈍足の状態異常から決まる
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpeedEffect {
+    /// 素早さが2倍になる
+    Haste,
+    /// 素早さが半分になる
+    Slow,
+}
+
+/// 素早さと行動のためのエネルギー
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Speed {
+    /// 効果を含まない素早さ
+    pub base: i32,
+    /// 現在のエネルギー
+    pub energy: i32,
+    /// かかっている効果、行動順を決める前に状態異常から設定する
+    #[serde(default)]
+    pub effect: Option<SpeedEffect>,
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self::new(NORMAL_SPEED)
+    }
+}
+
+impl Speed {
+    /// 素早さを指定して生成する
+    pub fn new(base: i32) -> Self {
+        Self {
+            base,
+            energy: 0,
+            effect: None,
+        }
+    }
+
+    /// 効果を含めた現在の素早さ、行動できなくならないよう最低でも1とする
+    pub fn current(&self) -> i32 {
+        let speed = match self.effect {
+            Some(SpeedEffect::Haste) => self.base * 2,
+            Some(SpeedEffect::Slow) => self.base / 2,
+            None => self.base,
+        };
+        speed.max(1)
+    }
+
+    /// 行動できるだけのエネルギーがあるかどうか
+    pub fn can_act(&self) -> bool {
+        self.energy >= ACTION_COST
+    }
+
+    /// 行動してエネルギーを消費する
+    ///
+    /// 行動できるようになる前に行動した場合(開始直後など)は0から貯め直す。
+    pub fn spend(&mut self) {
+        self.energy = (self.energy - ACTION_COST).max(0);
+    }
+
+    /// 時間を1単位進めてエネルギーを回復する
+    pub fn tick(&mut self) {
+        self.energy += self.current();
+    }
+}
+
+/// プレイヤーが行動した後、次にプレイヤーが行動できるようになるまでの敵の行動順を決める
+///
+/// 敵はIDと素早さの組で渡す。戻り値は同時に行動する敵のIDのまとまりを行動順に並べたもので、
+/// 1回のまとまりの中ではエネルギーが多い順、同じ場合はIDが小さい順に並ぶ。
+/// 素早い敵は複数のまとまりに含まれる。
+pub fn schedule_turn(player: &mut Speed, mobs: &mut [(i32, &mut Speed)]) -> Vec<Vec<i32>> {
+    player.spend();
+    let mut rounds = vec![];
+    while !player.can_act() {
+        player.tick();
+        for (_, speed) in mobs.iter_mut() {
+            speed.tick();
+        }
+        loop {
+            let mut ready: Vec<(i32, i32)> = mobs.iter()
+                .filter(|(_, speed)| speed.can_act())
+                .map(|(id, speed)| (*id, speed.energy))
+                .collect();
+            if ready.is_empty() {
+                break;
+            }
+            ready.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            for (id, speed) in mobs.iter_mut() {
+                if ready.iter().any(|(ready_id, _)| ready_id == id) {
+                    speed.spend();
+                }
+            }
+            rounds.push(ready.into_iter().map(|(id, _)| id).collect());
+        }
+    }
+    rounds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ターン数分スケジュールを進め、IDごとの行動回数を数える
+    fn count_actions(player: &mut Speed, mobs: &mut [Speed], turns: i32) -> Vec<usize> {
+        let mut counts = vec![0; mobs.len()];
+        for _ in 0..turns {
+            let mut entries: Vec<(i32, &mut Speed)> = mobs.iter_mut()
+                .enumerate()
+                .map(|(id, speed)| (id as i32, speed))
+                .collect();
+            for round in schedule_turn(player, &mut entries) {
+                for id in round {
+                    counts[id as usize] += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn test_speed() {
+        // 素早さに比例して行動する
+        let mut player = Speed::default();
+        let mut mobs = vec![Speed::new(100), Speed::new(200), Speed::new(50), Speed::new(150)];
+        let counts = count_actions(&mut player, &mut mobs, 100);
+        assert_eq!(counts, vec![100, 200, 50, 150]);
+    }
+
+    #[test]
+    fn test_fast_player() {
+        // プレイヤーが素早い場合、敵は2ターンに1回しか行動しない
+        let mut player = Speed::new(200);
+        let mut mobs = vec![Speed::new(100)];
+        let counts = count_actions(&mut player, &mut mobs, 100);
+        assert_eq!(counts, vec![50]);
+    }
+
+    #[test]
+    fn test_order() {
+        // エネルギーが多い順、同じならIDが小さい順に行動する
+        let mut player = Speed::default();
+        let mut fast = Speed::new(200);
+        let mut normal_a = Speed::new(100);
+        let mut normal_b = Speed::new(100);
+        normal_b.energy = 10;
+        let mut entries = vec![(3, &mut normal_a), (1, &mut fast), (2, &mut normal_b)];
+        let rounds = schedule_turn(&mut player, &mut entries);
+        assert_eq!(rounds, vec![vec![1, 2, 3], vec![1]]);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        // 同じ状態からは常に同じ行動順になる
+        let run = || {
+            let mut player = Speed::default();
+            let mut mobs = [Speed::new(70), Speed::new(130), Speed::new(100)];
+            let mut history = vec![];
+            for _ in 0..50 {
+                let mut entries: Vec<(i32, &mut Speed)> = mobs.iter_mut()
+                    .enumerate()
+                    .map(|(id, speed)| (id as i32, speed))
+                    .collect();
+                history.push(schedule_turn(&mut player, &mut entries));
+            }
+            history
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn test_effect() {
+        let mut speed = Speed::new(100);
+        speed.effect = Some(SpeedEffect::Haste);
+        assert_eq!(speed.current(), 200);
+        speed.tick();
+        assert_eq!(speed.energy, 200);
+        speed.effect = Some(SpeedEffect::Slow);
+        assert_eq!(speed.current(), 50);
+
+        // 鈍足の敵は効果がかかっている間は2ターンに1回しか行動しない
+        let mut player = Speed::default();
+        let mut mobs = vec![Speed::new(100)];
+        mobs[0].effect = Some(SpeedEffect::Slow);
+        assert_eq!(count_actions(&mut player, &mut mobs, 10), vec![5]);
+        mobs[0].effect = None;
+        assert_eq!(count_actions(&mut player, &mut mobs, 10), vec![10]);
+    }
+}
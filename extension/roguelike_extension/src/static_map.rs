@@ -1,22 +1,36 @@
 //! 静的マップ(地形)を管理するモジュール
 
 use crate::map_generator::{
-    generate_dungeon,
+    generate_connected,
     BSPNodeParams,
     Direction,
+    GeneratorKind,
 };
+use crate::pathfinding::PathFinder;
+use crate::tile::tile_at;
+use crate::tile::DoorState;
+use crate::tile::Tile;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
 
 
 /// 静的マップを管理するクラス
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StaticMapManager {
     /// マップの幅
     pub dungeon_width: i32,
     /// マップの高さ
     pub dungeon_height: i32,
     /// マップの内容を格納する2次元配列
-    pub dungeon_map_2d: Vec<Vec<i32>>,
+    pub dungeon_map_2d: Vec<Vec<Tile>>,
     /// 部屋の情報を格納する配列
     pub room_params: Vec<BSPNodeParams>,
+    /// マップ生成に使ったシード値
+    pub seed: u64,
 }
 
 impl StaticMapManager {
@@ -25,8 +39,9 @@ impl StaticMapManager {
         Self {
             dungeon_width: width,
             dungeon_height: height,
-            dungeon_map_2d: vec![vec![0; height as usize]; width as usize],
+            dungeon_map_2d: vec![vec![Tile::Floor; height as usize]; width as usize],
             room_params: vec![],
+            seed: 0,
         }
     }
 
@@ -34,18 +49,18 @@ impl StaticMapManager {
     pub fn generate_simple_map(&mut self, width: i32, height: i32) {
         self.dungeon_width = width;
         self.dungeon_height = height;
-        self.dungeon_map_2d = vec![vec![0; height as usize]; width as usize];
+        self.dungeon_map_2d = vec![vec![Tile::Floor; height as usize]; width as usize];
         for y in 0..self.dungeon_height {
-            self.dungeon_map_2d[0][y as usize] = 1;
-            self.dungeon_map_2d[(self.dungeon_width - 1) as usize][y as usize] = 1;
+            self.dungeon_map_2d[0][y as usize] = Tile::Wall;
+            self.dungeon_map_2d[(self.dungeon_width - 1) as usize][y as usize] = Tile::Wall;
         }
 
         for x in 1..(self.dungeon_width - 1) {
             for y in 1..(self.dungeon_height - 1) {
-                self.dungeon_map_2d[x as usize][y as usize] = 0;
+                self.dungeon_map_2d[x as usize][y as usize] = Tile::Floor;
             }
-            self.dungeon_map_2d[x as usize][0] = 1;
-            self.dungeon_map_2d[x as usize][(self.dungeon_height - 1) as usize] = 1;
+            self.dungeon_map_2d[x as usize][0] = Tile::Wall;
+            self.dungeon_map_2d[x as usize][(self.dungeon_height - 1) as usize] = Tile::Wall;
         }
         self.room_params = vec![];
         self.room_params.push(
@@ -62,31 +77,207 @@ impl StaticMapManager {
     }
 
     /// 自動生成を使ってダンジョンを生成する
-    pub fn generate_dungeon(&mut self, width: i32, height: i32) {
+    ///
+    /// シード値を指定しなかった場合はランダムに決め、使ったシード値を返す。
+    pub fn generate_dungeon(&mut self, width: i32, height: i32, seed: Option<u64>) -> u64 {
+        self.generate_with(GeneratorKind::Bsp, width, height, seed)
+    }
+
+    /// 生成アルゴリズムを指定してダンジョンを生成する
+    ///
+    /// どのアルゴリズムでも、すべての部屋がつながっていることを確かめてから採用する。
+    /// シード値を指定しなかった場合はランダムに決め、使ったシード値を返す。
+    pub fn generate_with(&mut self, kind: GeneratorKind, width: i32, height: i32, seed: Option<u64>) -> u64 {
+        let seed = seed.unwrap_or_else(rand::random::<u64>);
+        let mut rng = StdRng::seed_from_u64(seed);
         (self.dungeon_map_2d, self.room_params) =
-            generate_dungeon(width, height);
+            generate_connected(kind.generator().as_ref(), width, height, &mut rng);
         self.dungeon_width = width;
         self.dungeon_height = height;
+        self.seed = seed;
+        self.seed
+    }
+
+    /// 指定したマスの地形、マップの外は壁とみなす
+    pub fn tile(&self, x: i32, y: i32) -> Tile {
+        tile_at(&self.dungeon_map_2d, x, y)
+    }
+
+    /// 扉を開ける
+    ///
+    /// # Returns
+    /// 閉じているか鍵のかかっている扉を開けた場合はtrue
+    pub fn open_door(&mut self, x: i32, y: i32) -> bool {
+        if !matches!(self.tile(x, y), Tile::Door(DoorState::Closed | DoorState::Locked)) {
+            return false;
+        }
+        self.dungeon_map_2d[x as usize][y as usize] = Tile::Door(DoorState::Open);
+        true
+    }
+
+    /// 閉じた扉のうち一部に鍵をかけ、開けるための鍵を置く位置を決める
+    ///
+    /// 鍵はすべて`start`から鍵のかかった扉を通らずにたどり着ける床に置くので、
+    /// 鍵を集めればどの扉も開けられる。すでにアイテムがある床には置かず、
+    /// 鍵を置ける床が足りない場合は鍵をかけない。
+    ///
+    /// # Arguments
+    /// * `start` - プレイヤーの初期位置
+    /// * `occupied` - すでにアイテムが置かれている位置
+    /// * `probability` - 扉ごとに鍵をかける確率
+    /// * `finder` - たどり着けるかどうかの判定に使う経路探索の設定
+    ///
+    /// # Returns
+    /// 鍵を置く位置、鍵をかけた扉の数だけ返す
+    pub fn lock_doors<R: Rng + ?Sized>(
+        &mut self,
+        start: (i32, i32),
+        occupied: &[(i32, i32)],
+        probability: f32,
+        finder: &PathFinder,
+        rng: &mut R,
+    ) -> Vec<(i32, i32)> {
+        let mut locked = vec![];
+        for x in 0..self.dungeon_width {
+            for y in 0..self.dungeon_height {
+                if self.tile(x, y) == Tile::Door(DoorState::Closed) && rng.gen::<f32>() < probability {
+                    self.dungeon_map_2d[x as usize][y as usize] = Tile::Door(DoorState::Locked);
+                    locked.push((x, y));
+                }
+            }
+        }
+        if locked.is_empty() {
+            return vec![];
+        }
+
+        let distances = finder.dijkstra_map(&self.dungeon_map_2d, &[start]);
+        let mut candidates = vec![];
+        for x in 0..self.dungeon_width {
+            for y in 0..self.dungeon_height {
+                if (x, y) != start
+                    && !occupied.contains(&(x, y))
+                    && self.tile(x, y) == Tile::Floor
+                    && distances.distance(x, y).is_some()
+                {
+                    candidates.push((x, y));
+                }
+            }
+        }
+        if candidates.len() < locked.len() {
+            for (x, y) in locked {
+                self.dungeon_map_2d[x as usize][y as usize] = Tile::Door(DoorState::Closed);
+            }
+            return vec![];
+        }
+        candidates.choose_multiple(rng, locked.len()).copied().collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::map_generator::choose_reachable_start_and_goal_rooms;
+    use crate::map_generator::passable_regions;
 
     #[test]
     fn test_static_map_manager() {
         let mut manager = StaticMapManager::new(10, 10);
         manager.generate_simple_map(10, 10);
-        assert_eq!(manager.dungeon_map_2d[0][0], 1);
-        assert_eq!(manager.dungeon_map_2d[9][9], 1);
-        assert_eq!(manager.dungeon_map_2d[5][5], 0);
+        assert_eq!(manager.dungeon_map_2d[0][0], Tile::Wall);
+        assert_eq!(manager.dungeon_map_2d[9][9], Tile::Wall);
+        assert_eq!(manager.dungeon_map_2d[5][5], Tile::Floor);
     }
 
     #[test]
     fn test_generate_dungeon() {
         let mut manager = StaticMapManager::new(64, 64);
-        manager.generate_dungeon(64, 64);
+        manager.generate_dungeon(64, 64, None);
         assert_eq!(manager.dungeon_map_2d.len(), 64);
     }
+
+    #[test]
+    fn test_open_door() {
+        let mut manager = StaticMapManager::new(3, 3);
+        manager.dungeon_map_2d[1][1] = Tile::Door(DoorState::Locked);
+        assert!(manager.open_door(1, 1));
+        assert_eq!(manager.tile(1, 1), Tile::Door(DoorState::Open));
+        // 開いている扉や扉でないマスは開けられない
+        assert!(!manager.open_door(1, 1));
+        assert!(!manager.open_door(0, 0));
+    }
+
+    #[test]
+    fn test_lock_doors() {
+        let mut manager = StaticMapManager::new(64, 64);
+        manager.generate_dungeon(64, 64, Some(42));
+        let room = &manager.room_params[0];
+        let start = (room.room_center_x, room.room_center_y);
+        let door_count = manager.dungeon_map_2d.iter().flatten()
+            .filter(|tile| **tile == Tile::Door(DoorState::Closed))
+            .count();
+        assert!(door_count > 0);
+
+        // すべての扉に鍵をかけても、鍵はすべて初期位置からたどり着ける床に置かれる
+        let mut rng = StdRng::seed_from_u64(0);
+        let finder = PathFinder::default();
+        let keys = manager.lock_doors(start, &[], 1.0, &finder, &mut rng);
+        assert_eq!(keys.len(), door_count);
+        assert!(manager.dungeon_map_2d.iter().flatten().all(|tile| *tile != Tile::Door(DoorState::Closed)));
+        let distances = finder.dijkstra_map(&manager.dungeon_map_2d, &[start]);
+        for &(x, y) in &keys {
+            assert_eq!(manager.tile(x, y), Tile::Floor);
+            assert!(distances.distance(x, y).is_some());
+        }
+
+        // すでにアイテムがある床には鍵を置かない
+        let mut manager = StaticMapManager::new(64, 64);
+        manager.generate_dungeon(64, 64, Some(42));
+        let occupied = keys.clone();
+        let mut rng = StdRng::seed_from_u64(0);
+        let keys = manager.lock_doors(start, &occupied, 1.0, &finder, &mut rng);
+        assert_eq!(keys.len(), door_count);
+        assert!(keys.iter().all(|position| !occupied.contains(position)));
+
+        // 確率が0の場合は鍵をかけない
+        let mut manager = StaticMapManager::new(64, 64);
+        manager.generate_dungeon(64, 64, Some(42));
+        assert!(manager.lock_doors(start, &[], 0.0, &finder, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn test_goal_is_always_reachable() {
+        // どのアルゴリズムでも、多くのシード値について初期位置からゴールまでの経路がある
+        let finder = PathFinder::default();
+        for kind in GeneratorKind::ALL {
+            for seed in 0..200 {
+                let mut manager = StaticMapManager::new(64, 64);
+                manager.generate_with(kind, 64, 64, Some(seed));
+                let mut rng = StdRng::seed_from_u64(seed);
+                let (start_idx, goal_idx) = choose_reachable_start_and_goal_rooms(
+                    &manager.dungeon_map_2d, &manager.room_params, true, &mut rng).unwrap();
+                assert_ne!(start_idx, goal_idx, "{:?} seed {}", kind, seed);
+                let start = &manager.room_params[start_idx];
+                let goal = &manager.room_params[goal_idx];
+                let path = finder.find_path(
+                    &manager.dungeon_map_2d,
+                    (start.room_center_x, start.room_center_y),
+                    (goal.room_center_x, goal.room_center_y),
+                );
+                assert!(path.is_some(), "{:?} seed {}", kind, seed);
+                // 離れた領域は残っていない
+                assert_eq!(passable_regions(&manager.dungeon_map_2d).len(), 1, "{:?} seed {}", kind, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_dungeon_with_seed() {
+        let mut manager_a = StaticMapManager::new(64, 64);
+        let mut manager_b = StaticMapManager::new(64, 64);
+        assert_eq!(manager_a.generate_dungeon(64, 64, Some(42)), 42);
+        assert_eq!(manager_b.generate_dungeon(64, 64, Some(42)), 42);
+        assert_eq!(manager_a.seed, 42);
+        assert_eq!(manager_a.dungeon_map_2d, manager_b.dungeon_map_2d);
+        assert_eq!(manager_a.room_params, manager_b.room_params);
+    }
 }
\ No newline at end of file
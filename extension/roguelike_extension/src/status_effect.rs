@@ -0,0 +1,276 @@
+//! 一時的な状態異常を管理するモジュール
+//!
+//! プレイヤーと敵はそれぞれ`StatusEffects`を持ち、ターンの終わりに`tick`で効果を1ターン進める。
+//! 同じ種類の効果は1つにまとめ、重ね方は種類ごとに決めておく。
+
+use crate::player::Direction;
+use crate::scheduler::SpeedEffect;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// 混乱しているときに思った方向へ進めない確率
+pub const CONFUSED_RANDOM_MOVE_RATE: f32 = 0.5;
+
+/// 状態異常の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StatusKind {
+    /// 毎ターン強さの分だけダメージを受ける
+    Poison,
+    /// 行動できない、ダメージを受けると目を覚ます
+    Sleep,
+    /// 移動する方向がでたらめになる
+    Confusion,
+    /// 毎ターン強さの分だけHPが回復する
+    Regen,
+    /// 素早さが2倍になる
+    Haste,
+    /// 素早さが半分になる
+    Slow,
+}
+
+impl StatusKind {
+    pub const ALL: [StatusKind; 6] = [
+        StatusKind::Poison,
+        StatusKind::Sleep,
+        StatusKind::Confusion,
+        StatusKind::Regen,
+        StatusKind::Haste,
+        StatusKind::Slow,
+    ];
+
+    /// Godot側に渡すときやメッセージIDに使う文字列
+    pub fn key(&self) -> &'static str {
+        match self {
+            StatusKind::Poison => "poison",
+            StatusKind::Sleep => "sleep",
+            StatusKind::Confusion => "confusion",
+            StatusKind::Regen => "regen",
+            StatusKind::Haste => "haste",
+            StatusKind::Slow => "slow",
+        }
+    }
+
+    /// 文字列から種類を求める、知らない文字列の場合はNone
+    pub fn from_key(key: &str) -> Option<StatusKind> {
+        StatusKind::ALL.iter().copied().find(|kind| kind.key() == key)
+    }
+}
+
+/// 1つの状態異常
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    /// 残りターン数
+    pub turns: i32,
+    /// 強さ、毒のダメージや再生の回復量に使う
+    #[serde(default)]
+    pub potency: i32,
+}
+
+impl StatusEffect {
+    pub fn new(kind: StatusKind, turns: i32, potency: i32) -> Self {
+        Self { kind, turns, potency }
+    }
+}
+
+/// 1ターン進めたときの効果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusTick {
+    pub kind: StatusKind,
+    /// HPの増減、毒の場合は負の値になる
+    pub hp_delta: i32,
+    /// このターンで効果が切れたかどうか
+    pub expired: bool,
+}
+
+/// かかっている状態異常の一覧
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StatusEffects {
+    pub effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    /// 状態異常にかける
+    ///
+    /// 同じ種類の効果にすでにかかっている場合は次の規則でまとめる。
+    /// * 毒: 残りターンを足し合わせ、強さは強いほうにする
+    /// * 眠り、混乱、加速、鈍足: 残りターンの長いほうにする、重ねても延びない
+    /// * 再生: 残りターンと強さをそれぞれ大きいほうにする
+    ///
+    /// # Returns
+    /// 新しくかかった場合はtrue、すでにかかっていた効果をまとめた場合はfalse
+    pub fn add(&mut self, effect: StatusEffect) -> bool {
+        if effect.turns <= 0 {
+            return false;
+        }
+        let Some(current) = self.effects.iter_mut().find(|current| current.kind == effect.kind) else {
+            self.effects.push(effect);
+            return true;
+        };
+        match effect.kind {
+            StatusKind::Poison => {
+                current.turns += effect.turns;
+                current.potency = current.potency.max(effect.potency);
+            },
+            StatusKind::Sleep | StatusKind::Confusion | StatusKind::Haste | StatusKind::Slow => {
+                current.turns = current.turns.max(effect.turns);
+            },
+            StatusKind::Regen => {
+                current.turns = current.turns.max(effect.turns);
+                current.potency = current.potency.max(effect.potency);
+            },
+        }
+        false
+    }
+
+    /// 状態異常を治す
+    ///
+    /// # Returns
+    /// かかっていた場合はtrue
+    pub fn remove(&mut self, kind: StatusKind) -> bool {
+        let len = self.effects.len();
+        self.effects.retain(|effect| effect.kind != kind);
+        self.effects.len() != len
+    }
+
+    pub fn get(&self, kind: StatusKind) -> Option<&StatusEffect> {
+        self.effects.iter().find(|effect| effect.kind == kind)
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.get(kind).is_some()
+    }
+
+    /// 眠っていて行動できないかどうか
+    pub fn is_asleep(&self) -> bool {
+        self.has(StatusKind::Sleep)
+    }
+
+    /// 素早さにかかっている効果、加速と鈍足の両方にかかっている場合は打ち消し合う
+    pub fn speed_effect(&self) -> Option<SpeedEffect> {
+        match (self.has(StatusKind::Haste), self.has(StatusKind::Slow)) {
+            (true, false) => Some(SpeedEffect::Haste),
+            (false, true) => Some(SpeedEffect::Slow),
+            _ => None,
+        }
+    }
+
+    /// 混乱しているときに進む方向を決める
+    ///
+    /// # Returns
+    /// でたらめに進む場合はその方向、混乱していないか思った方向へ進める場合はNone
+    pub fn confused_direction<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Direction> {
+        if !self.has(StatusKind::Confusion) || rng.gen::<f32>() >= CONFUSED_RANDOM_MOVE_RATE {
+            return None;
+        }
+        Some(Direction::Up.rotated(rng.gen_range(0..8)))
+    }
+
+    /// すべての状態異常を1ターン進め、切れたものを取り除く
+    pub fn tick(&mut self) -> Vec<StatusTick> {
+        let ticks = self.effects.iter_mut()
+            .map(|effect| {
+                effect.turns -= 1;
+                let hp_delta = match effect.kind {
+                    StatusKind::Poison => -effect.potency,
+                    StatusKind::Regen => effect.potency,
+                    _ => 0,
+                };
+                StatusTick { kind: effect.kind, hp_delta, expired: effect.turns <= 0 }
+            })
+            .collect();
+        self.effects.retain(|effect| effect.turns > 0);
+        ticks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::schedule_turn;
+    use crate::scheduler::Speed;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_stacking() {
+        let mut effects = StatusEffects::default();
+        assert!(effects.add(StatusEffect::new(StatusKind::Poison, 3, 2)));
+        // 毒は残りターンを足し合わせる
+        assert!(!effects.add(StatusEffect::new(StatusKind::Poison, 2, 1)));
+        assert_eq!(effects.get(StatusKind::Poison), Some(&StatusEffect::new(StatusKind::Poison, 5, 2)));
+        // 眠りは重ねても延びない
+        effects.add(StatusEffect::new(StatusKind::Sleep, 4, 0));
+        effects.add(StatusEffect::new(StatusKind::Sleep, 2, 0));
+        assert_eq!(effects.get(StatusKind::Sleep).unwrap().turns, 4);
+        // 再生は強いほうの効果になる
+        effects.add(StatusEffect::new(StatusKind::Regen, 3, 1));
+        effects.add(StatusEffect::new(StatusKind::Regen, 1, 4));
+        assert_eq!(effects.get(StatusKind::Regen), Some(&StatusEffect::new(StatusKind::Regen, 3, 4)));
+        assert_eq!(effects.effects.len(), 3);
+
+        assert!(effects.is_asleep());
+        assert!(effects.remove(StatusKind::Sleep));
+        assert!(!effects.remove(StatusKind::Sleep));
+        assert!(!effects.is_asleep());
+    }
+
+    #[test]
+    fn test_tick() {
+        let mut effects = StatusEffects::default();
+        effects.add(StatusEffect::new(StatusKind::Poison, 2, 3));
+        effects.add(StatusEffect::new(StatusKind::Regen, 1, 1));
+        assert_eq!(effects.tick(), vec![
+            StatusTick { kind: StatusKind::Poison, hp_delta: -3, expired: false },
+            StatusTick { kind: StatusKind::Regen, hp_delta: 1, expired: true },
+        ]);
+        assert_eq!(effects.tick(), vec![
+            StatusTick { kind: StatusKind::Poison, hp_delta: -3, expired: true },
+        ]);
+        assert!(effects.effects.is_empty());
+        assert!(effects.tick().is_empty());
+    }
+
+    #[test]
+    fn test_confused_direction() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut effects = StatusEffects::default();
+        assert!((0..100).all(|_| effects.confused_direction(&mut rng).is_none()));
+        effects.add(StatusEffect::new(StatusKind::Confusion, 5, 0));
+        let random_moves = (0..100).filter(|_| effects.confused_direction(&mut rng).is_some()).count();
+        assert!(random_moves > 0 && random_moves < 100);
+    }
+
+    #[test]
+    fn test_speed_effect() {
+        let mut effects = StatusEffects::default();
+        assert_eq!(effects.speed_effect(), None);
+        effects.add(StatusEffect::new(StatusKind::Haste, 2, 0));
+        assert_eq!(effects.speed_effect(), Some(SpeedEffect::Haste));
+        // 加速と鈍足は打ち消し合う
+        effects.add(StatusEffect::new(StatusKind::Slow, 5, 0));
+        assert_eq!(effects.speed_effect(), None);
+        // 加速が切れると鈍足だけが残る
+        effects.tick();
+        effects.tick();
+        assert_eq!(effects.speed_effect(), Some(SpeedEffect::Slow));
+
+        // 鈍足の敵はプレイヤーの2ターンに1回しか行動しない
+        let mut player = Speed::default();
+        let mut mob = Speed { effect: effects.speed_effect(), ..Default::default() };
+        let rounds: usize = (0..4)
+            .map(|_| schedule_turn(&mut player, &mut [(1, &mut mob)]).len())
+            .sum();
+        assert_eq!(rounds, 2);
+    }
+
+    #[test]
+    fn test_key() {
+        for kind in StatusKind::ALL {
+            assert_eq!(StatusKind::from_key(kind.key()), Some(kind));
+        }
+        assert_eq!(StatusKind::from_key("unknown"), None);
+    }
+}
@@ -0,0 +1,233 @@
+//! マップの地形を定義するモジュール
+//!
+//! 静的マップは地形(`Tile`)の2次元配列で持ち、通れるかどうかや見通せるかどうかは
+//! 地形ごとの性質から決める。Godot側やセーブデータには地形ごとに決めた整数で渡す。
+
+use crate::status_effect::StatusEffect;
+use crate::status_effect::StatusKind;
+
+use serde::{Deserialize, Serialize};
+
+/// 水に踏み込んだときに鈍足になるターン数
+pub const WATER_SLOW_TURNS: i32 = 2;
+
+/// 地形の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(into = "i32", try_from = "i32")]
+pub enum Tile {
+    /// 床
+    #[default]
+    Floor,
+    /// 壁
+    Wall,
+    /// 扉、開いているときだけ通れて向こうを見通せる
+    Door(DoorState),
+    /// 浅い水、踏み込むと足を取られて鈍足になる
+    Water,
+    /// 溶岩、踏み込むとダメージを受ける
+    Lava,
+    /// 裂け目、通れないが向こうは見通せる
+    Chasm,
+    /// 次の階層への階段、ゴールのマスに置く
+    Stairs,
+}
+
+/// 扉の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DoorState {
+    /// 開いている
+    Open,
+    /// 閉じている、誰でも開けられる
+    Closed,
+    /// 鍵がかかっている、鍵を使わないと開けられない
+    Locked,
+}
+
+impl Tile {
+    pub const ALL: [Tile; 9] = [
+        Tile::Floor,
+        Tile::Wall,
+        Tile::Door(DoorState::Closed),
+        Tile::Water,
+        Tile::Lava,
+        Tile::Chasm,
+        Tile::Stairs,
+        Tile::Door(DoorState::Open),
+        Tile::Door(DoorState::Locked),
+    ];
+
+    /// Godot側やセーブデータに渡すときの値、一度決めた値は変えない
+    ///
+    /// 7は以前の罠の値で、罠は地形とは別に管理するようになったので使わない。
+    pub fn to_i32(&self) -> i32 {
+        match self {
+            Tile::Floor => 0,
+            Tile::Wall => 1,
+            Tile::Door(DoorState::Closed) => 2,
+            Tile::Water => 3,
+            Tile::Lava => 4,
+            Tile::Chasm => 5,
+            Tile::Stairs => 6,
+            Tile::Door(DoorState::Open) => 8,
+            Tile::Door(DoorState::Locked) => 9,
+        }
+    }
+
+    /// 値から地形を求める、知らない値の場合はNone
+    pub fn from_i32(value: i32) -> Option<Tile> {
+        Tile::ALL.iter().copied().find(|tile| tile.to_i32() == value)
+    }
+
+    /// Godot側に渡すときやメッセージIDに使う文字列
+    pub fn key(&self) -> &'static str {
+        match self {
+            Tile::Floor => "floor",
+            Tile::Wall => "wall",
+            Tile::Door(_) => "door",
+            Tile::Water => "water",
+            Tile::Lava => "lava",
+            Tile::Chasm => "chasm",
+            Tile::Stairs => "stairs",
+        }
+    }
+
+    /// 歩いて入れるかどうか
+    pub fn is_walkable(&self) -> bool {
+        !matches!(self, Tile::Wall | Tile::Chasm | Tile::Door(DoorState::Closed | DoorState::Locked))
+    }
+
+    /// 向こう側を見通せるかどうか、飛び道具もここを通り抜ける
+    pub fn is_transparent(&self) -> bool {
+        !matches!(self, Tile::Wall | Tile::Door(DoorState::Closed | DoorState::Locked))
+    }
+
+    /// 踏み込んだときに受けるダメージ
+    pub fn entry_damage(&self) -> i32 {
+        match self {
+            Tile::Lava => 10,
+            _ => 0,
+        }
+    }
+
+    /// 踏み込んだときにかかる状態異常
+    pub fn entry_status(&self) -> Option<StatusEffect> {
+        match self {
+            Tile::Water => Some(StatusEffect::new(StatusKind::Slow, WATER_SLOW_TURNS, 0)),
+            _ => None,
+        }
+    }
+
+    /// 歩いて入れて、かつ踏み込んでもダメージを受けないかどうか
+    pub fn is_safe(&self) -> bool {
+        self.is_walkable() && self.entry_damage() == 0
+    }
+}
+
+impl From<Tile> for i32 {
+    fn from(tile: Tile) -> i32 {
+        tile.to_i32()
+    }
+}
+
+impl TryFrom<i32> for Tile {
+    type Error = String;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        Tile::from_i32(value).ok_or_else(|| format!("unknown tile: {}", value))
+    }
+}
+
+/// 指定したマスの地形、マップの外は壁とみなす
+pub fn tile_at(map: &[Vec<Tile>], x: i32, y: i32) -> Tile {
+    if x < 0 || y < 0 {
+        return Tile::Wall;
+    }
+    map.get(x as usize)
+        .and_then(|column| column.get(y as usize))
+        .copied()
+        .unwrap_or(Tile::Wall)
+}
+
+/// 文字列からマップを作る、'#'が壁、'+'が閉じた扉、'L'が鍵のかかった扉、':'が裂け目でそれ以外は床
+#[cfg(test)]
+pub fn parse_map(rows: &[&str]) -> Vec<Vec<Tile>> {
+    let mut map = vec![vec![Tile::Floor; rows.len()]; rows[0].len()];
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            map[x][y] = match c {
+                '#' => Tile::Wall,
+                '+' => Tile::Door(DoorState::Closed),
+                'L' => Tile::Door(DoorState::Locked),
+                ':' => Tile::Chasm,
+                _ => Tile::Floor,
+            };
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mapping() {
+        for tile in Tile::ALL {
+            assert_eq!(Tile::from_i32(tile.to_i32()), Some(tile));
+        }
+        // 以前の床と壁の値はそのまま使える
+        assert_eq!(Tile::from_i32(0), Some(Tile::Floor));
+        assert_eq!(Tile::from_i32(1), Some(Tile::Wall));
+        assert_eq!(Tile::from_i32(2), Some(Tile::Door(DoorState::Closed)));
+        assert_eq!(Tile::from_i32(7), None);
+        assert_eq!(Tile::from_i32(100), None);
+    }
+
+    #[test]
+    fn test_properties() {
+        assert!(Tile::Floor.is_safe());
+        assert!(!Tile::Wall.is_walkable());
+        assert!(!Tile::Wall.is_transparent());
+        // 扉は開いているときだけ通れて見通せる
+        assert!(!Tile::Door(DoorState::Closed).is_walkable());
+        assert!(!Tile::Door(DoorState::Closed).is_transparent());
+        assert!(!Tile::Door(DoorState::Locked).is_walkable());
+        assert!(Tile::Door(DoorState::Open).is_walkable());
+        assert!(Tile::Door(DoorState::Open).is_transparent());
+        assert!(!Tile::Chasm.is_walkable());
+        assert!(Tile::Chasm.is_transparent());
+        assert!(Tile::Lava.is_walkable());
+        assert!(!Tile::Lava.is_safe());
+        assert!(Tile::Lava.entry_damage() > 0);
+        // 水は安全に通れるが鈍足になる
+        assert!(Tile::Water.is_safe());
+        assert_eq!(Tile::Water.entry_status().map(|effect| effect.kind), Some(StatusKind::Slow));
+        assert_eq!(Tile::Floor.entry_status(), None);
+        assert!(Tile::Stairs.is_safe());
+    }
+
+    #[test]
+    fn test_serialize() {
+        // セーブデータには整数で保存する
+        let map = vec![vec![Tile::Floor, Tile::Wall], vec![Tile::Water, Tile::Stairs]];
+        let json = serde_json::to_string(&map).unwrap();
+        assert_eq!(json, "[[0,1],[3,6]]");
+        assert_eq!(serde_json::from_str::<Vec<Vec<Tile>>>(&json).unwrap(), map);
+        assert!(serde_json::from_str::<Tile>("42").is_err());
+    }
+
+    #[test]
+    fn test_parse_map() {
+        let map = parse_map(&["#+", ".L"]);
+        assert_eq!(map[0], vec![Tile::Wall, Tile::Floor]);
+        assert_eq!(map[1], vec![Tile::Door(DoorState::Closed), Tile::Door(DoorState::Locked)]);
+    }
+
+    #[test]
+    fn test_tile_at() {
+        let map = vec![vec![Tile::Floor, Tile::Lava]];
+        assert_eq!(tile_at(&map, 0, 1), Tile::Lava);
+        assert_eq!(tile_at(&map, -1, 0), Tile::Wall);
+        assert_eq!(tile_at(&map, 1, 0), Tile::Wall);
+    }
+}
@@ -0,0 +1,178 @@
+//! 床に仕掛けられた罠を管理するモジュール
+//!
+//! 罠は見えない状態で置かれ、踏むか周りを調べると見つかる。
+//! Godot側には見つかった罠の位置だけを渡す。
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// 罠の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TrapKind {
+    /// 踏んだ者がダメージを受ける
+    Damage,
+    /// 踏んだ者を階層のどこかへ飛ばす
+    Teleport,
+    /// 階層の敵がすべて目を覚ましてやってくる
+    Alarm,
+    /// 踏んだ者が次の階層へ落ちる
+    Pit,
+}
+
+impl TrapKind {
+    pub const ALL: [TrapKind; 4] = [
+        TrapKind::Damage,
+        TrapKind::Teleport,
+        TrapKind::Alarm,
+        TrapKind::Pit,
+    ];
+
+    /// Godot側に渡すときやメッセージIDに使う文字列
+    pub fn key(&self) -> &'static str {
+        match self {
+            TrapKind::Damage => "damage",
+            TrapKind::Teleport => "teleport",
+            TrapKind::Alarm => "alarm",
+            TrapKind::Pit => "pit",
+        }
+    }
+
+    /// 置く罠の種類を選ぶ
+    ///
+    /// # Arguments
+    /// * `allow_pit` - 落とし穴を選んでよいかどうか、最後の階層では落ちる先がないので選ばない
+    pub fn choose<R: Rng + ?Sized>(allow_pit: bool, rng: &mut R) -> TrapKind {
+        let candidates: Vec<TrapKind> = TrapKind::ALL.iter()
+            .copied()
+            .filter(|kind| allow_pit || *kind != TrapKind::Pit)
+            .collect();
+        candidates[rng.gen_range(0..candidates.len())]
+    }
+}
+
+/// 1つの罠
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trap {
+    pub kind: TrapKind,
+    pub position: (i32, i32),
+    /// プレイヤーに見つかっているかどうか
+    #[serde(default)]
+    pub revealed: bool,
+}
+
+impl Trap {
+    pub fn new(kind: TrapKind, position: (i32, i32)) -> Self {
+        Self { kind, position, revealed: false }
+    }
+}
+
+/// 階層に置かれた罠の一覧
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Traps {
+    pub traps: Vec<Trap>,
+}
+
+impl Traps {
+    /// 罠を置く、同じマスにすでに罠がある場合は置かない
+    ///
+    /// # Returns
+    /// 置いた場合はtrue
+    pub fn add(&mut self, trap: Trap) -> bool {
+        if self.get(trap.position).is_some() {
+            return false;
+        }
+        self.traps.push(trap);
+        true
+    }
+
+    pub fn get(&self, position: (i32, i32)) -> Option<&Trap> {
+        self.traps.iter().find(|trap| trap.position == position)
+    }
+
+    pub fn clear(&mut self) {
+        self.traps.clear();
+    }
+
+    /// 罠を作動させる
+    ///
+    /// # Arguments
+    /// * `reveal` - 作動した罠を見つかったことにするかどうか、プレイヤーから見えていない場合はfalseにする
+    ///
+    /// # Returns
+    /// 罠があった場合はその種類
+    pub fn trigger(&mut self, position: (i32, i32), reveal: bool) -> Option<TrapKind> {
+        let trap = self.traps.iter_mut().find(|trap| trap.position == position)?;
+        trap.revealed |= reveal;
+        Some(trap.kind)
+    }
+
+    /// 指定した位置から半径以内にある、まだ見つかっていない罠を見つける
+    ///
+    /// # Returns
+    /// 新しく見つかった罠
+    pub fn reveal_around(&mut self, center: (i32, i32), radius: i32) -> Vec<Trap> {
+        let mut found = vec![];
+        for trap in self.traps.iter_mut() {
+            let (dx, dy) = (trap.position.0 - center.0, trap.position.1 - center.1);
+            if !trap.revealed && dx.abs() <= radius && dy.abs() <= radius {
+                trap.revealed = true;
+                found.push(*trap);
+            }
+        }
+        found
+    }
+
+    /// 見つかっている罠
+    pub fn known(&self) -> Vec<Trap> {
+        self.traps.iter().filter(|trap| trap.revealed).copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_trigger() {
+        let mut traps = Traps::default();
+        assert!(traps.add(Trap::new(TrapKind::Damage, (3, 4))));
+        assert!(!traps.add(Trap::new(TrapKind::Pit, (3, 4))));
+        assert_eq!(traps.trigger((0, 0), true), None);
+        // 見えていないところで作動した罠は見つからないまま
+        assert_eq!(traps.trigger((3, 4), false), Some(TrapKind::Damage));
+        assert!(traps.known().is_empty());
+        assert_eq!(traps.trigger((3, 4), true), Some(TrapKind::Damage));
+        assert_eq!(traps.known(), vec![Trap { kind: TrapKind::Damage, position: (3, 4), revealed: true }]);
+    }
+
+    #[test]
+    fn test_reveal_around() {
+        let mut traps = Traps::default();
+        traps.add(Trap::new(TrapKind::Alarm, (5, 5)));
+        traps.add(Trap::new(TrapKind::Teleport, (6, 4)));
+        traps.add(Trap::new(TrapKind::Pit, (8, 5)));
+        let found = traps.reveal_around((5, 5), 1);
+        assert_eq!(found.len(), 2);
+        // 一度見つけた罠は数えない
+        assert!(traps.reveal_around((5, 5), 1).is_empty());
+        assert_eq!(traps.known().len(), 2);
+        assert!(!traps.get((8, 5)).unwrap().revealed);
+    }
+
+    #[test]
+    fn test_choose() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!((0..100).all(|_| TrapKind::choose(false, &mut rng) != TrapKind::Pit));
+        assert!((0..100).any(|_| TrapKind::choose(true, &mut rng) == TrapKind::Pit));
+    }
+
+    #[test]
+    fn test_serialize() {
+        // 見つかったかどうかを持たない古いデータも読み込める
+        let traps: Traps = serde_json::from_str(r#"[{"kind": "Pit", "position": [1, 2]}]"#).unwrap();
+        assert_eq!(traps.traps, vec![Trap::new(TrapKind::Pit, (1, 2))]);
+    }
+}
@@ -0,0 +1,229 @@
+//! 用意した型の部屋を並べてマップを生成するモジュール
+//!
+//! マップを区画に分け、区画ごとに型を1つ選んで置く。型は裏返して置くこともある。
+//! 置いた部屋は順に通路でつなぎ、最後に部屋の入口に扉を置く。
+
+use crate::map_generator::{
+    carve_corridor,
+    place_doors,
+    room_around,
+    BSPNodeParams,
+    MapGenerator,
+};
+use crate::tile::Tile;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// 部屋の型、'#'が壁、'.'が床、'~'が水、'%'が溶岩、':'が裂け目、' 'はもとの地形を残す
+pub const PREFABS: &[&[&str]] = &[
+    // 柱の並んだ広間
+    &[
+        "###########",
+        "#.........#",
+        "#.#.#.#.#.#",
+        "#.........#",
+        "#.#.#.#.#.#",
+        "#.........#",
+        "###########",
+    ],
+    // 中央に泉のある部屋
+    &[
+        "#########",
+        "#.......#",
+        "#..~~~..#",
+        "#.~~~~~.#",
+        "#.~~~~~.#",
+        "#..~~~..#",
+        "#.......#",
+        "#########",
+    ],
+    // 溶岩の堀に囲まれた宝物庫
+    &[
+        "###########",
+        "#.........#",
+        "#.%%%%%%%.#",
+        "#.%.....%.#",
+        "#.%.....%.#",
+        "#.......%.#",
+        "#.%.....%.#",
+        "#.%%%%%%%.#",
+        "#.........#",
+        "###########",
+    ],
+    // 裂け目の走る部屋、裂け目の間の通路を通って渡る
+    &[
+        "###########",
+        "#.........#",
+        "#.:::.:::.#",
+        "#.:::.:::.#",
+        "#.........#",
+        "#.:::.:::.#",
+        "#.:::.:::.#",
+        "#.........#",
+        "###########",
+    ],
+    // 十字の部屋
+    &[
+        "   #####   ",
+        "   #...#   ",
+        "   #...#   ",
+        "####...####",
+        "#.........#",
+        "#.........#",
+        "#.........#",
+        "####...####",
+        "   #...#   ",
+        "   #...#   ",
+        "   #####   ",
+    ],
+    // 小部屋
+    &[
+        "#######",
+        "#.....#",
+        "#.....#",
+        "#.....#",
+        "#######",
+    ],
+];
+
+/// 型の文字に対応する地形、もとの地形を残す場合はNone
+fn prefab_tile(c: char) -> Option<Tile> {
+    match c {
+        '#' => Some(Tile::Wall),
+        '.' => Some(Tile::Floor),
+        '~' => Some(Tile::Water),
+        '%' => Some(Tile::Lava),
+        ':' => Some(Tile::Chasm),
+        _ => None,
+    }
+}
+
+/// 型を組み合わせてマップを生成するアルゴリズム
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VaultGenerator {
+    /// 1つの型を置く区画の大きさ
+    pub slot_size: i32,
+}
+
+impl Default for VaultGenerator {
+    fn default() -> Self {
+        Self { slot_size: 16 }
+    }
+}
+
+impl VaultGenerator {
+    /// 型をマップに置く
+    ///
+    /// # Arguments
+    /// * `origin` - 型の左上を置く位置
+    /// * `flip` - 左右と上下をそれぞれ裏返すかどうか
+    fn stamp(dungeon: &mut [Vec<Tile>], prefab: &[&str], origin: (i32, i32), flip: (bool, bool)) {
+        let (width, height) = prefab_size(prefab);
+        for (j, row) in prefab.iter().enumerate() {
+            for (i, c) in row.chars().enumerate() {
+                let Some(tile) = prefab_tile(c) else {
+                    continue;
+                };
+                let x = if flip.0 { width - 1 - i as i32 } else { i as i32 };
+                let y = if flip.1 { height - 1 - j as i32 } else { j as i32 };
+                dungeon[(origin.0 + x) as usize][(origin.1 + y) as usize] = tile;
+            }
+        }
+    }
+}
+
+/// 型の幅と高さ
+fn prefab_size(prefab: &[&str]) -> (i32, i32) {
+    let width = prefab.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+    (width as i32, prefab.len() as i32)
+}
+
+impl MapGenerator for VaultGenerator {
+    fn generate(&self, width: i32, height: i32, rng: &mut StdRng) -> (Vec<Vec<Tile>>, Vec<BSPNodeParams>) {
+        let mut dungeon = vec![vec![Tile::Wall; height as usize]; width as usize];
+        let slot_size = self.slot_size.max(1);
+        let mut rooms = vec![];
+        for slot_x in (0..width).step_by(slot_size as usize) {
+            for slot_y in (0..height).step_by(slot_size as usize) {
+                // 外周の壁を残して区画に収まる型だけを選ぶ
+                let left = slot_x.max(1);
+                let top = slot_y.max(1);
+                let right = (slot_x + slot_size).min(width - 1);
+                let bottom = (slot_y + slot_size).min(height - 1);
+                let candidates: Vec<&&[&str]> = PREFABS.iter()
+                    .filter(|prefab| {
+                        let (w, h) = prefab_size(prefab);
+                        w <= right - left && h <= bottom - top
+                    })
+                    .collect();
+                let Some(prefab) = candidates.choose(rng) else {
+                    continue;
+                };
+                let (w, h) = prefab_size(prefab);
+                let x = rng.gen_range(left..=right - w);
+                let y = rng.gen_range(top..=bottom - h);
+                Self::stamp(&mut dungeon, prefab, (x, y), (rng.gen(), rng.gen()));
+                rooms.extend(room_around(&dungeon, x, y, w, h));
+            }
+        }
+
+        // 置いた順に隣の部屋と通路でつなぐ
+        let centers: Vec<(i32, i32)> = rooms.iter().map(|room| (room.room_center_x, room.room_center_y)).collect();
+        for pair in centers.windows(2) {
+            carve_corridor(&mut dungeon, pair[0], pair[1], rng);
+        }
+        // 通路と部屋の境目に扉を置く
+        place_doors(&mut dungeon);
+        (dungeon, rooms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_generator::unreachable_rooms;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_prefabs() {
+        // どの型も区画に収まり、部屋の中心にできる床がある
+        for prefab in PREFABS {
+            let (w, h) = prefab_size(prefab);
+            assert!(w <= 14 && h <= 14);
+            assert!(prefab.iter().all(|row| row.chars().all(|c| c == ' ' || prefab_tile(c).is_some())));
+            let mut dungeon = vec![vec![Tile::Wall; h as usize]; w as usize];
+            VaultGenerator::stamp(&mut dungeon, prefab, (0, 0), (false, false));
+            assert!(room_around(&dungeon, 0, 0, w, h).is_some());
+        }
+    }
+
+    #[test]
+    fn test_stamp_flipped() {
+        let prefab: &[&str] = &["#.:", "~%."];
+        let mut dungeon = vec![vec![Tile::Wall; 2]; 3];
+        VaultGenerator::stamp(&mut dungeon, prefab, (0, 0), (true, true));
+        assert_eq!(dungeon[0][0], Tile::Floor);
+        assert_eq!(dungeon[1][0], Tile::Lava);
+        assert_eq!(dungeon[2][0], Tile::Water);
+        assert_eq!(dungeon[0][1], Tile::Chasm);
+        assert_eq!(dungeon[1][1], Tile::Floor);
+        assert_eq!(dungeon[2][1], Tile::Wall);
+    }
+
+    #[test]
+    fn test_vault_generator() {
+        let generator = VaultGenerator::default();
+        for seed in 0..10 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (dungeon, rooms) = generator.generate(64, 64, &mut rng);
+            assert_eq!(rooms.len(), 16);
+            for room in &rooms {
+                assert!(room.x >= 1 && room.x + room.width <= 63);
+                assert_eq!(dungeon[room.room_center_x as usize][room.room_center_y as usize], Tile::Floor);
+            }
+            assert!(unreachable_rooms(&dungeon, &rooms).is_empty());
+        }
+    }
+}
@@ -1,5 +1,11 @@
 extends GameMaster
 
+# get_game_stateの値
+const STATE_PLAYING = 0
+const STATE_DEAD = 1
+const STATE_DESCENDING = 2
+const STATE_WON = 3
+
 var current_scene = null
 
 # Called when the node enters the scene tree for the first time.
@@ -17,10 +23,25 @@ func goto_scene(path):
 
 	# The solution is to defer the load to a later time, when
 	# we can be sure that no code from the current scene is running:
-	next_level()
+	# 次の階層のマップはdescendで生成される、最後の階層を踏破した場合は移動しない
+	if !descend():
+		return
+	call_deferred("_deferred_goto_scene", path)
+
+
+# 落とし穴に落ちて次の階層へ移動する
+func fall_to_scene(path):
+	if !fall_into_pit():
+		return
 	call_deferred("_deferred_goto_scene", path)
 
 
+# 最初の階層から始め直す
+func restart():
+	restart_run()
+	call_deferred("_deferred_goto_scene", "res://main.tscn")
+
+
 func _deferred_goto_scene(path):
 	# It is now safe to remove the current scene.
 	current_scene.free()
@@ -47,16 +47,31 @@ var current_ui_state = UIState.Player
 
 # アイテム選択UI用の変数
 var selected_item
+# アイテムを使う代わりに投げるかどうか
+var throwing_item: bool
 
 # Called when the node enters the scene tree for the first time.
 func _ready():
 	goal_reached = false
 	current_ui_state = UIState.Player
 	gamemaster = get_node("/root/GlobalGameMaster")
+	# ターンの結果はシグナルで受け取る
+	gamemaster.mob_defeated.connect(_on_mob_defeated)
+	gamemaster.player_damaged.connect(_on_player_damaged)
+	gamemaster.level_up.connect(_on_level_up)
+	gamemaster.game_over.connect(_on_game_over)
+	gamemaster.goal_reached.connect(_on_goal_reached)
+	gamemaster.status_changed.connect(_on_status_changed)
+	gamemaster.tile_changed.connect(_on_tile_changed)
+	gamemaster.trap_revealed.connect(_on_trap_revealed)
 	# TODO: マップ初期化の際に現在の階層を考慮したレベルデザインを行う。
-	gamemaster.initialize_level(64, 64)
+	# 次の階層へ移動した場合はすでに生成されている
+	if !gamemaster.is_level_ready():
+		gamemaster.initialize_level(64, 64)
 	var gridmap = get_node("Map")
 	gridmap.initialize_map(gamemaster)
+	gridmap.update_traps(gamemaster)
+	gridmap.update_fog(gamemaster)
 	#dungeon_width = gridmap.dungeon_width
 	#dungeon_height = gridmap.dungeon_height
 	#print("dungeon_size: ", dungeon_width, " X ", dungeon_height)
@@ -74,35 +89,20 @@ func _ready():
 	#gamemaster.give_health_potion_to_player()
 	#gamemaster.player_attack()
 	
-	# 落ちているアイテムの情報を取得
+	# 落ちているアイテムの情報を取得、見えているものだけが返ってくる
 	var item_positions = gamemaster.get_dropped_item_positions()
 	var item_ids = gamemaster.get_dropped_item_ids()
-	# TODO: item_idを取得
 	for i in range(len(item_positions)):
-		var pos = item_positions[i]
-		var item_id = item_ids[i]
-		print("Item ", item_id, " pos: ", pos)
-		var item_inst = item_scene.instantiate()
-		item_inst.item_id = item_id
-		item_inst.transform = item_inst.transform.translated(
-			get_node("Map").grid_to_geometry(pos))
-		add_child(item_inst)
-		item_list.append(item_inst)
-
-	# 敵の情報を取得
+		print("Item ", item_ids[i], " pos: ", item_positions[i])
+		add_item_instance(item_ids[i], item_positions[i])
+
+	# 敵の情報を取得、見えているものだけが返ってくる
 	var mob_positions = gamemaster.get_mob_positions()
 	var mob_ids = gamemaster.get_mob_ids()
+	var mob_models = gamemaster.get_mob_models()
 	for i in range(len(mob_positions)):
-		var pos = mob_positions[i]
-		var mob_id = mob_ids[i]
-		print("Mob ", mob_id, " pos: ", pos)
-		var mob_inst = mob_scene.instantiate()
-		mob_inst.mob_id = mob_id
-		mob_inst.current_position_2d = pos
-		mob_inst.init_position(
-			get_node("Map").grid_to_geometry(pos))
-		add_child(mob_inst)
-		mob_list.append(mob_inst)
+		print("Mob ", mob_ids[i], " pos: ", mob_positions[i])
+		add_mob_instance(mob_ids[i], mob_positions[i], mob_models[i])
 
 	# ゴールを表示
 	goal_position = gamemaster.get_goal_position()
@@ -116,7 +116,7 @@ func _ready():
 	
 	# UIに文字を表示
 	message_label = get_node("Control/MessageArea/MessageLabel")
-	message_label.text = "現在" + str(gamemaster.current_level) + "階です。"
+	message_label.text = gamemaster.get_message("current_floor", {"level": gamemaster.current_level})
 	status_label = get_node("Control/StatusArea/StatusLabel")
 	item_label = get_node("Control/ItemArea/ItemLabel")
 	selected_item_label = get_node("Control/ItemArea/SelectedItemLabel")
@@ -132,9 +132,75 @@ func _ready():
 	command_area.visible = false
 	selected_idx = 0
 
+# 落ちているアイテムを表示する
+func add_item_instance(item_id, pos):
+	var item_inst = item_scene.instantiate()
+	item_inst.item_id = item_id
+	item_inst.transform = item_inst.transform.translated(
+		get_node("Map").grid_to_geometry(pos))
+	add_child(item_inst)
+	item_list.append(item_inst)
+
+# 敵を表示する
+func add_mob_instance(mob_id, pos, model):
+	var mob_inst = mob_scene.instantiate()
+	mob_inst.mob_id = mob_id
+	mob_inst.current_position_2d = pos
+	mob_inst.init_position(
+		get_node("Map").grid_to_geometry(pos))
+	add_child(mob_inst)
+	# 種類に応じた見た目にする
+	mob_inst.set_model(model)
+	mob_list.append(mob_inst)
+
+# 倒された敵を退場させる
+func _on_mob_defeated(mob_id, _pos):
+	for mob_idx in range(len(mob_list)):
+		var mob = mob_list[mob_idx]
+		if mob.mob_id == mob_id:
+			mob.queue_free()
+			mob_list.remove_at(mob_idx)
+			break
+
+# ダメージを受けたらステータスを更新
+func _on_player_damaged(_attacker_id, _damage, _hp):
+	update_status_label()
+
+# レベルが上がったらステータスを更新
+func _on_level_up(_level):
+	update_status_label()
+
+# ゴールに到達したら次の階層へ移動するかどうかを問い合わせる
+func _on_goal_reached(_pos):
+	goal_reached = true
+
+# 状態異常が変わったらステータスを更新
+func _on_status_changed(_actor_id):
+	update_status_label()
+
+# 扉が開いたらマップの見た目を更新する
+func _on_tile_changed(pos, tile):
+	var gridmap = get_node("Map")
+	gridmap.update_tile(gamemaster, pos, tile)
+	gridmap.update_fog(gamemaster)
+
+# 罠が見つかったらマップに表示する
+func _on_trap_revealed(_pos):
+	var gridmap = get_node("Map")
+	gridmap.update_traps(gamemaster)
+	gridmap.update_fog(gamemaster)
+
+# 力尽きたら原因を表示する
+func _on_game_over():
+	message_label.text += gamemaster.get_cause_of_death() + "\n"
+	message_label.text += gamemaster.get_message("restart_hint", {})
+
 # ステータスを表示
 func update_status_label():
 	status_label.text = gamemaster.get_player_status()
+	# かかっている状態異常を残りターン数とともに表示
+	for effect in gamemaster.get_player_status_effects():
+		status_label.text += "\n%s (%d)" % [effect["name"], effect["turns"]]
 
 # 所持アイテム一覧を表示
 func update_item_label():
@@ -151,9 +217,11 @@ func update_item_label():
 func update_command_list():
 	command_list.clear()
 	command_list.append("アイテムを使う")
+	command_list.append("アイテムを投げる")
 	command_list.append("装備変更")
+	command_list.append("周りを調べる")
 	# 次の階層へは自分がゴールの上にいるときだけ移動できる
-	if goal_position == player_position:
+	if gamemaster.is_player_on_goal():
 		command_list.append("次の階層へ移動")
 	command_list.append("ゲーム終了")
 
@@ -171,57 +239,68 @@ func update_command_label():
 		command_label.text += (command + "\n")
 
 # モブのアニメーションを実行
+# 視界の外にいる敵は非表示にし、初めて視界に入った敵は追加する
 func process_mob_animation():
 	var mob_positions = gamemaster.get_mob_positions()
 	var mob_ids = gamemaster.get_mob_ids()
 	var mob_directions = gamemaster.get_mob_directions()
+	var mob_models = gamemaster.get_mob_models()
 	for i in range(len(mob_positions)):
 		var pos = mob_positions[i]
 		var mob_id = mob_ids[i]
 		var mob_dir = mob_directions[i]
+		var found = false
 		for mob_inst in mob_list:
 			if mob_inst.mob_id == mob_id:
+				found = true
 				mob_inst.set_next_abs_rotation(mob_dir)	
-				if mob_inst.current_position_2d != pos:
+				if !mob_inst.visible:
+					# 見えていない間に移動していた場合はその位置に直接置く
+					mob_inst.visible = true
+					mob_inst.init_position(get_node("Map").grid_to_geometry(pos))
+					mob_inst.current_position_2d = pos
+				elif mob_inst.current_position_2d != pos:
 					print("mob ", mob_id, " moved from ", mob_inst.current_position_2d, " to ", pos)
 					mob_inst.set_next_position(
 						get_node("Map").grid_to_geometry(pos))
 					mob_inst.current_position_2d = pos
 				break;
-
-# アイテムが拾われる可能性がある行動が起きたとき、アイテムを削除するかどうか
-func remove_dropped_items():
-	var dropped_item_removed_ids = gamemaster.get_dropped_item_removed_ids()
-	for id in dropped_item_removed_ids:
-		for item_idx in range(len(item_list)):
-			var item = item_list[item_idx]
-			if item.item_id == id:
-				item.queue_free()
-				item_list.remove_at(item_idx)
-				break;
-
-# アイテムが落とされる可能性がある行動が起きたとき、アイテムを追加するかどうか
-func add_dropped_items():
-	var dropped_item_added_ids = gamemaster.get_dropped_item_added_ids()
+		if !found:
+			add_mob_instance(mob_id, pos, mob_models[i])
+	for mob_inst in mob_list:
+		if !mob_ids.has(mob_inst.mob_id):
+			mob_inst.visible = false
+
+# 表示しているアイテムを、見えているアイテムに合わせる
+# 拾われたアイテムや視界の外に出たアイテムは削除し、落とされたアイテムや視界に入ったアイテムは追加する
+func sync_dropped_items():
 	var dropped_item_ids = gamemaster.get_dropped_item_ids()
 	var dropped_item_pos = gamemaster.get_dropped_item_positions()
-	for item_id in dropped_item_added_ids:
-		for item_idx in range(len(dropped_item_ids)):
-			if dropped_item_ids[item_idx] == item_id:
-				var pos = dropped_item_pos[item_idx]
-				var item_inst = item_scene.instantiate()
-				item_inst.item_id = item_id
-				item_inst.transform = item_inst.transform.translated(
-					get_node("Map").grid_to_geometry(pos))
-				add_child(item_inst)
-				item_list.append(item_inst)
+	for item_idx in range(len(item_list) - 1, -1, -1):
+		var item = item_list[item_idx]
+		if !dropped_item_ids.has(item.item_id):
+			item.queue_free()
+			item_list.remove_at(item_idx)
+	for i in range(len(dropped_item_ids)):
+		var found = false
+		for item in item_list:
+			if item.item_id == dropped_item_ids[i]:
+				found = true
 				break
+		if !found:
+			add_item_instance(dropped_item_ids[i], dropped_item_pos[i])
 
 # Called every frame. 'delta' is the elapsed time since the previous frame.
 func _process(delta):
 	# playerに現在入力を受け付けていいかどうか問い合わせる
 	var player = get_node("Player")
 	var gridmap = get_node("Map")
+	# 力尽きた後や踏破した後は、決定ボタンで最初から始め直す
+	var state = gamemaster.get_game_state()
+	if state == gamemaster.STATE_DEAD or state == gamemaster.STATE_WON:
+		if Input.is_action_just_pressed("apply_button"):
+			gamemaster.restart()
+		return
 	if !player.anim_playing:
 		# UIを開いている場合は、コマンド選択モードにする。
 		if current_ui_state == UIState.Command:
@@ -235,9 +314,29 @@ func _process(delta):
 				# 選択されているコマンドを実行
 				if command_list[selected_idx] == "次の階層へ移動":
 					gamemaster.goto_scene("res://main.tscn")
-				elif command_list[selected_idx] == "アイテムを使う":
+					if gamemaster.get_game_state() == gamemaster.STATE_WON:
+						command_area.visible = false
+						message_label.text = ""
+						for msg_str in gamemaster.message:
+							message_label.text += (msg_str + "\n")
+						message_label.text += gamemaster.get_message("restart_hint", {})
+				elif command_list[selected_idx] == "周りを調べる":
+					# 隠れている罠を探す、ターンを消費する
+					gamemaster.clear_message()
+					gamemaster.player_search()
+					gamemaster.process()
+					process_mob_animation()
+					sync_dropped_items()
+					message_label.text = ""
+					for msg_str in gamemaster.message:
+						message_label.text += (msg_str + "\n")
+					update_status_label()
+					current_ui_state = UIState.Player
+					command_area.visible = false
+				elif command_list[selected_idx] == "アイテムを使う" or command_list[selected_idx] == "アイテムを投げる":
 					# アイテム選択UIに遷移
 					selected_item = 0
+					throwing_item = command_list[selected_idx] == "アイテムを投げる"
 					current_ui_state = UIState.ItemSelection
 					update_item_label()
 			elif Input.is_action_just_pressed("cancel_button"):
@@ -253,15 +352,24 @@ func _process(delta):
 				update_item_label()
 			elif Input.is_action_just_pressed("apply_button"):
 				# 実行ボタンを押したらアイテムを使ってすべてのコマンド用UIを閉じる
-				if gamemaster.player_can_use_item(selected_item):
+				var acted = false
+				if throwing_item:
+					# 向いている方向へ投げる
+					gamemaster.clear_message()
+					acted = gamemaster.player_throw_item(selected_item)
+				elif gamemaster.player_can_use_item(selected_item):
 					gamemaster.clear_message()
 					gamemaster.player_use_item(selected_item)
+					acted = true
+				if acted:
 					# ターンを消費する
 					gamemaster.process()
 					# TODO: プレイヤーの回復アニメーションを指定
+					# TODO: 投げた場合はget_eventsのprojectile_firedの軌道に沿って飛ぶアニメーションを指定
 					player.set_action(0)
 					# godot側でアニメーションを実行させる。
 					process_mob_animation()
+					sync_dropped_items()
 					
 					# メッセージの表示
 					message_label.text = ""
@@ -275,7 +383,7 @@ func _process(delta):
 					command_area.visible = false
 					
 				else:
-					message_label.text = "そのアイテムは使えません。"
+					message_label.text = gamemaster.get_message("item_unusable", {})
 
 			elif Input.is_action_just_pressed("cancel_button"):
 				# キャンセルボタンを押したらコマンド選択に戻る
@@ -284,9 +392,27 @@ func _process(delta):
 
 
 		elif current_ui_state == UIState.Player:
+			# 落とし穴に落ちた場合は、メッセージを表示したまま決定ボタンで次の階層へ移動する
+			# 落ちている間はほかの行動もコマンド選択もできない、セーブデータから再開した場合も同じ
+			if gamemaster.is_falling():
+				if Input.is_action_just_pressed("apply_button"):
+					gamemaster.fall_to_scene("res://main.tscn")
+
+			# 眠っている間は行動を選べず、決定ボタンでターンを進める
+			elif !gamemaster.player_can_act():
+				if Input.is_action_just_pressed("apply_button"):
+					gamemaster.clear_message()
+					gamemaster.process()
+					process_mob_animation()
+					sync_dropped_items()
+					message_label.text = ""
+					for msg_str in gamemaster.message:
+						message_label.text += (msg_str + "\n")
+					update_status_label()
+
 			# コマンド選択UIを経由して行動を決定する。
 			# キャンセルボタンで表示切替を行う。
-			if Input.is_action_just_pressed("cancel_button"):
+			elif Input.is_action_just_pressed("cancel_button"):
 				current_ui_state = UIState.Command
 				command_area.visible = true
 				# UIを開いた瞬間にUI用のメッセージを生成する
@@ -373,16 +499,22 @@ func _process(delta):
 						# mapに目標位置に移動可能かどうか問い合わせる
 						if gamemaster.player_move(next_player_position):
 							# 移動可能だった場合、gamemaster内部の状態はすでに移動済みである。
+							# 混乱していると思った場所とは違うところへ進むので、移動先と向きは問い合わせ直す
+							next_player_position = gamemaster.get_player_position()
+							player_direction = gamemaster.get_player_direction()
+							player.set_next_abs_rotation(player_direction)
 							# gamemaster側でターンを消費
 							gamemaster.process()
 							# godot側playerを内部的に移動させる
 							player.set_next_position(gridmap.grid_to_geometry(next_player_position))
 							player_position = next_player_position
+							# 視界が変わるので探索済みの範囲を表示し直す
+							gridmap.update_fog(gamemaster)
 							# アニメーションを実行させる。
 							process_mob_animation()
 
-							# 拾われたアイテムの処理
-							remove_dropped_items()
+							# 拾われたアイテムや視界に入ったアイテムの処理
+							sync_dropped_items()
 
 							# メッセージの表示
 							message_label.text = ""
@@ -391,34 +523,24 @@ func _process(delta):
 							update_status_label()
 							update_item_label()
 
-							# 移動した結果ゴールに到達したときはgoal_reachedをtrueに
-							if goal_position == player_position:
-								goal_reached = true
-
 						else:
 							print("position ", next_player_position, " is invalid, unable to move.")
+							# 鍵のかかった扉に阻まれた場合などはメッセージだけ表示する
+							message_label.text = ""
+							for msg_str in gamemaster.message:
+								message_label.text += (msg_str + "\n")
 					elif is_action:
 						# プレイヤーがターンを消費する行動を行う場合
 						gamemaster.clear_message()
 						# 今回はattackのみ
 						player.set_action(0)
 						gamemaster.process()
-						# この結果、倒されたmobがいる場合はそのmobを退場させる。
-						var defeated_ids = gamemaster.get_defeated_mob_ids()
-						for id in defeated_ids:
-							#print("defated mob id: ", id)
-							for mob_idx in range(len(mob_list)):
-								var mob = mob_list[mob_idx]
-								#print("  mob[" , mob_idx, "] id: ", mob.mob_id)
-								if mob.mob_id == id:
-									mob.queue_free()
-									mob_list.remove_at(mob_idx)
-									break
+						# 倒されたmobはmob_defeatedシグナルを受けて退場させる。
 						# godot側でアニメーションを実行させる。
 						process_mob_animation()
 
 						# 落とされたアイテムの処理
-						add_dropped_items()
+						sync_dropped_items()
 
 						# メッセージの表示
 						message_label.text = ""
@@ -2,10 +2,32 @@ extends Node3D
 
 @export var floor_chip: PackedScene
 @export var wall_chip: PackedScene
+# 床以外の地形のチップ、指定しない場合は床のチップで代用する
+# 閉じた扉は指定しない場合は壁のチップで代用する
+@export var door_chip: PackedScene
+@export var open_door_chip: PackedScene
+@export var water_chip: PackedScene
+@export var lava_chip: PackedScene
+@export var stairs_chip: PackedScene
+@export var trap_chip: PackedScene
 @export var chip_size: float = 1.0
 
+# dungeon_map_1dの値、Rust側のTile::to_i32と合わせる
+const TILE_FLOOR = 0
+const TILE_WALL = 1
+const TILE_DOOR = 2
+const TILE_WATER = 3
+const TILE_LAVA = 4
+const TILE_CHASM = 5
+const TILE_STAIRS = 6
+# 7は以前の罠の値で、今は使わない
+const TILE_OPEN_DOOR = 8
+const TILE_LOCKED_DOOR = 9
+
 var x0 = 0.0
 var y0 = 0.0
+# dungeon_map_1dと同じインデックスで配置したチップを保持する
+var chips = {}
 
 # Called when the node enters the scene tree for the first time.
 func _ready():
@@ -26,14 +48,65 @@ func initialize_map(gamemaster):
 		for y in range(gamemaster.dungeon_height):
 			idx = x + y * gamemaster.dungeon_width
 			var pos = Vector3(x0 + x * chip_size, 0, y0 + y * chip_size)
-			if gamemaster.dungeon_map_1d[idx] == 0:
-				var fc = floor_chip.instantiate()
-				fc.transform = fc.transform.translated(pos)
-				add_child(fc)
-			elif gamemaster.dungeon_map_1d[idx] == 1:
-				var wc = wall_chip.instantiate()
-				wc.transform = wc.transform.translated(pos)
-				add_child(wc)
+			place_chip(idx, pos, gamemaster.dungeon_map_1d[idx])
+
+# 指定したインデックスにチップを置く、すでに置いてあるチップは取り除く
+func place_chip(idx, pos, tile):
+	place_scene(idx, pos, chip_for_tile(tile))
+
+# 指定したインデックスにシーンを置く、すでに置いてあるチップは取り除く
+func place_scene(idx, pos, chip_scene):
+	var was_visible = true
+	if chips.has(idx):
+		was_visible = chips[idx].visible
+		chips[idx].queue_free()
+		chips.erase(idx)
+	if chip_scene == null:
+		return
+	var chip = chip_scene.instantiate()
+	chip.transform = chip.transform.translated(pos)
+	chip.visible = was_visible
+	add_child(chip)
+	chips[idx] = chip
+
+# 扉が開くなどして地形が変わったマスのチップを置き換える
+func update_tile(gamemaster, grid_pos, tile):
+	var idx = grid_pos.x + grid_pos.y * gamemaster.dungeon_width
+	place_chip(idx, grid_to_geometry(grid_pos), tile)
+
+# 見つかっている罠のマスを罠のチップに置き換える、罠は地形とは別に管理している
+func update_traps(gamemaster):
+	var chip_scene = trap_chip if trap_chip != null else floor_chip
+	for trap in gamemaster.get_known_traps():
+		var grid_pos = trap["position"]
+		var idx = grid_pos.x + grid_pos.y * gamemaster.dungeon_width
+		place_scene(idx, grid_to_geometry(grid_pos), chip_scene)
+
+# 地形に対応するチップ、裂け目のように何も置かない地形の場合はnull
+func chip_for_tile(tile):
+	match tile:
+		TILE_WALL:
+			return wall_chip
+		TILE_CHASM:
+			return null
+		TILE_DOOR, TILE_LOCKED_DOOR:
+			return door_chip if door_chip != null else wall_chip
+		TILE_OPEN_DOOR:
+			return open_door_chip if open_door_chip != null else floor_chip
+		TILE_WATER:
+			return water_chip if water_chip != null else floor_chip
+		TILE_LAVA:
+			return lava_chip if lava_chip != null else floor_chip
+		TILE_STAIRS:
+			return stairs_chip if stairs_chip != null else floor_chip
+		_:
+			return floor_chip
+
+# 探索済みのチップだけを表示する
+func update_fog(gamemaster):
+	var explored = gamemaster.get_explored_map_1d()
+	for idx in chips:
+		chips[idx].visible = explored[idx] == 1
 
 # 外部からマップ上のグリッド座標->ユークリッド座標への変換を問い合わせる
 func grid_to_geometry(pos):
@@ -8,6 +8,10 @@ var current_position_2d: Vector2i
 @export var speed: float = 2.0
 @export var angular_speed: float = 2.0
 
+# 種類ごとの見た目、キーはGameMasterのget_mob_modelsが返す値に対応する
+# 登録されていないキーの場合はデフォルトの見た目のままにする
+@export var models: Dictionary = {}
+
 var target_pos: Vector3
 var anim_playing: bool = false
 var current_rotation: float = 0.0
@@ -24,6 +28,17 @@ func init_position(pos):
 	transform.origin = pos
 	velocity = Vector3.ZERO
 
+func set_model(model_key):
+	if not models.has(model_key):
+		return
+	var pivot = get_node("Pivot")
+	var default_model = pivot.get_node("mob")
+	var model_inst = models[model_key].instantiate()
+	model_inst.transform = default_model.transform
+	pivot.remove_child(default_model)
+	default_model.queue_free()
+	pivot.add_child(model_inst)
+
 func set_next_position(pos):
 	target_pos = pos
 